@@ -0,0 +1,246 @@
+// Cheats for any Vm with debug access: a memory search that finds where a
+// game keeps a value, and freeze codes that write a value back every frame.
+// Cheat lists are kept in a directory with one file per ROM, named after a
+// hash of the ROM so renamed copies share their cheats.
+extern crate chip8core;
+#[cfg(test)]
+extern crate chip8vm;
+mod search;
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{ ErrorKind, Read, Write };
+use std::path::{ Path, PathBuf };
+use std::slice::Chunks;
+use chip8core::{ DebugVm, InstructionError, Key, RomError, Vm };
+
+pub use search::{ Compare, Search };
+
+const HEADER: &str = "# address value on|off name";
+
+// Writes value to address every frame while enabled
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cheat {
+    pub address: usize,
+    pub value: u8,
+    pub enabled: bool,
+    pub name: String,
+}
+
+impl Cheat {
+    pub fn new(address: usize, value: u8) -> Cheat {
+        Cheat { address, value, enabled: true, name: String::new() }
+    }
+}
+
+#[derive(Debug)]
+pub enum CheatError {
+    Io(io::Error),
+    InvalidLine(usize),
+}
+
+impl fmt::Display for CheatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CheatError::Io(ref e) => write!(f, "{}", e),
+            CheatError::InvalidLine(line) => write!(f, "Invalid cheat on line {}", line),
+        }
+    }
+}
+
+impl Error for CheatError {
+    fn description(&self) -> &str {
+        match *self {
+            CheatError::Io(_) => "I/O error",
+            CheatError::InvalidLine(_) => "Invalid cheat",
+        }
+    }
+}
+
+impl From<io::Error> for CheatError {
+    fn from(e: io::Error) -> CheatError {
+        CheatError::Io(e)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CheatList {
+    pub cheats: Vec<Cheat>,
+}
+
+impl CheatList {
+    // Freezes the enabled cheats. Addresses outside memory, and those that
+    // already hold the value, are skipped, so Vms that cache code don't
+    // see a write every frame.
+    pub fn apply(&self, vm: &mut dyn DebugVm) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            if vm.memory().get(cheat.address) != Some(&cheat.value) {
+                let _ = vm.write_memory(cheat.address, &[cheat.value]);
+            }
+        }
+    }
+
+    // One cheat per line as "0300 09 on Infinite lives", blank lines and
+    // lines starting with # are skipped
+    pub fn parse(text: &str) -> Result<CheatList, CheatError> {
+        let mut cheats = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            cheats.push(parse_cheat(line).ok_or(CheatError::InvalidLine(index + 1))?);
+        }
+        Ok(CheatList { cheats })
+    }
+
+    // A missing file is an empty list
+    pub fn load(path: &Path) -> Result<CheatList, CheatError> {
+        let mut text = String::new();
+        match File::open(path) {
+            Ok(mut file) => file.read_to_string(&mut text)?,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Default::default()),
+            Err(e) => return Err(e.into()),
+        };
+        CheatList::parse(&text)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), CheatError> {
+        let mut file = File::create(path)?;
+        write!(file, "{}", self)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for CheatList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for cheat in self.cheats.iter() {
+            let state = if cheat.enabled { "on" } else { "off" };
+            write!(f, "{:04X} {:02X} {}", cheat.address, cheat.value, state)?;
+            if cheat.name.is_empty() {
+                writeln!(f)?;
+            } else {
+                writeln!(f, " {}", cheat.name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_cheat(line: &str) -> Option<Cheat> {
+    let mut parts = line.splitn(4, char::is_whitespace);
+    let address = usize::from_str_radix(parts.next()?, 16).ok()?;
+    let value = u8::from_str_radix(parts.next()?, 16).ok()?;
+    let enabled = match parts.next()? {
+        "on" => true,
+        "off" => false,
+        _ => return None,
+    };
+    let name = parts.next().unwrap_or("").trim().to_string();
+    Some(Cheat { address, value, enabled, name })
+}
+
+// FNV-1a hash of the ROM's bytes
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// Where the cheats for a ROM are kept in a cheat directory
+pub fn cheat_file(dir: &Path, rom: &[u8]) -> PathBuf {
+    dir.join(format!("{:016x}.cheats", rom_hash(rom)))
+}
+
+// Applies the cheats after every step, for running without a frontend
+// that knows about them
+pub struct CheatVm<V> {
+    vm: V,
+    pub cheats: CheatList,
+}
+
+impl<V: Vm> CheatVm<V> {
+    pub fn new(vm: V, cheats: CheatList) -> CheatVm<V> {
+        CheatVm { vm, cheats }
+    }
+
+    pub fn vm(&mut self) -> &mut V {
+        &mut self.vm
+    }
+
+    pub fn into_inner(self) -> V {
+        self.vm
+    }
+}
+
+impl<V: Vm> Vm for CheatVm<V> {
+    fn step(&mut self, time: f64) -> Result<(), InstructionError> {
+        self.vm.step(time)?;
+        if let Some(vm) = self.vm.debug() {
+            self.cheats.apply(vm);
+        }
+        Ok(())
+    }
+
+    fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        self.vm.load_rom_bytes(rom)
+    }
+
+    fn reset(&mut self, keep_memory: bool) {
+        self.vm.reset(keep_memory);
+    }
+
+    fn reload_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        self.vm.reload_rom_bytes(rom)
+    }
+
+    fn pixels<'a>(&'a self) -> Chunks<'a, bool> {
+        self.vm.pixels()
+    }
+
+    fn press_key(&mut self, key: Key) {
+        self.vm.press_key(key);
+    }
+
+    fn release_key(&mut self, key: Key) {
+        self.vm.release_key(key);
+    }
+
+    fn debug(&mut self) -> Option<&mut dyn DebugVm> {
+        self.vm.debug()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cheat_lists_round_trip_through_text() {
+        let list = CheatList { cheats: vec![
+            Cheat { address: 0x300, value: 9, enabled: true, name: "Infinite lives".to_string() },
+            Cheat { address: 0xF00, value: 0xFF, enabled: false, name: String::new() },
+        ] };
+        let text = list.to_string();
+        assert_eq!(text, "# address value on|off name\n0300 09 on Infinite lives\n0F00 FF off\n");
+        assert_eq!(CheatList::parse(&text).unwrap(), list);
+    }
+
+    #[test]
+    fn parse_reports_the_bad_line() {
+        match CheatList::parse("\n0300 09 on\n0300 9G on\n") {
+            Err(CheatError::InvalidLine(3)) => (),
+            other => panic!("{:?}", other),
+        }
+        assert!(CheatList::parse("0300 09 maybe").is_err());
+    }
+
+    #[test]
+    fn cheat_files_are_named_by_rom_hash() {
+        let file = cheat_file(Path::new("cheats"), b"abc");
+        assert_eq!(file, Path::new("cheats").join("e71fa2190541574b.cheats"));
+    }
+}
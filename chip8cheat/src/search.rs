@@ -0,0 +1,104 @@
+use chip8core::DebugVm;
+
+// How a byte must compare to the last snapshot to stay a candidate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compare {
+    Equal,
+    Changed,
+    Increased,
+    Decreased,
+    // Holds this value now, whatever it was before
+    Value(u8),
+}
+
+// Narrows down the addresses that might hold a value, e.g. the lives
+// counter, by comparing memory between snapshots taken as the game plays
+pub struct Search {
+    snapshot: Vec<u8>,
+    candidates: Vec<usize>,
+}
+
+impl Search {
+    // Every address starts as a candidate
+    pub fn new(vm: &dyn DebugVm) -> Search {
+        let snapshot = vm.memory().to_vec();
+        Search { candidates: (0..snapshot.len()).collect(), snapshot }
+    }
+
+    // Drops the candidates that don't compare, then takes a new snapshot
+    pub fn filter(&mut self, vm: &dyn DebugVm, compare: Compare) {
+        let memory = vm.memory();
+        let snapshot = &self.snapshot;
+        self.candidates.retain(|&address| {
+            let (old, new) = match (snapshot.get(address), memory.get(address)) {
+                (Some(&old), Some(&new)) => (old, new),
+                _ => return false,
+            };
+            match compare {
+                Compare::Equal => new == old,
+                Compare::Changed => new != old,
+                Compare::Increased => new > old,
+                Compare::Decreased => new < old,
+                Compare::Value(value) => new == value,
+            }
+        });
+        self.snapshot = memory.to_vec();
+    }
+
+    pub fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+
+    // The value a candidate had in the last snapshot
+    pub fn value(&self, address: usize) -> Option<u8> {
+        self.snapshot.get(address).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8core::{ DebugVm, PROGRAM_START };
+    use chip8vm::Cpu;
+    use super::*;
+
+    fn poke(cpu: &mut Cpu, address: usize, value: u8) {
+        cpu.write_memory(address, &[value]).unwrap();
+    }
+
+    #[test]
+    fn filters_narrow_down_candidates() {
+        let mut cpu = Cpu::new();
+        poke(&mut cpu, 0x300, 5);
+        poke(&mut cpu, 0x301, 5);
+        poke(&mut cpu, 0x302, 5);
+        let mut search = Search::new(&cpu);
+        assert_eq!(search.candidates().len(), cpu.memory().len());
+
+        search.filter(&cpu, Compare::Value(5));
+        assert_eq!(search.candidates(), &[0x300, 0x301, 0x302]);
+
+        poke(&mut cpu, 0x300, 4);
+        poke(&mut cpu, 0x301, 6);
+        search.filter(&cpu, Compare::Changed);
+        assert_eq!(search.candidates(), &[0x300, 0x301]);
+
+        poke(&mut cpu, 0x300, 3);
+        search.filter(&cpu, Compare::Decreased);
+        assert_eq!(search.candidates(), &[0x300]);
+        assert_eq!(search.value(0x300), Some(3));
+    }
+
+    #[test]
+    fn equal_and_increased_compare_to_the_last_snapshot() {
+        let mut cpu = Cpu::new();
+        let mut search = Search::new(&cpu);
+        poke(&mut cpu, PROGRAM_START, 1);
+        search.filter(&cpu, Compare::Increased);
+        assert_eq!(search.candidates(), &[PROGRAM_START]);
+
+        search.filter(&cpu, Compare::Equal);
+        assert_eq!(search.candidates(), &[PROGRAM_START]);
+        search.filter(&cpu, Compare::Increased);
+        assert!(search.candidates().is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+// Finds a game's counter with the memory search the way a player would,
+// then freezes it and keeps the cheat in a file for the ROM.
+extern crate chip8cheat;
+extern crate chip8core;
+extern crate chip8vm;
+
+use std::env;
+use std::fs;
+use chip8cheat::{ cheat_file, Cheat, CheatList, CheatVm, Compare, Search };
+use chip8core::{ DebugVm, Key, Vm };
+use chip8vm::Cpu;
+
+// Takes one from the counter at 300 every time key 5 is pressed
+const ROM: [u8; 16] = [
+    0x61, 0x05, // LD V1, 5
+    0xF1, 0x0A, // LD V1, K
+    0xA3, 0x00, // LD I, 300
+    0xF0, 0x65, // LD V0, [I]
+    0x70, 0xFF, // ADD V0, FF
+    0xF0, 0x55, // LD [I], V0
+    0x12, 0x00, // JP 200
+    0x00, 0x00,
+];
+
+fn cpu() -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.load_rom_bytes(&ROM).unwrap();
+    cpu.write_memory(0x300, &[3]).unwrap();
+    cpu
+}
+
+// Presses and releases key 5, then lets the program run for a while
+fn lose_a_life<V: Vm>(vm: &mut V) {
+    vm.press_key(Key::D5);
+    vm.step(0.05).unwrap();
+    vm.release_key(Key::D5);
+    vm.step(0.05).unwrap();
+}
+
+#[test]
+fn search_finds_the_counter_and_freezing_keeps_it() {
+    let mut cpu = cpu();
+    cpu.step(0.05).unwrap();
+    let mut search = Search::new(&cpu);
+
+    cpu.step(0.05).unwrap();
+    search.filter(&cpu, Compare::Equal);
+    lose_a_life(&mut cpu);
+    search.filter(&cpu, Compare::Decreased);
+    lose_a_life(&mut cpu);
+    search.filter(&cpu, Compare::Value(1));
+    assert_eq!(search.candidates(), &[0x300]);
+
+    let cheats = CheatList { cheats: vec![Cheat::new(0x300, 9)] };
+    let mut vm = CheatVm::new(cpu, cheats);
+    for _ in 0..3 {
+        lose_a_life(&mut vm);
+    }
+    assert_eq!(vm.vm().memory()[0x300], 9);
+
+    vm.cheats.cheats[0].enabled = false;
+    lose_a_life(&mut vm);
+    assert_eq!(vm.vm().memory()[0x300], 8);
+}
+
+#[test]
+fn cheats_are_saved_per_rom() {
+    let dir = env::temp_dir().join(format!("chip8cheat-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = cheat_file(&dir, &ROM);
+    assert_eq!(CheatList::load(&file).unwrap(), CheatList::default());
+
+    let mut cheats = CheatList::default();
+    cheats.cheats.push(Cheat { name: "Lives".to_string(), ..Cheat::new(0x300, 9) });
+    cheats.save(&file).unwrap();
+    assert_eq!(CheatList::load(&file).unwrap(), cheats);
+    assert_ne!(cheat_file(&dir, &ROM[..14]), file);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// A game that takes a life every frame, with the lives frozen at 9. The JIT
+// must not recompile its blocks for every write the freeze makes.
+#[cfg(feature = "jit")]
+#[test]
+fn freezing_on_the_jit_keeps_the_compiled_blocks() {
+    use chip8vm::JitCpu;
+
+    let rom = [
+        0xA3, 0x00, // LD I, 300
+        0xF0, 0x65, // LD V0, [I]
+        0x70, 0xFF, // ADD V0, FF
+        0xF0, 0x55, // LD [I], V0
+        0x12, 0x00, // JP 200
+    ];
+    let mut jit = JitCpu::new();
+    jit.load_rom_bytes(&rom).unwrap();
+    let mut vm = CheatVm::new(jit, CheatList { cheats: vec![Cheat::new(0x300, 9)] });
+
+    for _ in 0..10 {
+        vm.step(1.0 / 60.0).unwrap();
+    }
+    let compiled = vm.vm().compiled_blocks();
+    for _ in 0..600 {
+        vm.step(1.0 / 60.0).unwrap();
+        assert_eq!(vm.vm().memory()[0x300], 9);
+    }
+    assert_eq!(vm.vm().compiled_blocks(), compiled);
+}
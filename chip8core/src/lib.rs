@@ -1,16 +1,185 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate core as std;
+mod quirks;
+
+#[cfg(feature = "std")]
 use std::io::Read;
 
+#[cfg(feature = "std")]
 use std::error::Error;
 use std::fmt;
+#[cfg(feature = "std")]
 use std::io;
 use std::slice::Chunks;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+pub use quirks::Quirks;
+
+pub const MEMORY_SIZE: usize = 4096;
+pub const PROGRAM_START: usize = 0x200;
 
 pub trait Vm {
     fn step(&mut self, time: f64) -> Result<(), InstructionError>;
-    fn load_rom<T: Read>(&mut self, input: &mut T) -> io::Result<()>;
+    fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError>;
+    // Back to the power-on state: registers, timers and screen cleared, the
+    // font written again and pc at the entry point of the loaded ROM. With
+    // keep_memory the rest of memory, and so the loaded ROM, stays as it is,
+    // otherwise it's cleared too.
+    fn reset(&mut self, keep_memory: bool);
+    // Resets and loads another ROM the way the running one was loaded, at
+    // the same address and entry point. A ROM that doesn't fit leaves the
+    // Vm as it was.
+    fn reload_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError>;
     fn pixels<'a>(&'a self) -> Chunks<'a, bool>;
     fn press_key(&mut self, key: Key);
     fn release_key(&mut self, key: Key);
+
+    // The generic methods are left out of `dyn Vm`, so frontends can hold
+    // different Vm implementations side by side
+    #[cfg(feature = "std")]
+    fn load_rom<T: Read>(&mut self, input: &mut T) -> io::Result<()> where Self: Sized {
+        let mut rom = Vec::new();
+        input.read_to_end(&mut rom)?;
+        self.load_rom_bytes(&rom).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn step_clock<C: Clock>(&mut self, clock: &mut C) -> Result<(), InstructionError>
+        where Self: Sized {
+        let time = clock.elapsed();
+        self.step(time)
+    }
+
+    // Lets frontends holding a `dyn Vm` inspect and patch it, None for Vms
+    // that can't be debugged
+    fn debug(&mut self) -> Option<&mut dyn DebugVm> {
+        None
+    }
+}
+
+// Lets wrappers that take a Vm by value run one that is only borrowed
+impl<V: Vm + ?Sized> Vm for &mut V {
+    fn step(&mut self, time: f64) -> Result<(), InstructionError> {
+        (**self).step(time)
+    }
+
+    fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        (**self).load_rom_bytes(rom)
+    }
+
+    fn reset(&mut self, keep_memory: bool) {
+        (**self).reset(keep_memory)
+    }
+
+    fn reload_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        (**self).reload_rom_bytes(rom)
+    }
+
+    fn pixels<'b>(&'b self) -> Chunks<'b, bool> {
+        (**self).pixels()
+    }
+
+    fn press_key(&mut self, key: Key) {
+        (**self).press_key(key)
+    }
+
+    fn release_key(&mut self, key: Key) {
+        (**self).release_key(key)
+    }
+
+    fn debug(&mut self) -> Option<&mut dyn DebugVm> {
+        (**self).debug()
+    }
+}
+
+// Inspection and patching of a running Vm, for debuggers and remote control
+pub trait DebugVm: Vm {
+    fn registers(&self) -> RegisterFile;
+    fn set_register(&mut self, register: Register, value: u16) -> Result<(), RegisterError>;
+    fn memory(&self) -> &[u8];
+    // Fails without writing anything if the data doesn't fit in memory
+    fn write_memory(&mut self, address: usize, data: &[u8]) -> Result<(), RomError>;
+    fn keys(&self) -> &[bool; 16];
+    fn quirks(&self) -> Quirks;
+    fn set_quirks(&mut self, quirks: Quirks);
+
+    // A snapshot of the whole machine that load_state restores
+    #[cfg(feature = "std")]
+    fn save_state(&self) -> Vec<u8>;
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError>;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RegisterFile {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub sp: u16,
+    // Entries at and above sp are stale
+    pub stack: [u16; 16],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Register {
+    V(u8),
+    I,
+    Pc,
+    Sp,
+    DelayTimer,
+    SoundTimer,
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => write!(f, "I"),
+            Register::Pc => write!(f, "PC"),
+            Register::Sp => write!(f, "SP"),
+            Register::DelayTimer => write!(f, "DT"),
+            Register::SoundTimer => write!(f, "ST"),
+        }
+    }
+}
+
+// A source of time for driving a Vm, e.g. a hardware timer on embedded targets
+pub trait Clock {
+    // Seconds passed since the previous call
+    fn elapsed(&mut self) -> f64;
+}
+
+#[cfg(feature = "std")]
+pub struct SystemClock {
+    last: Instant,
+}
+
+#[cfg(feature = "std")]
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock { last: Instant::now() }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn elapsed(&mut self) -> f64 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last);
+        self.last = now;
+        elapsed.as_secs_f64()
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -33,26 +202,55 @@ pub enum Key {
     F  = 0xF,
 }
 
+impl Key {
+    pub fn from_index(index: u8) -> Option<Key> {
+        match index {
+            0x0 => Some(Key::D0),
+            0x1 => Some(Key::D1),
+            0x2 => Some(Key::D2),
+            0x3 => Some(Key::D3),
+            0x4 => Some(Key::D4),
+            0x5 => Some(Key::D5),
+            0x6 => Some(Key::D6),
+            0x7 => Some(Key::D7),
+            0x8 => Some(Key::D8),
+            0x9 => Some(Key::D9),
+            0xA => Some(Key::A),
+            0xB => Some(Key::B),
+            0xC => Some(Key::C),
+            0xD => Some(Key::D),
+            0xE => Some(Key::E),
+            0xF => Some(Key::F),
+            _   => None,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum InstructionError {
-    Illegal,
-    Unsupported,
+    // The opcode isn't a CHIP-8 instruction
+    Illegal(u16),
+    // A real instruction this Vm can't run
+    Unsupported(u16),
 }
 
 impl fmt::Display for InstructionError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            InstructionError::Illegal => write!(f, ""),
-            InstructionError::Unsupported => write!(f, ""),
+            InstructionError::Illegal(opcode) => write!(f, "Illegal opcode 0x{:04X}", opcode),
+            InstructionError::Unsupported(opcode) => {
+                write!(f, "Unsupported opcode 0x{:04X}", opcode)
+            },
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl Error for InstructionError {
     fn description(&self) -> &str {
         match *self {
-            InstructionError::Illegal => "",
-            InstructionError::Unsupported => "",
+            InstructionError::Illegal(_) => "Illegal opcode",
+            InstructionError::Unsupported(_) => "Unsupported opcode",
         }
     }
 
@@ -60,3 +258,82 @@ impl Error for InstructionError {
         None
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RomError {
+    TooLarge,
+    OutOfBounds,
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RomError::TooLarge => write!(f, "ROM exceeds maximum size"),
+            RomError::OutOfBounds => write!(f, "Load address is outside of memory"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for RomError {
+    fn description(&self) -> &str {
+        match *self {
+            RomError::TooLarge => "ROM exceeds maximum size",
+            RomError::OutOfBounds => "Load address is outside of memory",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StateError {
+    Malformed,
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::Malformed => write!(f, "Saved state is malformed"),
+            StateError::UnsupportedVersion(v) => write!(f, "Saved state version {} is not supported", v),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for StateError {
+    fn description(&self) -> &str {
+        match *self {
+            StateError::Malformed => "Saved state is malformed",
+            StateError::UnsupportedVersion(_) => "Saved state version is not supported",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RegisterError {
+    // The value is wider than the register, or PC or SP would point outside
+    // of memory or the stack
+    OutOfRange(Register, u16),
+    NoSuchRegister(Register),
+}
+
+impl fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RegisterError::OutOfRange(register, value) => {
+                write!(f, "{:#X} doesn't fit in {}", value, register)
+            },
+            RegisterError::NoSuchRegister(register) => write!(f, "There is no register {}", register),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for RegisterError {
+    fn description(&self) -> &str {
+        match *self {
+            RegisterError::OutOfRange(..) => "Value doesn't fit in the register",
+            RegisterError::NoSuchRegister(_) => "There is no such register",
+        }
+    }
+}
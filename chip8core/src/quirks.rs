@@ -0,0 +1,60 @@
+// Behaviour that differs between CHIP-8 interpreters. The default matches
+// what Cpu has always done, the presets match the original platforms.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Quirks {
+    // 8XY6/8XYE shift VY into VX instead of shifting VX in place
+    pub shift_uses_vy: bool,
+    // FX55/FX65 leave I pointing past the last register
+    pub load_store_increments_i: bool,
+    // 8XY1/8XY2/8XY3 set VF to 0
+    pub logic_resets_vf: bool,
+    // DXYN cuts sprites off at the screen edge instead of wrapping them
+    pub clip_sprites: bool,
+    // BNNN jumps to NNN + VX, where X is the highest nibble of NNN
+    pub jump_uses_vx: bool,
+}
+
+impl Quirks {
+    // COSMAC VIP
+    pub fn chip8() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            logic_resets_vf: true,
+            clip_sprites: true,
+            jump_uses_vx: false,
+        }
+    }
+
+    // SUPER-CHIP 1.1 on the HP 48
+    pub fn superchip() -> Quirks {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            jump_uses_vx: true,
+        }
+    }
+
+    // Octo's XO-CHIP
+    pub fn xochip() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            logic_resets_vf: false,
+            clip_sprites: false,
+            jump_uses_vx: false,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Quirks> {
+        match name {
+            "chip8" | "vip" => Some(Quirks::chip8()),
+            "schip" | "superchip" => Some(Quirks::superchip()),
+            "xochip" => Some(Quirks::xochip()),
+            "none" => Some(Quirks::default()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::str;
+use chip8core::DebugVm;
+use chip8vm::Random;
+use packet::MAX_PACKET;
+use target;
+use super::{ GdbVm, State };
+
+pub enum Reply {
+    Packet(Vec<u8>),
+    // Continuing and stepping answer once the Vm stops
+    None,
+    // Answers OK and lets the program go
+    Detach,
+}
+
+// Packets the stub doesn't know get an empty reply, which tells gdb
+// they aren't supported
+pub fn handle<R: Random>(vm: &mut GdbVm<R>, data: &[u8]) -> Reply {
+    // X carries raw bytes that needn't be valid UTF-8
+    if data.first() == Some(&b'X') {
+        return Reply::Packet(write_binary(vm, &data[1..]).unwrap_or_else(error));
+    }
+    let packet = match str::from_utf8(data) {
+        Ok(packet) if !packet.is_empty() => packet,
+        _ => return Reply::Packet(Vec::new()),
+    };
+    let (command, args) = packet.split_at(1);
+
+    let reply = match command {
+        "?" => format!("S{:02x}", vm.signal).into_bytes(),
+        "g" => to_hex(&target::registers(&vm.cpu)).into_bytes(),
+        "p" => match parse_hex(args).and_then(|n| target::register(&vm.cpu, n)) {
+            Some(value) => to_hex(&value).into_bytes(),
+            None => error(),
+        },
+        "P" => write_register(vm, args).unwrap_or_else(error),
+        "m" => read_memory(vm, args).unwrap_or_else(error),
+        "M" => write_memory(vm, args).unwrap_or_else(error),
+        "Z" | "z" => breakpoint(vm, command == "Z", args).unwrap_or_else(error),
+        // Resuming at another address would need to set PC, which gdb
+        // doesn't ask for in practice
+        "c" => {
+            vm.resume(State::Running);
+            return Reply::None;
+        },
+        "s" => {
+            vm.resume(State::Stepping);
+            return Reply::None;
+        },
+        "D" => return Reply::Detach,
+        "k" => {
+            vm.detach();
+            return Reply::None;
+        },
+        "H" => b"OK".to_vec(),
+        "q" | "Q" => query(vm, packet),
+        _ => Vec::new(),
+    };
+    Reply::Packet(reply)
+}
+
+fn query<R: Random>(vm: &mut GdbVm<R>, packet: &str) -> Vec<u8> {
+    if packet.starts_with("qSupported") {
+        return format!("PacketSize={:x};qXfer:features:read+;QStartNoAckMode+",
+                       MAX_PACKET).into_bytes();
+    }
+    if packet == "QStartNoAckMode" {
+        if let Some(ref mut client) = vm.client {
+            client.ack = false;
+        }
+        return b"OK".to_vec();
+    }
+    if packet == "qAttached" {
+        return b"1".to_vec();
+    }
+    if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+        return match parse_range(range) {
+            Some((offset, length)) => read_chunk(target::description().as_bytes(),
+                                                 offset, length),
+            None => error(),
+        };
+    }
+    Vec::new()
+}
+
+// P n=value with the value in target byte order
+fn write_register<R: Random>(vm: &mut GdbVm<R>, args: &str) -> Option<Vec<u8>> {
+    let mut parts = args.splitn(2, '=');
+    let n = parse_hex(parts.next()?)?;
+    let value = from_hex(parts.next()?)?;
+    target::set_register(&mut vm.cpu, n, &value)?;
+    Some(b"OK".to_vec())
+}
+
+// m addr,length with hex numbers, reads stop at the end of memory
+fn read_memory<R: Random>(vm: &GdbVm<R>, args: &str) -> Option<Vec<u8>> {
+    let (address, length) = parse_range(args)?;
+    let memory = vm.cpu.memory();
+    if address >= memory.len() {
+        return None;
+    }
+    let end = memory.len().min(address.saturating_add(length));
+    Some(to_hex(&memory[address..end]).into_bytes())
+}
+
+// M addr,length:hex
+fn write_memory<R: Random>(vm: &mut GdbVm<R>, args: &str) -> Option<Vec<u8>> {
+    let colon = args.find(':')?;
+    let data = from_hex(&args[(colon + 1)..])?;
+    write_range(vm, &args[..colon], &data)
+}
+
+// X addr,length:binary
+fn write_binary<R: Random>(vm: &mut GdbVm<R>, args: &[u8]) -> Option<Vec<u8>> {
+    let colon = args.iter().position(|&b| b == b':')?;
+    let range = str::from_utf8(&args[..colon]).ok()?;
+    write_range(vm, range, &args[(colon + 1)..])
+}
+
+fn write_range<R: Random>(vm: &mut GdbVm<R>, range: &str, data: &[u8]) -> Option<Vec<u8>> {
+    let (address, length) = parse_range(range)?;
+    if data.len() != length {
+        return None;
+    }
+    vm.cpu.write_memory(address, data).ok()?;
+    Some(b"OK".to_vec())
+}
+
+// Z0,addr,kind inserts a software breakpoint, z0 removes it. The stub
+// checks PC against them instead of patching memory, so the program
+// never sees them.
+fn breakpoint<R: Random>(vm: &mut GdbVm<R>, insert: bool, args: &str) -> Option<Vec<u8>> {
+    let mut parts = args.split(',');
+    if parts.next()? != "0" {
+        return Some(Vec::new());
+    }
+    let address = parse_hex(parts.next()?)?;
+    if address >= vm.cpu.memory().len() {
+        return None;
+    }
+    if insert {
+        vm.breakpoints.insert(address as u16);
+    } else {
+        vm.breakpoints.remove(&(address as u16));
+    }
+    Some(b"OK".to_vec())
+}
+
+fn error() -> Vec<u8> {
+    b"E01".to_vec()
+}
+
+// qXfer replies start with m when there is more to read and l at the end
+fn read_chunk(data: &[u8], offset: usize, length: usize) -> Vec<u8> {
+    let start = data.len().min(offset);
+    let end = data.len().min(start.saturating_add(length));
+    let mut reply = vec![if end < data.len() { b'm' } else { b'l' }];
+    reply.extend_from_slice(&data[start..end]);
+    reply
+}
+
+fn parse_hex(s: &str) -> Option<usize> {
+    usize::from_str_radix(s, 16).ok()
+}
+
+// addr,length
+fn parse_range(s: &str) -> Option<(usize, usize)> {
+    let mut parts = s.splitn(2, ',');
+    Some((parse_hex(parts.next()?)?, parse_hex(parts.next()?)?))
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|n| u8::from_str_radix(&s[n..(n + 2)], 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_chunk_marks_the_last_one() {
+        assert_eq!(read_chunk(b"abcdef", 0, 4), b"mabcd");
+        assert_eq!(read_chunk(b"abcdef", 4, 4), b"lef");
+        assert_eq!(read_chunk(b"abcdef", 9, 4), b"l");
+    }
+
+    #[test]
+    fn parse_range_takes_hex_pairs() {
+        assert_eq!(parse_range("200,a"), Some((0x200, 10)));
+        assert_eq!(parse_range("200"), None);
+        assert_eq!(parse_range("x,1"), None);
+    }
+}
@@ -0,0 +1,294 @@
+// A gdb remote serial protocol stub, so CHIP-8 programs can be debugged
+// with gdb or any other RSP client:
+//
+//   (gdb) target remote localhost:1234
+//
+// gdb gets V0-VF, I, PC, SP and the timers as registers, see target.rs,
+// and the Cpu's memory as its address space. Breakpoints, single-step and
+// continue are supported. One debugger is served at a time, the Vm waits
+// halted until it attaches and runs freely again once it detaches.
+//
+// GdbVm keeps the Vm trait, so a frontend keeps drawing the screen and
+// feeding keys while gdb controls execution. Requests are answered at the
+// start of every step.
+extern crate chip8core;
+extern crate chip8vm;
+mod commands;
+mod packet;
+mod target;
+
+use std::collections::BTreeSet;
+use std::io;
+use std::io::{ ErrorKind, Read, Write };
+use std::net::{ SocketAddr, TcpListener, TcpStream, ToSocketAddrs };
+use std::slice::Chunks;
+use chip8core::{ DebugVm, InstructionError, Key, RomError, Vm };
+use chip8vm::{ Cpu, DefaultRandom, Random, CLOCK_PERIOD };
+use packet::{ Decoder, Event };
+
+// Signals reported in stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Halted,
+    Running,
+    // Runs until one instruction has executed
+    Stepping,
+}
+
+struct Client {
+    stream: TcpStream,
+    decoder: Decoder,
+    // Cleared by QStartNoAckMode
+    ack: bool,
+}
+
+impl Client {
+    // Waits until the whole packet is written
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        self.write(&packet::encode(data))
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.stream.set_nonblocking(false)?;
+        self.stream.write_all(data)?;
+        self.stream.flush()?;
+        self.stream.set_nonblocking(true)
+    }
+}
+
+pub struct GdbVm<R: Random = DefaultRandom> {
+    cpu: Cpu<R>,
+    listener: TcpListener,
+    client: Option<Client>,
+    breakpoints: BTreeSet<u16>,
+    state: State,
+    // Why the Vm last stopped, for the ? packet
+    signal: u8,
+    // True until the instruction execution resumed at has run, so a
+    // breakpoint there doesn't stop it again right away
+    resuming: bool,
+    clock_accumulator: f64,
+}
+
+impl<R: Random> GdbVm<R> {
+    // Anyone who can reach the port controls the emulator, so only the
+    // loopback interface is allowed
+    pub fn bind<A: ToSocketAddrs>(cpu: Cpu<R>, addr: A) -> io::Result<GdbVm<R>> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        if addrs.is_empty() || addrs.iter().any(|a| !a.ip().is_loopback()) {
+            return Err(io::Error::new(ErrorKind::InvalidInput,
+                                      "The gdb stub only listens on loopback addresses"));
+        }
+        let listener = TcpListener::bind(&addrs[..])?;
+        listener.set_nonblocking(true)?;
+        Ok(GdbVm {
+            cpu,
+            listener,
+            client: None,
+            breakpoints: BTreeSet::new(),
+            state: State::Halted,
+            signal: SIGTRAP,
+            resuming: false,
+            clock_accumulator: 0.0,
+        })
+    }
+
+    // To find the port when bound to port 0
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn cpu(&self) -> &Cpu<R> {
+        &self.cpu
+    }
+
+    pub fn into_inner(self) -> Cpu<R> {
+        self.cpu
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.client.is_some()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.state == State::Halted
+    }
+
+    // Accepts a debugger if none is attached and answers its packets
+    pub fn poll(&mut self) {
+        if self.client.is_none() {
+            if let Ok((stream, _)) = self.listener.accept() {
+                if stream.set_nonblocking(true).is_ok() {
+                    // Attaching stops the program like it does for any process
+                    self.client = Some(Client { stream, decoder: Decoder::default(), ack: true });
+                    self.state = State::Halted;
+                    self.signal = SIGTRAP;
+                }
+            }
+        }
+
+        if !self.receive() {
+            self.detach();
+        }
+    }
+
+    // Returns false once the debugger is gone
+    fn receive(&mut self) -> bool {
+        let mut buffer = [0; 4096];
+        loop {
+            let client = match self.client {
+                Some(ref mut client) => client,
+                None => return true,
+            };
+            match client.stream.read(&mut buffer) {
+                Ok(0) => return false,
+                Ok(n) => client.decoder.push(&buffer[..n]),
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+
+        loop {
+            let event = match self.client {
+                Some(ref mut client) => client.decoder.next_event(),
+                None => return true,
+            };
+            let sent = match event {
+                None => return true,
+                Some(Event::Interrupt) => {
+                    if self.state != State::Halted {
+                        self.stop(SIGINT);
+                    }
+                    Ok(())
+                },
+                Some(Event::Corrupt) => self.write_ack(b"-"),
+                Some(Event::Packet(data)) => {
+                    self.write_ack(b"+").and_then(|_| match commands::handle(self, &data) {
+                        commands::Reply::Packet(reply) => self.send(&reply),
+                        commands::Reply::None => Ok(()),
+                        commands::Reply::Detach => {
+                            let sent = self.send(b"OK");
+                            self.detach();
+                            sent
+                        },
+                    })
+                },
+            };
+            if sent.is_err() {
+                return false;
+            }
+        }
+    }
+
+    fn write_ack(&mut self, ack: &[u8]) -> io::Result<()> {
+        match self.client {
+            Some(ref mut client) if client.ack => client.write(ack),
+            _ => Ok(()),
+        }
+    }
+
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        match self.client {
+            Some(ref mut client) => client.send(data),
+            None => Ok(()),
+        }
+    }
+
+    // The program carries on without breakpoints
+    fn detach(&mut self) {
+        if self.client.take().is_some() {
+            self.breakpoints.clear();
+            self.resume(State::Running);
+        }
+    }
+
+    fn resume(&mut self, state: State) {
+        self.state = state;
+        self.resuming = true;
+        self.clock_accumulator = 0.0;
+    }
+
+    fn stop(&mut self, signal: u8) {
+        self.state = State::Halted;
+        self.signal = signal;
+        // A dead debugger is noticed on the next poll
+        let _ = self.send(format!("S{:02x}", signal).as_bytes());
+    }
+
+    fn run(&mut self, time: f64) -> Result<(), InstructionError> {
+        self.clock_accumulator += time;
+
+        while self.clock_accumulator > CLOCK_PERIOD {
+            self.clock_accumulator -= CLOCK_PERIOD;
+
+            // Nothing executes while waiting for a key, so there is no
+            // instruction to break on
+            let executes = !self.cpu.is_waiting_for_key();
+            if executes && !self.resuming && self.breakpoints.contains(&self.cpu.pc()) {
+                self.stop(SIGTRAP);
+                return Ok(());
+            }
+
+            if let Err(e) = self.cpu.step_cycle() {
+                if self.client.is_none() {
+                    return Err(e);
+                }
+                self.stop(SIGILL);
+                return Ok(());
+            }
+
+            if executes {
+                self.resuming = false;
+                if self.state == State::Stepping {
+                    self.stop(SIGTRAP);
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Random> Vm for GdbVm<R> {
+    fn step(&mut self, time: f64) -> Result<(), InstructionError> {
+        self.poll();
+        match self.state {
+            State::Halted => Ok(()),
+            State::Running | State::Stepping => self.run(time),
+        }
+    }
+
+    fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        self.cpu.load_rom_bytes(rom)
+    }
+
+    fn reset(&mut self, keep_memory: bool) {
+        self.cpu.reset(keep_memory);
+    }
+
+    fn reload_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        self.cpu.reload_rom_bytes(rom)
+    }
+
+    fn pixels<'a>(&'a self) -> Chunks<'a, bool> {
+        self.cpu.pixels()
+    }
+
+    fn press_key(&mut self, key: Key) {
+        self.cpu.press_key(key);
+    }
+
+    fn release_key(&mut self, key: Key) {
+        self.cpu.release_key(key);
+    }
+
+    fn debug(&mut self) -> Option<&mut dyn DebugVm> {
+        Some(&mut self.cpu)
+    }
+}
@@ -0,0 +1,157 @@
+// Framing of the remote serial protocol: $data#checksum, acknowledged with
+// + or - unless no-ack mode is on, and a lone 0x03 byte to interrupt.
+
+use std::str;
+
+const INTERRUPT: u8 = 0x03;
+
+// Longest packet accepted, gdb is told about it in qSupported
+pub const MAX_PACKET: usize = 0x1000;
+
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    Packet(Vec<u8>),
+    // A packet with a bad checksum, the client should send it again
+    Corrupt,
+    Interrupt,
+}
+
+#[derive(Default)]
+pub struct Decoder {
+    buffer: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    // The next complete event, acks and noise between packets are skipped
+    pub fn next_event(&mut self) -> Option<Event> {
+        match self.buffer.iter().position(|&b| b == b'$' || b == INTERRUPT) {
+            Some(start) => {
+                self.buffer.drain(..start);
+            },
+            None => {
+                self.buffer.clear();
+                return None;
+            },
+        }
+        if self.buffer[0] == INTERRUPT {
+            self.buffer.remove(0);
+            return Some(Event::Interrupt);
+        }
+
+        let end = match self.buffer.iter().position(|&b| b == b'#') {
+            Some(end) if self.buffer.len() >= end + 3 => end,
+            // Drop runaway packets so a broken client can't grow the buffer
+            None if self.buffer.len() > MAX_PACKET + 4 => {
+                self.buffer.clear();
+                return Some(Event::Corrupt);
+            },
+            _ => return None,
+        };
+        let packet: Vec<u8> = self.buffer.drain(..(end + 3)).collect();
+        let data = &packet[1..end];
+        let checksum = str::from_utf8(&packet[(end + 1)..])
+            .ok()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if checksum != Some(checksum_of(data)) {
+            return Some(Event::Corrupt);
+        }
+        Some(Event::Packet(unescape(data)))
+    }
+}
+
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len() + 4);
+    for &b in data {
+        match b {
+            b'$' | b'#' | b'}' | b'*' => {
+                escaped.push(b'}');
+                escaped.push(b ^ 0x20);
+            },
+            b => escaped.push(b),
+        }
+    }
+
+    let mut out = Vec::with_capacity(escaped.len() + 4);
+    out.push(b'$');
+    out.extend_from_slice(&escaped);
+    out.extend_from_slice(format!("#{:02x}", checksum_of(&escaped)).as_bytes());
+    out
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &b| sum.wrapping_add(b))
+}
+
+// Binary data in X packets escapes $, # and } as } followed by the byte
+// xor 0x20
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(&b) = bytes.next() {
+        match b {
+            b'}' => {
+                if let Some(&next) = bytes.next() {
+                    out.push(next ^ 0x20);
+                }
+            },
+            b => out.push(b),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(data: &[u8]) -> Vec<Event> {
+        let mut decoder = Decoder::default();
+        decoder.push(data);
+        let mut events = Vec::new();
+        while let Some(event) = decoder.next_event() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn encode_adds_checksum() {
+        assert_eq!(encode(b"OK"), b"$OK#9a");
+        assert_eq!(encode(b""), b"$#00");
+    }
+
+    #[test]
+    fn encode_escapes_special_bytes() {
+        assert_eq!(encode(b"a#b"), b"$a}\x03b#43");
+    }
+
+    #[test]
+    fn decoder_splits_packets_and_skips_acks() {
+        assert_eq!(events(b"+$g#67+$?#3f"),
+                   vec![Event::Packet(b"g".to_vec()), Event::Packet(b"?".to_vec())]);
+    }
+
+    #[test]
+    fn decoder_waits_for_the_checksum() {
+        let mut decoder = Decoder::default();
+        decoder.push(b"$g#6");
+        assert_eq!(decoder.next_event(), None);
+        decoder.push(b"7");
+        assert_eq!(decoder.next_event(), Some(Event::Packet(b"g".to_vec())));
+    }
+
+    #[test]
+    fn decoder_reports_bad_checksums_and_interrupts() {
+        assert_eq!(events(b"$g#00\x03"), vec![Event::Corrupt, Event::Interrupt]);
+        assert_eq!(events(b"$g#zz"), vec![Event::Corrupt]);
+    }
+
+    #[test]
+    fn decoder_unescapes_binary_data() {
+        assert_eq!(events(&encode(b"X0,1:}")), vec![Event::Packet(b"X0,1:}".to_vec())]);
+    }
+}
@@ -0,0 +1,112 @@
+// The register set gdb sees. CHIP-8 is big endian, so registers wider than
+// a byte are sent high byte first like words in memory.
+use std::fmt::Write;
+use chip8core::{ DebugVm, Register };
+use chip8vm::{ Cpu, Random };
+
+pub const REGISTER_COUNT: usize = 21;
+
+pub const PC: usize = 17;
+
+// Name and width in bytes, in gdb's register numbering
+fn register_layout(n: usize) -> Option<(String, usize)> {
+    match n {
+        0..=15 => Some((format!("v{:x}", n), 1)),
+        16 => Some(("i".to_string(), 2)),
+        PC => Some(("pc".to_string(), 2)),
+        18 => Some(("sp".to_string(), 2)),
+        19 => Some(("dt".to_string(), 1)),
+        20 => Some(("st".to_string(), 1)),
+        _ => None,
+    }
+}
+
+fn register_id(n: usize) -> Option<Register> {
+    match n {
+        0..=15 => Some(Register::V(n as u8)),
+        16 => Some(Register::I),
+        PC => Some(Register::Pc),
+        18 => Some(Register::Sp),
+        19 => Some(Register::DelayTimer),
+        20 => Some(Register::SoundTimer),
+        _ => None,
+    }
+}
+
+pub fn register<R: Random>(cpu: &Cpu<R>, n: usize) -> Option<Vec<u8>> {
+    let value = match n {
+        0..=15 => cpu.v()[n] as u16,
+        16 => cpu.i(),
+        PC => cpu.pc(),
+        18 => cpu.sp(),
+        19 => cpu.delay_timer() as u16,
+        20 => cpu.sound_timer() as u16,
+        _ => return None,
+    };
+    let (_, size) = register_layout(n)?;
+    Some(value.to_be_bytes()[(2 - size)..].to_vec())
+}
+
+// The value must be exactly as wide as the register
+pub fn set_register<R: Random>(cpu: &mut Cpu<R>, n: usize, value: &[u8]) -> Option<()> {
+    let (_, size) = register_layout(n)?;
+    if value.len() != size {
+        return None;
+    }
+    let value = value.iter().fold(0, |value, &b| value << 8 | b as u16);
+    cpu.set_register(register_id(n)?, value).ok()
+}
+
+// All registers back to back, the reply to g
+pub fn registers<R: Random>(cpu: &Cpu<R>) -> Vec<u8> {
+    (0..REGISTER_COUNT).flat_map(|n| register(cpu, n).unwrap()).collect()
+}
+
+// Lets gdb show the registers by name without knowing about CHIP-8
+pub fn description() -> String {
+    let mut xml = String::from("<?xml version=\"1.0\"?>\n\
+                                <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n\
+                                <target version=\"1.0\">\n\
+                                <feature name=\"org.chip8.cpu\">\n");
+    for n in 0..REGISTER_COUNT {
+        let (name, size) = register_layout(n).unwrap();
+        let kind = match n {
+            16 => "data_ptr".to_string(),
+            PC => "code_ptr".to_string(),
+            _ => format!("uint{}", size * 8),
+        };
+        writeln!(xml, "<reg name=\"{}\" bitsize=\"{}\" type=\"{}\" regnum=\"{}\"/>",
+                 name, size * 8, kind, n).unwrap();
+    }
+    xml.push_str("</feature>\n</target>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8core::Vm;
+    use super::*;
+
+    #[test]
+    fn registers_are_big_endian() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes(&[0x6F, 0x42, 0xA1, 0x23]).unwrap();
+        cpu.step_instruction().unwrap();
+        cpu.step_instruction().unwrap();
+
+        let registers = registers(&cpu);
+        assert_eq!(registers.len(), 24);
+        assert_eq!(registers[15], 0x42);
+        assert_eq!(&registers[16..20], &[0x01, 0x23, 0x02, 0x04]);
+        assert_eq!(register(&cpu, PC), Some(vec![0x02, 0x04]));
+        assert_eq!(register(&cpu, REGISTER_COUNT), None);
+    }
+
+    #[test]
+    fn description_lists_every_register() {
+        let xml = description();
+        assert_eq!(xml.matches("<reg ").count(), REGISTER_COUNT);
+        assert!(xml.contains("<reg name=\"va\" bitsize=\"8\" type=\"uint8\" regnum=\"10\"/>"));
+        assert!(xml.contains("<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\" regnum=\"17\"/>"));
+    }
+}
@@ -0,0 +1,228 @@
+// Drives a GdbVm the way a frontend would while a scripted RSP client
+// debugs it over a real socket, in place of gdb.
+extern crate chip8core;
+extern crate chip8gdb;
+extern crate chip8vm;
+
+use std::io::{ Read, Write };
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+use chip8core::{ DebugVm, Vm };
+use chip8gdb::GdbVm;
+use chip8vm::Cpu;
+
+// Counts up in V0 forever
+const ROM: [u8; 8] = [
+    0x60, 0x00, // LD V0, 0
+    0xA3, 0x00, // LD I, 300
+    0x70, 0x01, // ADD V0, 1
+    0x12, 0x04, // JP 204
+];
+
+struct Client {
+    stream: TcpStream,
+    ack: bool,
+}
+
+impl Client {
+    fn byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    fn write_packet(&mut self, data: &str) {
+        let checksum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        write!(self.stream, "${}#{:02x}", data, checksum).unwrap();
+        if self.ack {
+            assert_eq!(self.byte() as char, '+');
+        }
+    }
+
+    fn read_packet(&mut self) -> String {
+        while self.byte() != b'$' {}
+        let mut data = Vec::new();
+        loop {
+            match self.byte() {
+                b'#' => break,
+                b => data.push(b),
+            }
+        }
+        let checksum = [self.byte(), self.byte()];
+        let checksum = u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap();
+        assert_eq!(data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)), checksum);
+        if self.ack {
+            self.stream.write_all(b"+").unwrap();
+        }
+        String::from_utf8(data).unwrap()
+    }
+
+    fn request(&mut self, data: &str) -> String {
+        self.write_packet(data);
+        self.read_packet()
+    }
+
+    fn register(&mut self, n: usize) -> u16 {
+        u16::from_str_radix(&self.request(&format!("p{:x}", n)), 16).unwrap()
+    }
+}
+
+// Steps the Vm like a frontend until the script is done
+fn debug<F: FnOnce(&mut Client) + Send + 'static>(script: F) -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.load_rom_bytes(&ROM).unwrap();
+    let mut vm = GdbVm::bind(cpu, "127.0.0.1:0").unwrap();
+    let stream = TcpStream::connect(vm.local_addr().unwrap()).unwrap();
+
+    let client = thread::spawn(move || script(&mut Client { stream, ack: true }));
+    while !client.is_finished() {
+        vm.step(0.001).unwrap();
+        thread::sleep(Duration::from_millis(1));
+    }
+    client.join().unwrap();
+    // Let the stub notice a detach
+    vm.step(0.001).unwrap();
+    vm.into_inner()
+}
+
+#[test]
+fn attaching_halts_the_program() {
+    debug(|client| {
+        assert_eq!(client.request("?"), "S05");
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(client.register(17), 0x200);
+    });
+}
+
+#[test]
+fn registers_are_read_in_target_order() {
+    debug(|client| {
+        for _ in 0..3 {
+            assert_eq!(client.request("s"), "S05");
+        }
+        let registers = client.request("g");
+        // V0-VF, I, PC, SP, DT and ST
+        assert_eq!(registers.len(), 48);
+        assert_eq!(&registers[..2], "01");
+        assert_eq!(&registers[32..40], "03000206");
+        assert_eq!(&registers[40..], "00000000");
+        assert_eq!(client.request("p15"), "E01");
+
+        assert_eq!(client.request("P11=0300"), "OK");
+        assert_eq!(client.register(17), 0x300);
+        assert_eq!(client.request("P0=ff"), "OK");
+        assert_eq!(client.register(0), 0xFF);
+        assert_eq!(client.request("P11=1000"), "E01");
+        assert_eq!(client.request("P0=0100"), "E01");
+    });
+}
+
+#[test]
+fn target_description_names_registers() {
+    debug(|client| {
+        let supported = client.request("qSupported:multiprocess+;xmlRegisters=i386");
+        assert!(supported.contains("qXfer:features:read+"));
+
+        let mut xml = String::new();
+        loop {
+            let chunk = client.request(&format!("qXfer:features:read:target.xml:{:x},40",
+                                                xml.len()));
+            xml.push_str(&chunk[1..]);
+            if chunk.starts_with('l') {
+                break;
+            }
+            assert!(chunk.starts_with('m'));
+        }
+        assert!(xml.starts_with("<?xml"));
+        assert!(xml.contains("<reg name=\"v0\""));
+        assert!(xml.contains("<reg name=\"pc\""));
+        assert!(xml.contains("<reg name=\"st\""));
+        assert!(xml.ends_with("</target>\n"));
+    });
+}
+
+#[test]
+fn continue_stops_at_breakpoints() {
+    debug(|client| {
+        assert_eq!(client.request("Z0,204,2"), "OK");
+        assert_eq!(client.request("c"), "S05");
+        assert_eq!(client.register(17), 0x204);
+        assert_eq!(client.register(0), 0);
+
+        // Continuing from a breakpoint runs the instruction it's on
+        assert_eq!(client.request("c"), "S05");
+        assert_eq!(client.register(17), 0x204);
+        assert_eq!(client.register(0), 1);
+
+        assert_eq!(client.request("z0,204,2"), "OK");
+        assert_eq!(client.request("Z0,1000,2"), "E01");
+        assert_eq!(client.request("Z1,204,2"), "");
+    });
+}
+
+#[test]
+fn interrupt_stops_a_running_program() {
+    debug(|client| {
+        client.write_packet("c");
+        thread::sleep(Duration::from_millis(30));
+        client.stream.write_all(&[0x03]).unwrap();
+        assert_eq!(client.read_packet(), "S02");
+        assert_eq!(client.request("?"), "S02");
+        assert!(client.register(0) > 0);
+    });
+}
+
+#[test]
+fn memory_can_be_read_and_written() {
+    let cpu = debug(|client| {
+        assert_eq!(client.request("m200,4"), "6000a300");
+        assert_eq!(client.request("mffe,8"), "0000");
+        assert_eq!(client.request("m1000,1"), "E01");
+
+        assert_eq!(client.request("M300,2:beef"), "OK");
+        assert_eq!(client.request("m300,2"), "beef");
+        assert_eq!(client.request("Mfff,2:beef"), "E01");
+        assert_eq!(client.request("M300,2:be"), "E01");
+
+        // Patch LD V0, 0 into LD V0, 7
+        assert_eq!(client.request("X201,1:\x07"), "OK");
+        assert_eq!(client.request("s"), "S05");
+        assert_eq!(client.register(0), 7);
+    });
+    assert_eq!(&cpu.memory()[0x300..0x302], &[0xBE, 0xEF]);
+}
+
+#[test]
+fn no_ack_mode_drops_acks() {
+    debug(|client| {
+        assert_eq!(client.request("QStartNoAckMode"), "OK");
+        client.ack = false;
+        assert_eq!(client.request("m200,2"), "6000");
+        assert_eq!(client.request("vMustReplyEmpty"), "");
+    });
+}
+
+#[test]
+fn corrupt_packets_are_nacked() {
+    debug(|client| {
+        client.stream.write_all(b"$g#00").unwrap();
+        assert_eq!(client.byte(), b'-');
+        assert_eq!(client.request("m200,1"), "60");
+    });
+}
+
+#[test]
+fn detaching_lets_the_program_run() {
+    let cpu = debug(|client| {
+        assert_eq!(client.request("Z0,204,2"), "OK");
+        assert_eq!(client.request("D"), "OK");
+        thread::sleep(Duration::from_millis(50));
+    });
+    assert!(cpu.v()[0] > 1);
+}
+
+#[test]
+fn stub_only_binds_loopback() {
+    assert!(GdbVm::bind(Cpu::new(), "0.0.0.0:0").is_err());
+}
@@ -0,0 +1,408 @@
+// Two player netplay. Both players run the same ROM on their own Cpu and
+// exchange key events over TCP, tagged with the frame they apply to. A
+// frame is only stepped once both players' input for it has arrived, so
+// both Cpus see exactly the same input and stay in lockstep. Key events
+// are scheduled a few frames ahead to hide the round trip.
+extern crate chip8core;
+extern crate chip8vm;
+mod protocol;
+
+use std::collections::HashMap;
+use std::cmp;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::{ BufReader, BufWriter, Write };
+use std::net::{ Shutdown, TcpListener, TcpStream, ToSocketAddrs };
+use std::slice::Chunks;
+use std::time::{ SystemTime, UNIX_EPOCH };
+use chip8core::{ InstructionError, Key, RomError, Vm };
+use chip8vm::{ Cpu, Random };
+use protocol::{ KeyEvent, Message, VERSION };
+
+// Netplay always steps whole frames so both players' clocks agree
+pub const FRAME: f64 = 1.0 / 60.0;
+
+// Most key events sent for one frame, the rest wait for the next one
+const MAX_EVENTS: usize = 255;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    // Frames between a key press and the frame it is applied in
+    pub input_delay: u32,
+    // Frames between two state hash comparisons, 0 turns the check off
+    pub hash_interval: u32,
+    // Seed for CXNN, both players' Cpus must be created with it
+    pub seed: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos());
+        Settings {
+            input_delay: 2,
+            hash_interval: 60,
+            seed: nanos.unwrap_or(1) | 1,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NetError {
+    Io(io::Error),
+    // The players run different versions or ROMs
+    Mismatch(String),
+    // The players' Cpus no longer agree
+    Desync { frame: u32, local: u64, remote: u64 },
+    Instruction(InstructionError),
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NetError::Io(ref e) => write!(f, "{}", e),
+            NetError::Mismatch(ref what) => write!(f, "The players don't match: {}", what),
+            NetError::Desync { frame, local, remote } =>
+                write!(f, "Desync in frame {}: state hash {:016X}, the other player has {:016X}",
+                       frame, local, remote),
+            NetError::Instruction(ref e) => write!(f, "{:?} instruction", e),
+        }
+    }
+}
+
+impl Error for NetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            NetError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NetError {
+    fn from(e: io::Error) -> NetError {
+        NetError::Io(e)
+    }
+}
+
+impl From<InstructionError> for NetError {
+    fn from(e: InstructionError) -> NetError {
+        NetError::Instruction(e)
+    }
+}
+
+// FNV-1a hash of everything that affects how the Cpu runs from here on
+pub fn state_hash<R: Random>(cpu: &Cpu<R>) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    {
+        let mut add = |byte: u8| {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        };
+
+        for &word in [cpu.pc(), cpu.i(), cpu.sp()].iter().chain(cpu.stack().iter()) {
+            add((word >> 8) as u8);
+            add(word as u8);
+        }
+        cpu.v().iter().chain(cpu.mem().iter()).for_each(|&byte| add(byte));
+        add(cpu.delay_timer());
+        add(cpu.sound_timer());
+        cpu.keys().iter().for_each(|&key| add(key as u8));
+        cpu.pixels().flat_map(|row| row.iter()).for_each(|&pixel| add(pixel as u8));
+    }
+    hash
+}
+
+fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+pub struct Session {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    // The host's key events are applied before the guest's in a frame
+    host: bool,
+    settings: Settings,
+    frame: u32,
+    // Local key events not sent yet
+    pending: Vec<KeyEvent>,
+    local: HashMap<u32, Vec<KeyEvent>>,
+    remote: HashMap<u32, Vec<KeyEvent>>,
+    local_hashes: HashMap<u32, u64>,
+    remote_hashes: HashMap<u32, u64>,
+}
+
+impl Session {
+    // Waits for the other player to connect. The host's settings are used
+    // by both players.
+    pub fn host(listener: &TcpListener, rom: &[u8], settings: Settings)
+                -> Result<Session, NetError> {
+        let (stream, _) = listener.accept()?;
+        let mut session = Session::new(stream, true, settings)?;
+        session.send(&session.hello(rom))?;
+
+        let hello = session.receive()?;
+        session.check_hello(&hello, rom)?;
+        Ok(session)
+    }
+
+    pub fn join<A: ToSocketAddrs>(addr: A, rom: &[u8]) -> Result<Session, NetError> {
+        let stream = TcpStream::connect(addr)?;
+        let mut session = Session::new(stream, false, Settings::default())?;
+
+        let hello = session.receive()?;
+        if let Message::Hello { input_delay, hash_interval, seed, .. } = hello {
+            session.settings = Settings { input_delay, hash_interval, seed };
+        }
+        session.send(&session.hello(rom))?;
+        session.check_hello(&hello, rom)?;
+        Ok(session)
+    }
+
+    fn new(stream: TcpStream, host: bool, settings: Settings) -> Result<Session, NetError> {
+        // Every frame waits on the other player, don't let Nagle add to it
+        stream.set_nodelay(true)?;
+        Ok(Session {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+            host,
+            settings,
+            frame: 0,
+            pending: Vec::new(),
+            local: HashMap::new(),
+            remote: HashMap::new(),
+            local_hashes: HashMap::new(),
+            remote_hashes: HashMap::new(),
+        })
+    }
+
+    fn hello(&self, rom: &[u8]) -> Message {
+        Message::Hello {
+            version: VERSION,
+            input_delay: self.settings.input_delay,
+            hash_interval: self.settings.hash_interval,
+            seed: self.settings.seed,
+            rom_hash: rom_hash(rom),
+        }
+    }
+
+    fn check_hello(&self, hello: &Message, rom: &[u8]) -> Result<(), NetError> {
+        match *hello {
+            Message::Hello { version, .. } if version != VERSION =>
+                Err(NetError::Mismatch(format!("protocol version {} and {}", VERSION, version))),
+            Message::Hello { rom_hash: hash, .. } if hash != rom_hash(rom) =>
+                Err(NetError::Mismatch("different ROMs".to_string())),
+            Message::Hello { .. } => Ok(()),
+            _ => Err(NetError::Mismatch("expected a hello".to_string())),
+        }
+    }
+
+    pub fn settings(&self) -> Settings {
+        self.settings
+    }
+
+    // The next frame to be stepped
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn press_key(&mut self, key: Key) {
+        self.pending.push((key as u8, true));
+    }
+
+    pub fn release_key(&mut self, key: Key) {
+        self.pending.push((key as u8, false));
+    }
+
+    // Steps the Cpu by one frame with both players' input. Blocks until the
+    // other player's input for the frame has arrived.
+    pub fn advance<R: Random>(&mut self, cpu: &mut Cpu<R>) -> Result<(), NetError> {
+        let target = self.frame + self.settings.input_delay;
+        let count = cmp::min(self.pending.len(), MAX_EVENTS);
+        let events: Vec<KeyEvent> = self.pending.drain(..count).collect();
+        self.send(&Message::Input { frame: target, events: events.clone() })?;
+        self.local.insert(target, events);
+
+        // Nobody sends input for the frames before the first delayed one
+        while self.frame >= self.settings.input_delay && !self.remote.contains_key(&self.frame) {
+            let msg = self.receive()?;
+            self.handle(msg)?;
+        }
+
+        let local = self.local.remove(&self.frame).unwrap_or_default();
+        let remote = self.remote.remove(&self.frame).unwrap_or_default();
+        let (first, second) = if self.host { (local, remote) } else { (remote, local) };
+        for (key, pressed) in first.into_iter().chain(second) {
+            let key = Key::from_index(key).unwrap();
+            if pressed {
+                cpu.press_key(key);
+            } else {
+                cpu.release_key(key);
+            }
+        }
+        cpu.step(FRAME)?;
+
+        let interval = self.settings.hash_interval;
+        if interval > 0 && self.frame.is_multiple_of(interval) {
+            let hash = state_hash(cpu);
+            self.send(&Message::Hash { frame: self.frame, hash })?;
+            self.local_hashes.insert(self.frame, hash);
+            self.compare_hashes(self.frame)?;
+        }
+
+        self.frame += 1;
+        Ok(())
+    }
+
+    // Ends the session, the other player's next frame fails
+    pub fn close(&mut self) {
+        let _ = self.writer.flush();
+        let _ = self.writer.get_ref().shutdown(Shutdown::Both);
+    }
+
+    // A player that has left is noticed when reading its next message, the
+    // messages it no longer needs can be dropped
+    fn send(&mut self, msg: &Message) -> io::Result<()> {
+        match msg.write_to(&mut self.writer).and_then(|_| self.writer.flush()) {
+            Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe ||
+                          e.kind() == io::ErrorKind::ConnectionReset => Ok(()),
+            result => result,
+        }
+    }
+
+    fn receive(&mut self) -> io::Result<Message> {
+        Message::read_from(&mut self.reader)
+    }
+
+    fn handle(&mut self, msg: Message) -> Result<(), NetError> {
+        match msg {
+            Message::Input { frame, events } => {
+                self.remote.insert(frame, events);
+                Ok(())
+            },
+            Message::Hash { frame, hash } => {
+                self.remote_hashes.insert(frame, hash);
+                self.compare_hashes(frame)
+            },
+            Message::Hello { .. } => Err(NetError::Mismatch("unexpected hello".to_string())),
+        }
+    }
+
+    fn compare_hashes(&mut self, frame: u32) -> Result<(), NetError> {
+        let (local, remote) = match (self.local_hashes.get(&frame), self.remote_hashes.get(&frame)) {
+            (Some(&local), Some(&remote)) => (local, remote),
+            _ => return Ok(()),
+        };
+        self.local_hashes.remove(&frame);
+        self.remote_hashes.remove(&frame);
+
+        if local != remote {
+            return Err(NetError::Desync { frame, local, remote });
+        }
+        Ok(())
+    }
+}
+
+// Runs a netplay session behind the Vm trait so the existing frontends can
+// drive it. Real time is turned into whole frames. After a network error
+// the session is closed and the Vm stops, the error is kept for the caller
+// to report.
+pub struct NetVm<R: Random> {
+    cpu: Cpu<R>,
+    session: Session,
+    accumulator: f64,
+    error: Option<NetError>,
+}
+
+impl<R: Random> NetVm<R> {
+    // The Cpu has to be seeded with the session's seed
+    pub fn new(cpu: Cpu<R>, session: Session) -> NetVm<R> {
+        NetVm {
+            cpu,
+            session,
+            accumulator: 0.0,
+            error: None,
+        }
+    }
+
+    pub fn cpu(&self) -> &Cpu<R> {
+        &self.cpu
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    pub fn error(&self) -> Option<&NetError> {
+        self.error.as_ref()
+    }
+}
+
+impl<R: Random> Vm for NetVm<R> {
+    fn step(&mut self, time: f64) -> Result<(), InstructionError> {
+        if self.error.is_some() {
+            return Ok(());
+        }
+
+        self.accumulator += time;
+        while self.accumulator >= FRAME {
+            self.accumulator -= FRAME;
+            match self.session.advance(&mut self.cpu) {
+                Ok(()) => (),
+                Err(NetError::Instruction(e)) => return Err(e),
+                Err(e) => {
+                    self.session.close();
+                    self.error = Some(e);
+                    return Ok(());
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // Only safe before the first frame, a ROM loaded by one player later
+    // shows up as a desync
+    fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        self.cpu.load_rom_bytes(rom)
+    }
+
+    // Like loading a ROM, a reset by one player alone shows up as a desync
+    fn reset(&mut self, keep_memory: bool) {
+        self.cpu.reset(keep_memory);
+    }
+
+    // The same goes for reloading, unless every player reloads the same ROM
+    fn reload_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        self.cpu.reload_rom_bytes(rom)
+    }
+
+    fn pixels<'a>(&'a self) -> Chunks<'a, bool> {
+        self.cpu.pixels()
+    }
+
+    fn press_key(&mut self, key: Key) {
+        self.session.press_key(key);
+    }
+
+    fn release_key(&mut self, key: Key) {
+        self.session.release_key(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8vm::XorShift;
+    use super::*;
+
+    #[test]
+    fn state_hash_changes_with_state() {
+        let mut cpu = Cpu::with_random(XorShift::new(1));
+        let before = state_hash(&cpu);
+        assert_eq!(state_hash(&Cpu::with_random(XorShift::new(1))), before);
+
+        cpu.press_key(Key::D5);
+        assert!(state_hash(&cpu) != before);
+    }
+}
@@ -0,0 +1,135 @@
+// Messages exchanged between the peers. Every message starts with a tag
+// byte, numbers are big endian.
+use std::io;
+use std::io::{ Read, Write };
+
+pub const VERSION: u16 = 1;
+
+const HELLO: u8 = 1;
+const INPUT: u8 = 2;
+const HASH: u8 = 3;
+
+// A key press (true) or release (false)
+pub type KeyEvent = (u8, bool);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    // Sent by both peers when connecting. The host's settings win, the
+    // guest echoes them back.
+    Hello { version: u16, input_delay: u32, hash_interval: u32, seed: u32, rom_hash: u64 },
+    // The sender's key events to apply before stepping `frame`, sent for
+    // every frame even when there are none
+    Input { frame: u32, events: Vec<KeyEvent> },
+    // The sender's state hash after stepping `frame`
+    Hash { frame: u32, hash: u64 },
+}
+
+impl Message {
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        match *self {
+            Message::Hello { version, input_delay, hash_interval, seed, rom_hash } => {
+                out.write_all(&[HELLO])?;
+                out.write_all(&version.to_be_bytes())?;
+                out.write_all(&input_delay.to_be_bytes())?;
+                out.write_all(&hash_interval.to_be_bytes())?;
+                out.write_all(&seed.to_be_bytes())?;
+                out.write_all(&rom_hash.to_be_bytes())
+            },
+            Message::Input { frame, ref events } => {
+                out.write_all(&[INPUT])?;
+                out.write_all(&frame.to_be_bytes())?;
+                out.write_all(&[events.len() as u8])?;
+                for &(key, pressed) in events.iter() {
+                    out.write_all(&[key, pressed as u8])?;
+                }
+                Ok(())
+            },
+            Message::Hash { frame, hash } => {
+                out.write_all(&[HASH])?;
+                out.write_all(&frame.to_be_bytes())?;
+                out.write_all(&hash.to_be_bytes())
+            },
+        }
+    }
+
+    pub fn read_from<R: Read>(input: &mut R) -> io::Result<Message> {
+        match read_u8(input)? {
+            HELLO => Ok(Message::Hello {
+                version: u16::from_be_bytes(read_array(input)?),
+                input_delay: u32::from_be_bytes(read_array(input)?),
+                hash_interval: u32::from_be_bytes(read_array(input)?),
+                seed: u32::from_be_bytes(read_array(input)?),
+                rom_hash: u64::from_be_bytes(read_array(input)?),
+            }),
+            INPUT => {
+                let frame = u32::from_be_bytes(read_array(input)?);
+                let count = read_u8(input)?;
+                let mut events = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let [key, pressed] = read_array(input)?;
+                    if key > 0xF || pressed > 1 {
+                        return Err(invalid("Invalid key event"));
+                    }
+                    events.push((key, pressed == 1));
+                }
+                Ok(Message::Input { frame, events })
+            },
+            HASH => Ok(Message::Hash {
+                frame: u32::from_be_bytes(read_array(input)?),
+                hash: u64::from_be_bytes(read_array(input)?),
+            }),
+            tag => Err(invalid(&format!("Unknown message {}", tag))),
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u8<R: Read>(input: &mut R) -> io::Result<u8> {
+    let [byte] = read_array(input)?;
+    Ok(byte)
+}
+
+fn read_array<R: Read, const N: usize>(input: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    input.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::*;
+
+    fn round_trip(msg: Message) {
+        let mut buf = Vec::new();
+        msg.write_to(&mut buf).unwrap();
+        assert_eq!(Message::read_from(&mut Cursor::new(buf)).unwrap(), msg);
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        round_trip(Message::Hello {
+            version: VERSION, input_delay: 3, hash_interval: 60, seed: 0xDEAD_BEEF,
+            rom_hash: 0x0123_4567_89AB_CDEF,
+        });
+        round_trip(Message::Input { frame: 7, events: vec![] });
+        round_trip(Message::Input { frame: 70000, events: vec![(0xA, true), (0x3, false)] });
+        round_trip(Message::Hash { frame: 120, hash: u64::MAX });
+    }
+
+    #[test]
+    fn unknown_tag_is_invalid_data() {
+        let err = Message::read_from(&mut Cursor::new(vec![0xFF])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn out_of_range_key_is_invalid_data() {
+        let err = Message::read_from(&mut Cursor::new(vec![INPUT, 0, 0, 0, 1, 1, 0x10, 1]))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,198 @@
+// Runs both players of a netplay session on loopback and checks that their
+// Cpus stay identical. The players run on two threads, or in two processes
+// by starting this test binary again as the guest.
+extern crate chip8core;
+extern crate chip8net;
+extern crate chip8vm;
+
+use std::env;
+use std::net::TcpListener;
+use std::process::Command;
+use std::thread;
+use chip8core::{ Key, Vm };
+use chip8net::{ state_hash, NetError, NetVm, Session, Settings, FRAME };
+use chip8vm::{ Cpu, Quirks, XorShift };
+
+const FRAMES: u32 = 90;
+const GUEST_ADDR: &str = "CHIP8NET_TEST_GUEST";
+
+// Counts the frames key 5 is held in V2 and those key 9 is held in V3,
+// and keeps VF busy with CXNN so both players' random numbers are checked
+const ROM: [u8; 16] = [
+    0x61, 0x05, // LD V1, 5
+    0xE1, 0xA1, // SKNP V1
+    0x72, 0x01, // ADD V2, 1
+    0x61, 0x09, // LD V1, 9
+    0xE1, 0xA1, // SKNP V1
+    0x73, 0x01, // ADD V3, 1
+    0xC4, 0xFF, // RND V4, FF
+    0x12, 0x00, // JP 200
+];
+
+// Key events (frame, key, pressed) each player makes
+const HOST_INPUT: [(u32, u8, bool); 2] = [(10, 0x5, true), (20, 0x5, false)];
+const GUEST_INPUT: [(u32, u8, bool); 2] = [(30, 0x9, true), (45, 0x9, false)];
+
+fn settings() -> Settings {
+    Settings { input_delay: 3, hash_interval: 10, seed: 42 }
+}
+
+fn cpu(session: &Session, quirks: Quirks) -> Cpu<XorShift> {
+    let mut cpu = Cpu::with_random(XorShift::new(session.settings().seed));
+    cpu.set_quirks(quirks);
+    cpu.load_rom_bytes(&ROM).unwrap();
+    cpu
+}
+
+// Plays the session, returning the state hash after every frame
+fn play(session: Session, quirks: Quirks, input: &[(u32, u8, bool)])
+        -> Result<Vec<u64>, String> {
+    let mut vm = NetVm::new(cpu(&session, quirks), session);
+    let mut hashes = Vec::new();
+
+    for frame in 0..FRAMES {
+        for &(_, key, pressed) in input.iter().filter(|&&(f, _, _)| f == frame) {
+            let key = Key::from_index(key).unwrap();
+            if pressed {
+                vm.press_key(key);
+            } else {
+                vm.release_key(key);
+            }
+        }
+        vm.step(FRAME).unwrap();
+        if let Some(err) = vm.error() {
+            return Err(err.to_string());
+        }
+        hashes.push(state_hash(vm.cpu()));
+    }
+    Ok(hashes)
+}
+
+fn play_both(host_quirks: Quirks, guest_quirks: Quirks)
+             -> (Result<Vec<u64>, String>, Result<Vec<u64>, String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let guest = thread::spawn(move || {
+        let session = Session::join(addr, &ROM).unwrap();
+        play(session, guest_quirks, &GUEST_INPUT)
+    });
+    let session = Session::host(&listener, &ROM, settings()).unwrap();
+    let host = play(session, host_quirks, &HOST_INPUT);
+    (host, guest.join().unwrap())
+}
+
+#[test]
+fn players_stay_in_lockstep() {
+    let (host, guest) = play_both(Quirks::default(), Quirks::default());
+    let (host, guest) = (host.unwrap(), guest.unwrap());
+    assert_eq!(host, guest);
+}
+
+#[test]
+fn input_is_applied_after_delay_on_both_sides() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let guest = thread::spawn(move || {
+        let session = Session::join(addr, &ROM).unwrap();
+        let mut vm = NetVm::new(cpu(&session, Quirks::default()), session);
+        let mut held = Vec::new();
+        for _ in 0..FRAMES {
+            vm.step(FRAME).unwrap();
+            held.push(vm.cpu().keys()[0x5]);
+        }
+        held
+    });
+
+    let session = Session::host(&listener, &ROM, settings()).unwrap();
+    let mut vm = NetVm::new(cpu(&session, Quirks::default()), session);
+    for frame in 0..FRAMES {
+        if frame == 10 {
+            vm.press_key(Key::D5);
+        }
+        vm.step(FRAME).unwrap();
+        // Pressed before frame 10, applied in frame 13
+        assert_eq!(vm.cpu().keys()[0x5], frame >= 13);
+    }
+    let guest_held = guest.join().unwrap();
+    assert_eq!(guest_held.iter().position(|&held| held), Some(13));
+}
+
+#[test]
+fn desync_is_detected() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // The guest ignores the host's seed, so CXNN gives different numbers
+    let guest = thread::spawn(move || {
+        let session = Session::join(addr, &ROM).unwrap();
+        let mut cpu = Cpu::with_random(XorShift::new(session.settings().seed + 1));
+        cpu.load_rom_bytes(&ROM).unwrap();
+        let mut vm = NetVm::new(cpu, session);
+        for _ in 0..FRAMES {
+            vm.step(FRAME).unwrap();
+        }
+        vm.error().map(|e| e.to_string())
+    });
+
+    let session = Session::host(&listener, &ROM, settings()).unwrap();
+    let mut vm = NetVm::new(cpu(&session, Quirks::default()), session);
+    for _ in 0..FRAMES {
+        vm.step(FRAME).unwrap();
+    }
+    let host_error = vm.error().map(|e| e.to_string());
+    let guest_error = guest.join().unwrap();
+
+    // Whoever compares first reports the desync, the other one may only see
+    // the connection close
+    assert!(host_error.is_some() && guest_error.is_some());
+    assert!(host_error.into_iter().chain(guest_error).any(|e| e.starts_with("Desync in frame")));
+}
+
+#[test]
+fn different_roms_are_rejected() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let guest = thread::spawn(move || Session::join(addr, &ROM[..14]).err());
+    let host = Session::host(&listener, &ROM, settings()).err();
+
+    for err in host.into_iter().chain(guest.join().unwrap()) {
+        match err {
+            NetError::Mismatch(_) => (),
+            err => panic!("Unexpected error {}", err),
+        }
+    }
+}
+
+// Hosts a session and starts this test binary again to join it from a
+// second process. The child only runs `guest_process`.
+#[test]
+fn players_in_two_processes_stay_in_lockstep() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let mut child = Command::new(env::current_exe().unwrap())
+        .args(["guest_process", "--exact", "--nocapture", "--test-threads=1"])
+        .env(GUEST_ADDR, addr.to_string())
+        .spawn()
+        .unwrap();
+
+    let session = Session::host(&listener, &ROM, settings()).unwrap();
+    let hashes = play(session, Quirks::default(), &HOST_INPUT).unwrap();
+    assert!(child.wait().unwrap().success(), "Guest process failed");
+    assert_eq!(hashes.len(), FRAMES as usize);
+}
+
+// The guest side of `players_in_two_processes_stay_in_lockstep`, does
+// nothing when run on its own
+#[test]
+fn guest_process() {
+    let addr = match env::var(GUEST_ADDR) {
+        Ok(addr) => addr,
+        Err(_) => return,
+    };
+    let session = Session::join(addr.as_str(), &ROM).unwrap();
+    play(session, Quirks::default(), &GUEST_INPUT).unwrap();
+}
@@ -0,0 +1,42 @@
+use std::io::{ Read, Seek };
+use zip::ZipArchive;
+use { Platform, RomError };
+
+const ZIP_MAGIC: [u8; 4] = [0x50, 0x4B, 0x03, 0x04];
+
+pub fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(&ZIP_MAGIC)
+}
+
+// Names of the entries in the archive that look like ROMs
+pub fn rom_names<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Vec<String> {
+    archive.file_names()
+        .filter(|name| !name.ends_with('/') && Platform::from_name(name).is_some())
+        .map(|name| name.to_string())
+        .collect()
+}
+
+// Reads the only ROM in the archive, or the entry called `name` if given
+pub fn read<R: Read + Seek>(reader: R, name: Option<&str>) -> Result<(String, Vec<u8>), RomError> {
+    let mut archive = ZipArchive::new(reader)?;
+
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => {
+            let mut names = rom_names(&mut archive);
+            match names.len() {
+                0 => return Err(RomError::NoRomInArchive),
+                1 => names.remove(0),
+                _ => {
+                    names.sort();
+                    return Err(RomError::AmbiguousArchive(names));
+                },
+            }
+        },
+    };
+
+    let mut file = archive.by_name(&name)?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok((name, data))
+}
@@ -0,0 +1,72 @@
+use RomError;
+
+// Parses Octo style hex dumps, e.g. `0x6A 0x25 0xA2 0x1E`. Bytes may also be
+// written without a prefix or run together (`6A25A21E`), separated by
+// whitespace or commas. Everything after a `#` on a line is a comment.
+pub fn parse(text: &str) -> Result<Vec<u8>, RomError> {
+    let mut bytes = Vec::new();
+
+    for (line_idx, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap();
+        let tokens = line.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|t| !t.is_empty());
+
+        for token in tokens {
+            let invalid = || RomError::InvalidHex { line: line_idx + 1, token: token.to_string() };
+
+            let digits = if token.starts_with("0x") || token.starts_with("0X") {
+                &token[2..]
+            } else {
+                token
+            };
+
+            if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+
+            if digits.len() <= 2 {
+                bytes.push(u8::from_str_radix(digits, 16).unwrap());
+            } else if digits.len() % 2 == 0 {
+                for i in (0..digits.len()).step_by(2) {
+                    bytes.push(u8::from_str_radix(&digits[i..i + 2], 16).unwrap());
+                }
+            } else {
+                return Err(invalid());
+            }
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_prefixed_bytes() {
+        assert_eq!(parse("0x6A 0x25\n0xa2,0x1E").unwrap(), vec![0x6A, 0x25, 0xA2, 0x1E]);
+    }
+
+    #[test]
+    fn parse_runs_of_digits() {
+        assert_eq!(parse("6A25 A21E\n00E0").unwrap(), vec![0x6A, 0x25, 0xA2, 0x1E, 0x00, 0xE0]);
+    }
+
+    #[test]
+    fn parse_skips_comments() {
+        let text = "# Generated by Octo\n0x00 0xE0 # clear\n";
+        assert_eq!(parse(text).unwrap(), vec![0x00, 0xE0]);
+    }
+
+    #[test]
+    fn parse_reports_invalid_token() {
+        match parse("0x00 0xE0\n0x12 zz") {
+            Err(RomError::InvalidHex { line, token }) => {
+                assert_eq!(line, 2);
+                assert_eq!(token, "zz");
+            },
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
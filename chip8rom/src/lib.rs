@@ -0,0 +1,271 @@
+extern crate chip8core;
+extern crate zip;
+#[cfg(test)]
+extern crate chip8vm;
+mod archive;
+mod hex;
+mod watch;
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{ Cursor, Read };
+use std::path::Path;
+use chip8core::PROGRAM_START;
+use zip::result::ZipError;
+
+pub use watch::{ WatchVm, Watcher, POLL_INTERVAL };
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    // Detects the platform from the extension of a file name
+    pub fn from_name(name: &str) -> Option<Platform> {
+        let ext = match Path::new(name).extension().and_then(|e| e.to_str()) {
+            Some(ext) => ext.to_ascii_lowercase(),
+            None => return None,
+        };
+
+        match ext.as_str() {
+            "ch8" => Some(Platform::Chip8),
+            "sc8" => Some(Platform::SuperChip),
+            "xo8" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    // XO-CHIP has 64 KiB, but every Vm here has CHIP-8 memory, so larger
+    // ROMs would only fail once loaded
+    pub fn memory_size(self) -> usize {
+        chip8core::MEMORY_SIZE
+    }
+
+    // Largest ROM that fits in memory when loaded at PROGRAM_START
+    pub fn max_rom_size(self) -> usize {
+        self.memory_size() - PROGRAM_START
+    }
+}
+
+#[derive(Debug)]
+pub struct Rom {
+    pub name: String,
+    pub platform: Platform,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum RomError {
+    Io(io::Error),
+    Zip(ZipError),
+    TooLarge { size: usize, max: usize },
+    InvalidHex { line: usize, token: String },
+    NoRomInArchive,
+    // The archive holds several ROMs, pick one with `load_zip_entry`
+    AmbiguousArchive(Vec<String>),
+    // The Vm refused to load it
+    Vm(chip8core::RomError),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RomError::Io(ref e) => write!(f, "{}", e),
+            RomError::Zip(ref e) => write!(f, "{}", e),
+            RomError::TooLarge { size, max } =>
+                write!(f, "ROM is {} bytes, only {} bytes fit in memory", size, max),
+            RomError::InvalidHex { line, ref token } =>
+                write!(f, "Invalid hex byte '{}' on line {}", token, line),
+            RomError::NoRomInArchive => write!(f, "Archive does not contain a ROM"),
+            RomError::AmbiguousArchive(ref names) =>
+                write!(f, "Archive contains several ROMs: {}", names.join(", ")),
+            RomError::Vm(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for RomError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            RomError::Io(ref e) => Some(e),
+            RomError::Zip(ref e) => Some(e),
+            RomError::Vm(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RomError {
+    fn from(e: io::Error) -> RomError {
+        RomError::Io(e)
+    }
+}
+
+impl From<chip8core::RomError> for RomError {
+    fn from(e: chip8core::RomError) -> RomError {
+        RomError::Vm(e)
+    }
+}
+
+impl From<ZipError> for RomError {
+    fn from(e: ZipError) -> RomError {
+        RomError::Zip(e)
+    }
+}
+
+// Loads a raw ROM, a zip archive or a hex dump from a file. The format is
+// picked from the extension, archives are also recognized by their contents.
+pub fn load_path<P: AsRef<Path>>(path: P) -> Result<Rom, RomError> {
+    let path = path.as_ref();
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    from_bytes(&name, &data)
+}
+
+// Loads a single entry out of a zip archive holding several ROMs
+pub fn load_zip_entry<P: AsRef<Path>>(path: P, entry: &str) -> Result<Rom, RomError> {
+    let (name, data) = archive::read(File::open(path)?, Some(entry))?;
+    raw(&name, data)
+}
+
+// Loads a ROM held in memory, `name` is only used to detect the format and platform
+pub fn from_bytes(name: &str, data: &[u8]) -> Result<Rom, RomError> {
+    let ext = Path::new(name).extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match ext.as_str() {
+        "zip" => zip(data),
+        "hex" => from_hex(name, data),
+        _ if Platform::from_name(name).is_some() => raw(name, data.to_vec()),
+        _ if archive::is_zip(data) => zip(data),
+        _ => raw(name, data.to_vec()),
+    }
+}
+
+// Loads a hex dump whatever its name. Binary ROMs can consist of nothing
+// but hex digits too, so hex dumps are never guessed from their contents.
+pub fn from_hex(name: &str, data: &[u8]) -> Result<Rom, RomError> {
+    raw(name, hex::parse(&String::from_utf8_lossy(data))?)
+}
+
+fn zip(data: &[u8]) -> Result<Rom, RomError> {
+    let (name, data) = archive::read(Cursor::new(data), None)?;
+    raw(&name, data)
+}
+
+fn raw(name: &str, data: Vec<u8>) -> Result<Rom, RomError> {
+    let platform = Platform::from_name(name).unwrap_or(Platform::Chip8);
+    let max = platform.max_rom_size();
+    if data.len() > max {
+        return Err(RomError::TooLarge { size: data.len(), max });
+    }
+
+    Ok(Rom {
+        name: name.to_string(),
+        platform,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::ZipWriter;
+    use zip::write::FileOptions;
+
+    fn zip_of(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for &(name, data) in entries {
+            zip.start_file(name, FileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn platform_from_extension() {
+        assert_eq!(Platform::from_name("pong.ch8"), Some(Platform::Chip8));
+        assert_eq!(Platform::from_name("ANT.SC8"), Some(Platform::SuperChip));
+        assert_eq!(Platform::from_name("dir/game.xo8"), Some(Platform::XoChip));
+        assert_eq!(Platform::from_name("readme.txt"), None);
+    }
+
+    #[test]
+    fn from_bytes_raw_rom() {
+        let rom = from_bytes("pong.sc8", &[0x00, 0xE0]).unwrap();
+
+        assert_eq!(rom.name, "pong.sc8");
+        assert_eq!(rom.platform, Platform::SuperChip);
+        assert_eq!(rom.data, vec![0x00, 0xE0]);
+    }
+
+    #[test]
+    fn from_bytes_hex_dump() {
+        let rom = from_bytes("pong.hex", b"0x00 0xE0 0x12 0x00").unwrap();
+        assert_eq!(rom.data, vec![0x00, 0xE0, 0x12, 0x00]);
+
+        let rom = from_hex("stdin", b"00E0 1200\n").unwrap();
+        assert_eq!(rom.data, vec![0x00, 0xE0, 0x12, 0x00]);
+
+        // Only the name or the caller makes it a hex dump
+        let rom = from_bytes("stdin", b"00E0 1200\n").unwrap();
+        assert_eq!(rom.data, b"00E0 1200\n".to_vec());
+    }
+
+    #[test]
+    fn from_bytes_too_large() {
+        let data = vec![0; 4096 - 0x200 + 1];
+        match from_bytes("big.ch8", &data) {
+            Err(RomError::TooLarge { size, max }) => {
+                assert_eq!(size, 3585);
+                assert_eq!(max, 3584);
+            },
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        assert!(from_bytes("big.xo8", &data).is_err());
+    }
+
+    #[test]
+    fn from_bytes_zip_with_single_rom() {
+        let data = zip_of(&[("README.txt", b"hello"), ("games/tank.xo8", &[0x00, 0xE0])]);
+
+        let rom = from_bytes("tank.zip", &data).unwrap();
+        assert_eq!(rom.name, "games/tank.xo8");
+        assert_eq!(rom.platform, Platform::XoChip);
+        assert_eq!(rom.data, vec![0x00, 0xE0]);
+
+        // Archives are detected by content as well
+        assert!(from_bytes("stdin", &data).is_ok());
+    }
+
+    #[test]
+    fn from_bytes_zip_with_several_roms() {
+        let data = zip_of(&[("b.ch8", &[0x00]), ("a.sc8", &[0x00])]);
+
+        match from_bytes("pack.zip", &data) {
+            Err(RomError::AmbiguousArchive(names)) => assert_eq!(names, vec!["a.sc8", "b.ch8"]),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn from_bytes_zip_without_rom() {
+        let data = zip_of(&[("README.txt", b"hello")]);
+
+        match from_bytes("pack.zip", &data) {
+            Err(RomError::NoRomInArchive) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
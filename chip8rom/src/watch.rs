@@ -0,0 +1,212 @@
+// Hot reload for ROM authors: the ROM file is loaded again and the Vm reset
+// whenever the file changes on disk, so edits show up without restarting.
+use std::fs;
+use std::path::{ Path, PathBuf };
+use std::slice::Chunks;
+use std::time::{ Duration, Instant, SystemTime };
+use chip8core::{ DebugVm, InstructionError, Key, Vm };
+use { load_path, Rom, RomError };
+
+// How often the file is checked
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct Watcher {
+    path: PathBuf,
+    interval: Duration,
+    last_poll: Option<Instant>,
+    // Modification time and length, an editor writing the file within the
+    // same tick of a coarse clock still changes the length most of the time
+    stamp: Option<(SystemTime, u64)>,
+}
+
+impl Watcher {
+    pub fn new<P: AsRef<Path>>(path: P) -> Watcher {
+        Watcher::with_interval(path, POLL_INTERVAL)
+    }
+
+    // The file as it is now counts as loaded already
+    pub fn with_interval<P: AsRef<Path>>(path: P, interval: Duration) -> Watcher {
+        let path = path.as_ref().to_path_buf();
+        let stamp = stamp(&path);
+        Watcher { path, interval, last_poll: None, stamp }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // The ROM loaded again when the file changed since the last call. A
+    // file that is missing for a moment, like while an editor replaces it,
+    // is waited for.
+    pub fn poll(&mut self) -> Option<Result<Rom, RomError>> {
+        let now = Instant::now();
+        if self.last_poll.is_some_and(|last| now.duration_since(last) < self.interval) {
+            return None;
+        }
+        self.last_poll = Some(now);
+
+        let stamp = stamp(&self.path);
+        if stamp.is_none() || stamp == self.stamp {
+            return None;
+        }
+        self.stamp = stamp;
+        Some(load_path(&self.path))
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+// Called with the ROM, or why it failed to load, after every reload
+type OnReload = Box<dyn FnMut(Result<&Rom, &RomError>)>;
+
+// Checks the ROM file before every step. A changed ROM is loaded the way
+// the Vm loaded the first one, one that fails to load leaves the Vm running
+// the previous one.
+pub struct WatchVm<V: Vm> {
+    vm: V,
+    watcher: Watcher,
+    on_reload: OnReload,
+}
+
+impl<V: Vm> WatchVm<V> {
+    pub fn new(vm: V, watcher: Watcher) -> WatchVm<V> {
+        WatchVm { vm, watcher, on_reload: Box::new(|_| ()) }
+    }
+
+    // Called after every reload, e.g. to tell the user it failed
+    pub fn on_reload<F: FnMut(Result<&Rom, &RomError>) + 'static>(&mut self, f: F) {
+        self.on_reload = Box::new(f);
+    }
+
+    pub fn vm(&mut self) -> &mut V {
+        &mut self.vm
+    }
+
+    pub fn into_inner(self) -> V {
+        self.vm
+    }
+
+    fn reload(&mut self) {
+        let rom = match self.watcher.poll() {
+            Some(rom) => rom,
+            None => return,
+        };
+        let loaded = rom.and_then(|rom| {
+            self.vm.reload_rom_bytes(&rom.data)?;
+            Ok(rom)
+        });
+        (self.on_reload)(loaded.as_ref());
+    }
+}
+
+impl<V: Vm> Vm for WatchVm<V> {
+    fn step(&mut self, time: f64) -> Result<(), InstructionError> {
+        self.reload();
+        self.vm.step(time)
+    }
+
+    fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), chip8core::RomError> {
+        self.vm.load_rom_bytes(rom)
+    }
+
+    fn reset(&mut self, keep_memory: bool) {
+        self.vm.reset(keep_memory);
+    }
+
+    fn reload_rom_bytes(&mut self, rom: &[u8]) -> Result<(), chip8core::RomError> {
+        self.vm.reload_rom_bytes(rom)
+    }
+
+    fn pixels<'a>(&'a self) -> Chunks<'a, bool> {
+        self.vm.pixels()
+    }
+
+    fn press_key(&mut self, key: Key) {
+        self.vm.press_key(key);
+    }
+
+    fn release_key(&mut self, key: Key) {
+        self.vm.release_key(key);
+    }
+
+    fn debug(&mut self) -> Option<&mut dyn DebugVm> {
+        self.vm.debug()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::env;
+    use std::process;
+    use std::rc::Rc;
+    use chip8vm::{ Cpu, LoadOptions };
+    use super::*;
+
+    fn rom_file(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("chip8rom-{}-{}.ch8", name, process::id()))
+    }
+
+    #[test]
+    fn watcher_only_loads_changed_files() {
+        let path = rom_file("watcher");
+        fs::write(&path, [0x60, 0x07]).unwrap();
+        let mut watcher = Watcher::with_interval(&path, Duration::from_secs(0));
+        assert!(watcher.poll().is_none());
+
+        fs::write(&path, [0x60, 0x07, 0x61, 0x08]).unwrap();
+        assert_eq!(watcher.poll().unwrap().unwrap().data, vec![0x60, 0x07, 0x61, 0x08]);
+        assert!(watcher.poll().is_none());
+
+        fs::remove_file(&path).unwrap();
+        assert!(watcher.poll().is_none());
+    }
+
+    #[test]
+    fn watch_vm_resets_into_the_new_rom() {
+        let path = rom_file("vm");
+        // LD V0, 7; JP 202
+        fs::write(&path, [0x60, 0x07, 0x12, 0x02]).unwrap();
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes(&[0x60, 0x07, 0x12, 0x02]).unwrap();
+        let mut vm = WatchVm::new(cpu, Watcher::with_interval(&path, Duration::from_secs(0)));
+        let reloads = Rc::new(Cell::new(0));
+        let counter = reloads.clone();
+        vm.on_reload(move |rom| {
+            assert!(rom.is_ok());
+            counter.set(counter.get() + 1);
+        });
+
+        vm.step(0.01).unwrap();
+        assert_eq!(vm.vm().registers().v[0], 7);
+
+        fs::write(&path, [0x61, 0x08, 0x12, 0x02, 0x00, 0xE0]).unwrap();
+        vm.step(0.0).unwrap();
+        assert_eq!(reloads.get(), 1);
+        let cpu = vm.into_inner();
+        assert_eq!(cpu.registers().v[0], 0);
+        assert_eq!(cpu.registers().pc, 0x200);
+        assert_eq!(&cpu.memory()[0x200..0x206], &[0x61, 0x08, 0x12, 0x02, 0x00, 0xE0]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn watch_vm_reloads_at_the_original_address() {
+        let path = rom_file("eti660");
+        fs::write(&path, [0x60, 0x07]).unwrap();
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes_with(&[0x60, 0x07], &LoadOptions::eti660()).unwrap();
+        let mut vm = WatchVm::new(cpu, Watcher::with_interval(&path, Duration::from_secs(0)));
+
+        fs::write(&path, [0x61, 0x08, 0x16, 0x02]).unwrap();
+        vm.step(0.0).unwrap();
+        let cpu = vm.into_inner();
+        assert_eq!(cpu.registers().pc, 0x600);
+        assert_eq!(&cpu.memory()[0x600..0x604], &[0x61, 0x08, 0x16, 0x02]);
+        assert_eq!(cpu.memory()[0x200], 0);
+        fs::remove_file(&path).unwrap();
+    }
+}
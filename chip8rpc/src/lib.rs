@@ -0,0 +1,215 @@
+// Remote control for scripting the emulator, e.g. from test rigs. Clients
+// connect over localhost TCP or a Unix socket and send JSON-RPC 2.0
+// requests, one per line, and get one response line per request back.
+// Requests are answered between frames, so they always see the Vm between
+// two steps.
+//
+// Methods, with their params:
+//   load_rom      { path } or { data }     data is hex encoded, starts from power-on
+//   reset         { keep_memory }          back to power-on, memory kept if true
+//   pause, resume                          the frontend's clock stops driving the Vm
+//   step          { frames }               runs whole frames, defaults to 1
+//   press_key     { key }                  0-15 or a hex digit
+//   release_key   { key }
+//   registers
+//   read_memory   { address, length }      returns { data }
+//   write_memory  { address, data }
+//   screenshot                             returns { width, height, rows }
+//   save_state                             returns { state }
+//   load_state    { state }
+extern crate chip8core;
+#[macro_use]
+extern crate serde_json;
+mod methods;
+mod transport;
+
+use std::io;
+use std::io::{ ErrorKind, Read, Write };
+use std::net::{ SocketAddr, ToSocketAddrs };
+#[cfg(unix)]
+use std::path::Path;
+use std::slice::Chunks;
+use chip8core::{ DebugVm, InstructionError, Key, RomError, Vm };
+use transport::{ Listener, Stream };
+
+// The step method counts in frames of the 60 Hz timer
+pub const FRAME: f64 = 1.0 / 60.0;
+
+// Longest request a client may send, larger ones drop the client
+const MAX_LINE: usize = 64 * 1024;
+
+struct Client {
+    stream: Box<dyn Stream>,
+    // Received bytes not yet ending in a newline
+    pending: Vec<u8>,
+}
+
+impl Client {
+    // Reads what has arrived and answers every complete line. Returns false
+    // once the client is gone.
+    fn serve<F: FnMut(&str) -> Option<String>>(&mut self, handle: &mut F) -> bool {
+        let mut buffer = [0; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return false,
+                Ok(n) => self.pending.extend_from_slice(&buffer[..n]),
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..(end + 1)).collect();
+            let line = String::from_utf8_lossy(&line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = handle(line.trim()) {
+                if self.send(&response).is_err() {
+                    return false;
+                }
+            }
+        }
+        self.pending.len() <= MAX_LINE
+    }
+
+    // Waits until the whole response is written
+    fn send(&mut self, response: &str) -> io::Result<()> {
+        self.stream.set_nonblocking(false)?;
+        self.stream.write_all(response.as_bytes())?;
+        self.stream.write_all(b"\n")?;
+        self.stream.flush()?;
+        self.stream.set_nonblocking(true)
+    }
+}
+
+pub struct Server {
+    listener: Listener,
+    clients: Vec<Client>,
+}
+
+impl Server {
+    // "unix:PATH" for a Unix socket, otherwise a TCP address
+    pub fn bind(addr: &str) -> io::Result<Server> {
+        if let Some(path) = addr.strip_prefix("unix:") {
+            #[cfg(unix)]
+            return Server::bind_unix(path);
+            #[cfg(not(unix))]
+            return Err(io::Error::new(ErrorKind::InvalidInput,
+                                      "Unix sockets are not supported on this platform"));
+        }
+        Server::bind_tcp(addr)
+    }
+
+    // Only loopback addresses are accepted
+    pub fn bind_tcp<A: ToSocketAddrs>(addr: A) -> io::Result<Server> {
+        Listener::tcp(addr).map(Server::new)
+    }
+
+    #[cfg(unix)]
+    pub fn bind_unix<P: AsRef<Path>>(path: P) -> io::Result<Server> {
+        Listener::unix(path).map(Server::new)
+    }
+
+    fn new(listener: Listener) -> Server {
+        Server {
+            listener,
+            clients: Vec::new(),
+        }
+    }
+
+    // The TCP address, to find the port when bound to port 0
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    // Accepts waiting clients and passes each request line that has arrived
+    // to `handle`, sending back the response it returns. Never blocks on
+    // clients that are still typing.
+    pub fn poll<F: FnMut(&str) -> Option<String>>(&mut self, mut handle: F) {
+        while let Ok(Some(stream)) = self.listener.accept() {
+            self.clients.push(Client { stream, pending: Vec::new() });
+        }
+        self.clients.retain_mut(|client| client.serve(&mut handle));
+    }
+}
+
+// Puts a Vm under remote control behind the Vm trait, so the existing
+// frontends can drive it. Requests are answered at the start of every step.
+pub struct RpcVm<V: DebugVm> {
+    vm: V,
+    server: Server,
+    paused: bool,
+}
+
+impl<V: DebugVm> RpcVm<V> {
+    pub fn new(vm: V, server: Server) -> RpcVm<V> {
+        RpcVm {
+            vm,
+            server,
+            paused: false,
+        }
+    }
+
+    pub fn vm(&self) -> &V {
+        &self.vm
+    }
+
+    pub fn into_inner(self) -> V {
+        self.vm
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Starting paused lets a script set things up before the first frame
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn poll(&mut self) {
+        let vm = &mut self.vm;
+        let paused = &mut self.paused;
+        self.server.poll(|line| methods::handle(vm, paused, line));
+    }
+}
+
+impl<V: DebugVm> Vm for RpcVm<V> {
+    fn step(&mut self, time: f64) -> Result<(), InstructionError> {
+        self.poll();
+        if self.paused {
+            return Ok(());
+        }
+        self.vm.step(time)
+    }
+
+    fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        self.vm.load_rom_bytes(rom)
+    }
+
+    fn reset(&mut self, keep_memory: bool) {
+        self.vm.reset(keep_memory);
+    }
+
+    fn reload_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        self.vm.reload_rom_bytes(rom)
+    }
+
+    fn pixels<'a>(&'a self) -> Chunks<'a, bool> {
+        self.vm.pixels()
+    }
+
+    fn press_key(&mut self, key: Key) {
+        self.vm.press_key(key);
+    }
+
+    fn release_key(&mut self, key: Key) {
+        self.vm.release_key(key);
+    }
+
+    fn debug(&mut self) -> Option<&mut dyn DebugVm> {
+        Some(&mut self.vm)
+    }
+}
@@ -0,0 +1,218 @@
+use std::fmt;
+use std::fs;
+use serde_json::{ self, Map, Value };
+use chip8core::{ DebugVm, Key };
+use super::FRAME;
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// The request was fine but the Vm refused it
+const VM_ERROR: i64 = -32000;
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new<M: fmt::Display>(code: i64, message: M) -> RpcError {
+        RpcError { code, message: message.to_string() }
+    }
+
+    fn params<M: fmt::Display>(message: M) -> RpcError {
+        RpcError::new(INVALID_PARAMS, message)
+    }
+
+    fn vm<M: fmt::Display>(message: M) -> RpcError {
+        RpcError::new(VM_ERROR, message)
+    }
+}
+
+// Answers one request line, None for notifications
+pub fn handle<V: DebugVm>(vm: &mut V, paused: &mut bool, line: &str) -> Option<String> {
+    let (id, result) = match serde_json::from_str::<Value>(line) {
+        Ok(Value::Object(request)) => {
+            let result = call(vm, paused, &request);
+            match request.get("id") {
+                Some(id) => (id.clone(), result),
+                None => return None,
+            }
+        },
+        Ok(_) => (Value::Null, Err(RpcError::new(INVALID_REQUEST, "Request must be an object"))),
+        Err(e) => (Value::Null, Err(RpcError::new(PARSE_ERROR, e))),
+    };
+
+    let response = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": e.code, "message": e.message },
+        }),
+    };
+    Some(response.to_string())
+}
+
+fn call<V: DebugVm>(vm: &mut V, paused: &mut bool, request: &Map<String, Value>)
+                    -> Result<Value, RpcError> {
+    let method = request.get("method").and_then(Value::as_str)
+        .ok_or_else(|| RpcError::new(INVALID_REQUEST, "Request has no method"))?;
+    let no_params = Map::new();
+    let params = match request.get("params") {
+        None | Some(Value::Null) => &no_params,
+        Some(Value::Object(params)) => params,
+        Some(_) => return Err(RpcError::params("Params must be an object")),
+    };
+
+    match method {
+        "load_rom" => {
+            let rom = match (params.get("path"), params.get("data")) {
+                (Some(Value::String(path)), None) => fs::read(path)
+                    .map_err(|e| RpcError::vm(format!("Failed to read {}: {}", path, e)))?,
+                (None, Some(_)) => hex_param(params, "data")?,
+                _ => return Err(RpcError::params("Expected either path or data")),
+            };
+            vm.reload_rom_bytes(&rom).map_err(RpcError::vm)?;
+            Ok(Value::Null)
+        },
+        "reset" => {
+            let keep_memory = match params.get("keep_memory") {
+                None => false,
+                Some(&Value::Bool(keep)) => keep,
+                Some(_) => return Err(RpcError::params("keep_memory must be true or false")),
+            };
+            vm.reset(keep_memory);
+            Ok(Value::Null)
+        },
+        "pause" | "resume" => {
+            *paused = method == "pause";
+            Ok(json!({ "paused": *paused }))
+        },
+        // Runs whole frames right away, also while paused
+        "step" => {
+            let frames = match params.get("frames") {
+                Some(_) => int_param(params, "frames", u32::MAX as u64)?,
+                None => 1,
+            };
+            for frame in 0..frames {
+                vm.step(FRAME).map_err(|e| {
+                    RpcError::vm(format!("{:?} instruction in frame {}", e, frame))
+                })?;
+            }
+            Ok(json!({ "frames": frames }))
+        },
+        "press_key" => {
+            vm.press_key(key_param(params)?);
+            Ok(Value::Null)
+        },
+        "release_key" => {
+            vm.release_key(key_param(params)?);
+            Ok(Value::Null)
+        },
+        "registers" => {
+            let r = vm.registers();
+            Ok(json!({
+                "v": r.v,
+                "i": r.i,
+                "pc": r.pc,
+                "sp": r.sp,
+                "stack": r.stack,
+                "delay_timer": r.delay_timer,
+                "sound_timer": r.sound_timer,
+            }))
+        },
+        "read_memory" => {
+            let memory = vm.memory();
+            let address = int_param(params, "address", memory.len() as u64)? as usize;
+            let length = int_param(params, "length", (memory.len() - address) as u64)? as usize;
+            Ok(json!({ "data": to_hex(&memory[address..(address + length)]) }))
+        },
+        "write_memory" => {
+            let address = int_param(params, "address", vm.memory().len() as u64)? as usize;
+            let data = hex_param(params, "data")?;
+            vm.write_memory(address, &data).map_err(RpcError::vm)?;
+            Ok(Value::Null)
+        },
+        // One string per row, 1 for lit pixels and 0 for dark ones
+        "screenshot" => {
+            let rows: Vec<String> = vm.pixels()
+                .map(|row| row.iter().map(|&on| if on { '1' } else { '0' }).collect())
+                .collect();
+            let width = rows.first().map_or(0, |row| row.len());
+            Ok(json!({ "width": width, "height": rows.len(), "rows": rows }))
+        },
+        "save_state" => Ok(json!({ "state": to_hex(&vm.save_state()) })),
+        "load_state" => {
+            let state = hex_param(params, "state")?;
+            vm.load_state(&state).map_err(RpcError::vm)?;
+            Ok(Value::Null)
+        },
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method {}", method))),
+    }
+}
+
+fn int_param(params: &Map<String, Value>, name: &str, max: u64) -> Result<u64, RpcError> {
+    params.get(name).and_then(Value::as_u64).filter(|&n| n <= max)
+        .ok_or_else(|| RpcError::params(format!("{} must be a number up to {}", name, max)))
+}
+
+fn hex_param(params: &Map<String, Value>, name: &str) -> Result<Vec<u8>, RpcError> {
+    params.get(name).and_then(Value::as_str).and_then(from_hex)
+        .ok_or_else(|| RpcError::params(format!("{} must be a hex string", name)))
+}
+
+// Either the key's index or its hex digit, so 10 and "A" are the same key
+fn key_param(params: &Map<String, Value>) -> Result<Key, RpcError> {
+    let index = match params.get("key") {
+        Some(Value::String(digit)) if digit.len() == 1 => u8::from_str_radix(digit, 16).ok(),
+        Some(key) => key.as_u64().filter(|&n| n < 16).map(|n| n as u8),
+        None => None,
+    };
+    index.and_then(Key::from_index)
+        .ok_or_else(|| RpcError::params("key must be 0-15 or a hex digit"))
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|n| u8::from_str_radix(&s[n..(n + 2)], 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_round_trips() {
+        assert_eq!(to_hex(&[0x00, 0xA2, 0xFF]), "00a2ff");
+        assert_eq!(from_hex("00a2FF"), Some(vec![0x00, 0xA2, 0xFF]));
+        assert_eq!(from_hex(""), Some(vec![]));
+    }
+
+    #[test]
+    fn from_hex_rejects_bad_input() {
+        assert_eq!(from_hex("abc"), None);
+        assert_eq!(from_hex("zz"), None);
+        assert_eq!(from_hex("+1"), None);
+        assert_eq!(from_hex("é1"), None);
+    }
+
+    #[test]
+    fn key_param_takes_index_or_digit() {
+        let params = |key: Value| json!({ "key": key }).as_object().unwrap().clone();
+        assert_eq!(key_param(&params(json!(10))).unwrap() as u8, 0xA);
+        assert_eq!(key_param(&params(json!("a"))).unwrap() as u8, 0xA);
+        assert!(key_param(&params(json!(16))).is_err());
+        assert!(key_param(&params(json!("10"))).is_err());
+        assert!(key_param(&Map::new()).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+#[cfg(unix)]
+use std::fs;
+use std::io;
+use std::io::{ Read, Write };
+use std::net::{ SocketAddr, TcpListener, TcpStream, ToSocketAddrs };
+#[cfg(unix)]
+use std::os::unix::net::{ UnixListener, UnixStream };
+#[cfg(unix)]
+use std::path::{ Path, PathBuf };
+
+pub trait Stream: Read + Write {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+
+impl Stream for TcpStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        TcpStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+#[cfg(unix)]
+impl Stream for UnixStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        UnixStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+// Accepts clients without blocking, so it can be polled between frames
+pub enum Listener {
+    Tcp(TcpListener),
+    // The socket file is removed again when the listener is dropped
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    // Anyone who can reach the port controls the emulator, so only the
+    // loopback interface is allowed
+    pub fn tcp<A: ToSocketAddrs>(addr: A) -> io::Result<Listener> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        if addrs.is_empty() || addrs.iter().any(|a| !a.ip().is_loopback()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "Remote control only listens on loopback addresses"));
+        }
+        let listener = TcpListener::bind(&addrs[..])?;
+        listener.set_nonblocking(true)?;
+        Ok(Listener::Tcp(listener))
+    }
+
+    #[cfg(unix)]
+    pub fn unix<P: AsRef<Path>>(path: P) -> io::Result<Listener> {
+        let listener = UnixListener::bind(path.as_ref())?;
+        listener.set_nonblocking(true)?;
+        Ok(Listener::Unix(listener, path.as_ref().to_path_buf()))
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match *self {
+            Listener::Tcp(ref listener) => listener.local_addr().ok(),
+            #[cfg(unix)]
+            Listener::Unix(..) => None,
+        }
+    }
+
+    // None when no client is waiting
+    pub fn accept(&self) -> io::Result<Option<Box<dyn Stream>>> {
+        let stream: io::Result<Box<dyn Stream>> = match *self {
+            Listener::Tcp(ref listener) => listener.accept().map(|(s, _)| Box::new(s) as _),
+            #[cfg(unix)]
+            Listener::Unix(ref listener, _) => listener.accept().map(|(s, _)| Box::new(s) as _),
+        };
+        match stream {
+            Ok(stream) => {
+                stream.set_nonblocking(true)?;
+                Ok(Some(stream))
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            if let Listener::Unix(_, ref path) = *self {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
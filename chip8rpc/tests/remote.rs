@@ -0,0 +1,364 @@
+// Drives an RpcVm the way a frontend would while a client thread sends it
+// requests over a real socket.
+extern crate chip8core;
+extern crate chip8rpc;
+extern crate chip8vm;
+#[macro_use]
+extern crate serde_json;
+
+use std::env;
+use std::io::{ BufRead, BufReader, Write };
+use std::net::TcpStream;
+use std::process;
+use std::thread;
+use std::time::Duration;
+use chip8core::{ DebugVm, Vm };
+use chip8rpc::{ RpcVm, Server };
+use chip8vm::Cpu;
+use serde_json::Value;
+
+// Counts frames in V0 through the delay timer and draws the digit 0.
+// Waits for a key into V2 first.
+const ROM: [u8; 20] = [
+    0xF2, 0x0A, // LD V2, K
+    0xA0, 0x00, // LD I, 0
+    0xD1, 0x15, // DRW V1, V1, 5
+    0x63, 0x01, // LD V3, 1
+    0xF3, 0x15, // LD DT, V3
+    0xF3, 0x07, // LD V3, DT
+    0x33, 0x00, // SE V3, 0
+    0x12, 0x0A, // JP 20A
+    0x70, 0x01, // ADD V0, 1
+    0x12, 0x06, // JP 206
+];
+
+struct Client<S: BufRead + Write> {
+    stream: S,
+    next_id: u64,
+}
+
+impl<S: BufRead + Write> Client<S> {
+    fn send(&mut self, line: &str) -> Value {
+        self.stream.write_all(line.as_bytes()).unwrap();
+        self.stream.write_all(b"\n").unwrap();
+        let mut response = String::new();
+        self.stream.read_line(&mut response).unwrap();
+        serde_json::from_str(&response).unwrap()
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let request = json!({ "jsonrpc": "2.0", "id": self.next_id, "method": method,
+                              "params": params });
+        let response = self.send(&request.to_string());
+        assert_eq!(response["id"], self.next_id);
+        response
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Value {
+        let response = self.request(method, params);
+        assert!(response.get("error").is_none(), "{} failed: {}", method, response);
+        response["result"].clone()
+    }
+}
+
+struct Duplex(BufReader<TcpStream>);
+
+impl Duplex {
+    fn connect(server: &Server) -> Duplex {
+        Duplex(BufReader::new(TcpStream::connect(server.local_addr().unwrap()).unwrap()))
+    }
+}
+
+impl std::io::Read for Duplex {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl BufRead for Duplex {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.0.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.0.consume(amt)
+    }
+}
+
+impl Write for Duplex {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.get_mut().flush()
+    }
+}
+
+// Steps the Vm like a frontend until the script is done. The Vm starts
+// paused so scripts see it before its first instruction.
+fn serve<S, F>(server: Server, stream: S, script: F) -> Cpu
+    where S: BufRead + Write + Send + 'static, F: FnOnce(&mut Client<S>) + Send + 'static {
+    let mut cpu = Cpu::new();
+    cpu.load_rom_bytes(&ROM).unwrap();
+    let mut vm = RpcVm::new(cpu, server);
+    vm.set_paused(true);
+
+    let client = thread::spawn(move || script(&mut Client { stream, next_id: 0 }));
+    while !client.is_finished() {
+        vm.step(0.001).unwrap();
+        thread::sleep(Duration::from_millis(1));
+    }
+    client.join().unwrap();
+    vm.into_inner()
+}
+
+fn tcp_server() -> Server {
+    Server::bind_tcp("127.0.0.1:0").unwrap()
+}
+
+fn run_script<F: FnOnce(&mut Client<Duplex>) + Send + 'static>(script: F) -> Cpu {
+    let server = tcp_server();
+    let stream = Duplex::connect(&server);
+    serve(server, stream, script)
+}
+
+#[test]
+fn paused_vm_only_runs_requested_frames() {
+    run_script(|client| {
+        assert_eq!(client.call("pause", json!({})), json!({ "paused": true }));
+        client.call("step", json!({}));
+        client.call("press_key", json!({ "key": "B" }));
+        let registers = client.call("registers", Value::Null);
+        assert_eq!(registers["v"][2], 0xB);
+        let pc = registers["pc"].clone();
+
+        // The delay timer loop waits for the next frame
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(client.call("registers", Value::Null)["pc"], pc);
+
+        assert_eq!(client.call("step", json!({ "frames": 3 })), json!({ "frames": 3 }));
+        let frames = client.call("registers", Value::Null)["v"][0].as_u64().unwrap();
+        assert!((2..=4).contains(&frames), "counted {} frames", frames);
+        assert_eq!(client.call("resume", Value::Null), json!({ "paused": false }));
+    });
+}
+
+#[test]
+fn memory_can_be_read_and_written() {
+    let cpu = run_script(|client| {
+        client.call("pause", Value::Null);
+        let result = client.call("read_memory", json!({ "address": 0x200, "length": 4 }));
+        assert_eq!(result, json!({ "data": "f20aa000" }));
+
+        client.call("write_memory", json!({ "address": 0xFFE, "data": "beef" }));
+        let result = client.call("read_memory", json!({ "address": 0xFFE, "length": 2 }));
+        assert_eq!(result["data"], "beef");
+
+        let response = client.request("write_memory", json!({ "address": 0xFFF, "data": "beef" }));
+        assert_eq!(response["error"]["code"], -32000);
+    });
+    assert_eq!(&cpu.memory()[0xFFE..], &[0xBE, 0xEF]);
+}
+
+#[test]
+fn screenshot_shows_framebuffer() {
+    run_script(|client| {
+        client.call("step", Value::Null);
+        client.call("press_key", json!({ "key": 1 }));
+        client.call("step", json!({ "frames": 1 }));
+
+        let screenshot = client.call("screenshot", Value::Null);
+        assert_eq!(screenshot["width"], 64);
+        assert_eq!(screenshot["height"], 32);
+        // The top of the digit 0 is F0
+        assert_eq!(screenshot["rows"][0].as_str().unwrap()[..8], *"11110000");
+        assert_eq!(screenshot["rows"][1].as_str().unwrap()[..8], *"10010000");
+        assert!(screenshot["rows"][5].as_str().unwrap().chars().all(|c| c == '0'));
+    });
+}
+
+#[test]
+fn load_state_restores_saved_state() {
+    run_script(|client| {
+        let state = client.call("save_state", Value::Null)["state"].clone();
+
+        client.call("step", Value::Null);
+        client.call("press_key", json!({ "key": 7 }));
+        client.call("step", json!({ "frames": 2 }));
+        assert_eq!(client.call("registers", Value::Null)["v"][2], 7);
+
+        client.call("load_state", json!({ "state": state }));
+        let registers = client.call("registers", Value::Null);
+        assert_eq!(registers["v"][2], 0);
+        assert_eq!(registers["pc"], 0x200);
+
+        let response = client.request("load_state", json!({ "state": "00" }));
+        assert_eq!(response["error"]["code"], -32000);
+    });
+}
+
+#[test]
+fn load_rom_takes_data_or_path() {
+    let path = env::temp_dir().join(format!("chip8rpc-{}.ch8", process::id()));
+    std::fs::write(&path, [0x6A, 0x02]).unwrap();
+    let path_param = path.to_str().unwrap().to_string();
+
+    let cpu = run_script(move |client| {
+        client.call("pause", Value::Null);
+        // LD VA, 1; JP 202
+        client.call("load_rom", json!({ "data": "6a011202" }));
+        client.call("step", Value::Null);
+        assert_eq!(client.call("registers", Value::Null)["v"][0xA], 1);
+
+        client.call("load_rom", json!({ "path": path_param }));
+        let result = client.call("read_memory", json!({ "address": 0x200, "length": 2 }));
+        assert_eq!(result["data"], "6a02");
+
+        let response = client.request("load_rom", json!({ "path": "/no/such/rom.ch8" }));
+        assert_eq!(response["error"]["code"], -32000);
+    });
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(&cpu.memory()[0x200..0x202], &[0x6A, 0x02]);
+}
+
+#[test]
+fn load_rom_starts_the_new_rom_from_power_on() {
+    let cpu = run_script(|client| {
+        client.call("pause", Value::Null);
+        client.call("step", Value::Null);
+        client.call("press_key", json!({ "key": 5 }));
+        client.call("step", json!({ "frames": 2 }));
+        let registers = client.call("registers", Value::Null);
+        assert_eq!(registers["v"][2], 5);
+        assert_ne!(registers["pc"], 0x200);
+
+        // LD VA, 1; JP 202
+        client.call("load_rom", json!({ "data": "6a011202" }));
+        let registers = client.call("registers", Value::Null);
+        assert_eq!(registers["pc"], 0x200);
+        assert_eq!(registers["v"][2], 0);
+        assert_eq!(registers["i"], 0);
+
+        client.call("step", Value::Null);
+        let registers = client.call("registers", Value::Null);
+        assert_eq!(registers["v"][0xA], 1);
+        assert_eq!(registers["pc"], 0x202);
+        assert_eq!(registers["v"][0], 0);
+    });
+    assert_eq!(&cpu.memory()[0x200..0x204], &[0x6A, 0x01, 0x12, 0x02]);
+    assert!(cpu.memory()[0x204..0x214].iter().all(|&b| b == 0));
+    assert!(cpu.pixels().all(|row| row.iter().all(|&p| !p)));
+}
+
+#[test]
+fn reset_restarts_with_or_without_memory() {
+    let cpu = run_script(|client| {
+        client.call("write_memory", json!({ "address": 0x300, "data": "ff" }));
+        client.call("step", Value::Null);
+        client.call("press_key", json!({ "key": 5 }));
+        client.call("step", Value::Null);
+        assert_eq!(client.call("registers", Value::Null)["v"][2], 5);
+
+        client.call("reset", json!({ "keep_memory": true }));
+        assert_eq!(client.call("registers", Value::Null)["v"][2], 0);
+        assert_eq!(client.call("registers", Value::Null)["pc"], 0x200);
+        let result = client.call("read_memory", json!({ "address": 0x300, "length": 1 }));
+        assert_eq!(result["data"], "ff");
+
+        let response = client.request("reset", json!({ "keep_memory": 1 }));
+        assert_eq!(response["error"]["code"], -32602);
+        client.call("reset", Value::Null);
+    });
+    assert_eq!(&cpu.memory()[0x200..0x202], &[0, 0]);
+    assert_eq!(cpu.memory()[0x300], 0);
+}
+
+#[test]
+fn bad_requests_get_json_rpc_errors() {
+    run_script(|client| {
+        let response = client.send("{ not json");
+        assert_eq!(response["error"]["code"], -32700);
+        assert_eq!(response["id"], Value::Null);
+
+        let response = client.send("[1, 2]");
+        assert_eq!(response["error"]["code"], -32600);
+
+        assert_eq!(client.request("fly", Value::Null)["error"]["code"], -32601);
+        assert_eq!(client.request("press_key", json!({ "key": 16 }))["error"]["code"], -32602);
+        assert_eq!(client.request("read_memory", json!({ "address": 0x1000, "length": 1 }))
+                   ["error"]["code"], -32602);
+        assert_eq!(client.request("step", json!([1]))["error"]["code"], -32602);
+    });
+}
+
+#[test]
+fn notifications_are_not_answered() {
+    let cpu = run_script(|client| {
+        client.stream.write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"step\"}\n").unwrap();
+        // The next response belongs to the next request
+        assert_eq!(client.call("registers", Value::Null)["pc"], 0x202);
+    });
+    assert_eq!(cpu.registers().pc, 0x202);
+}
+
+#[test]
+fn several_clients_are_served() {
+    let server = tcp_server();
+    let mut other = Client { stream: Duplex::connect(&server), next_id: 0 };
+    let stream = Duplex::connect(&server);
+    serve(server, stream, move |client| {
+        client.call("write_memory", json!({ "address": 0x300, "data": "01" }));
+        let result = other.call("read_memory", json!({ "address": 0x300, "length": 1 }));
+        assert_eq!(result["data"], "01");
+    });
+}
+
+#[test]
+fn tcp_server_only_binds_loopback() {
+    assert!(Server::bind_tcp("0.0.0.0:0").is_err());
+    assert!(Server::bind("127.0.0.1:0").is_ok());
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket_is_served_and_removed() {
+    use std::os::unix::net::UnixStream;
+
+    struct UnixDuplex(BufReader<UnixStream>);
+
+    impl std::io::Read for UnixDuplex {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl BufRead for UnixDuplex {
+        fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+            self.0.fill_buf()
+        }
+
+        fn consume(&mut self, amt: usize) {
+            self.0.consume(amt)
+        }
+    }
+
+    impl Write for UnixDuplex {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.get_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.0.get_mut().flush()
+        }
+    }
+
+    let path = env::temp_dir().join(format!("chip8rpc-{}.sock", process::id()));
+    let server = Server::bind(&format!("unix:{}", path.display())).unwrap();
+    let stream = UnixDuplex(BufReader::new(UnixStream::connect(&path).unwrap()));
+    serve(server, stream, |client| {
+        assert_eq!(client.call("pause", Value::Null), json!({ "paused": true }));
+    });
+    assert!(!path.exists());
+}
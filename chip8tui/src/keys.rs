@@ -0,0 +1,79 @@
+use chip8core::Key;
+
+// Terminals only report key presses (and auto-repeats), never releases. A key
+// is therefore treated as held for a number of frames after its last press.
+pub struct HeldKeys {
+    hold_frames: u32,
+    frames_left: [u32; 16],
+}
+
+impl HeldKeys {
+    pub fn new(hold_frames: u32) -> HeldKeys {
+        HeldKeys {
+            hold_frames,
+            frames_left: [0; 16],
+        }
+    }
+
+    // Returns true if the key was not already held. Keys are held for at
+    // least one frame, or they would never be released.
+    pub fn press(&mut self, key: Key) -> bool {
+        let was_held = self.frames_left[key as usize] > 0;
+        self.frames_left[key as usize] = self.hold_frames.max(1);
+        !was_held
+    }
+
+    // Advances one frame and returns the keys whose hold expired
+    pub fn tick(&mut self) -> Vec<Key> {
+        let mut released = Vec::new();
+        for (i, frames) in self.frames_left.iter_mut().enumerate() {
+            if *frames > 0 {
+                *frames -= 1;
+                if *frames == 0 {
+                    released.push(Key::from_index(i as u8).unwrap());
+                }
+            }
+        }
+        released
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8core::Key;
+
+    #[test]
+    fn key_is_released_after_hold_frames() {
+        let mut keys = HeldKeys::new(3);
+
+        assert!(keys.press(Key::A));
+        assert!(keys.tick().is_empty());
+        assert!(keys.tick().is_empty());
+
+        let released = keys.tick();
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0] as usize, Key::A as usize);
+        assert!(keys.tick().is_empty());
+    }
+
+    #[test]
+    fn repeated_press_extends_hold() {
+        let mut keys = HeldKeys::new(2);
+
+        assert!(keys.press(Key::D5));
+        keys.tick();
+        assert!(!keys.press(Key::D5));
+        assert!(keys.tick().is_empty());
+        assert_eq!(keys.tick().len(), 1);
+    }
+
+    #[test]
+    fn zero_hold_frames_releases_on_next_tick() {
+        let mut keys = HeldKeys::new(0);
+
+        assert!(keys.press(Key::F));
+        assert_eq!(keys.tick().len(), 1);
+        assert!(keys.tick().is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+extern crate termion;
+extern crate chip8core;
+mod keys;
+mod screen;
+
+use std::io;
+use std::io::Write;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{ Duration, Instant };
+use termion::cursor;
+use termion::clear;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use chip8core::Key as Chip8Key;
+use keys::HeldKeys;
+use screen::Screen;
+
+pub use screen::Glyphs;
+
+pub struct Settings {
+    pub glyphs: Glyphs,
+    // Number of frames a key stays pressed after the terminal reported it
+    pub hold_frames: u32,
+    pub fps: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            glyphs: Glyphs::HalfBlock,
+            hold_frames: 6,
+            fps: 60,
+        }
+    }
+}
+
+pub struct Runner {}
+
+impl Runner {
+    pub fn run<T: chip8core::Vm>(vm: &mut T) -> Result<(), String> {
+        Runner::run_with(vm, &Default::default())
+    }
+
+    pub fn run_with<T: chip8core::Vm>(vm: &mut T, settings: &Settings) -> Result<(), String> {
+        // Stdin may have carried the ROM, so keyboard input is read from the tty directly
+        let tty = termion::get_tty().map_err(|e| e.to_string())?;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for key in tty.keys() {
+                if tx.send(key).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut out = io::stdout().into_raw_mode().map_err(|e| e.to_string())?;
+        write!(out, "{}{}", clear::All, cursor::Hide).unwrap();
+
+        let result = event_loop(vm, settings, &rx, &mut out);
+
+        write!(out, "{}{}{}", clear::All, cursor::Goto(1, 1), cursor::Show).unwrap();
+        out.flush().unwrap();
+
+        result
+    }
+}
+
+fn event_loop<T, W>(vm: &mut T, settings: &Settings, rx: &mpsc::Receiver<io::Result<Key>>,
+                    out: &mut W) -> Result<(), String>
+    where T: chip8core::Vm, W: Write
+{
+    let frame = Duration::from_secs(1) / settings.fps;
+    let mut screen = Screen::new(settings.glyphs);
+    let mut held = HeldKeys::new(settings.hold_frames);
+    let mut last = Instant::now();
+
+    loop {
+        for key in held.tick() {
+            vm.release_key(key);
+        }
+
+        while let Ok(key) = rx.try_recv() {
+            match key.map_err(|e| e.to_string())? {
+                Key::Esc | Key::Ctrl('c') => return Ok(()),
+                Key::Ctrl('l') => screen.invalidate(),
+                key => if let Some(key) = chip8_key_from_key(key) {
+                    if held.press(key) {
+                        vm.press_key(key);
+                    }
+                },
+            }
+        }
+
+        let now = Instant::now();
+        let dt = now.duration_since(last);
+        last = now;
+        vm.step(dt.as_secs_f64()).map_err(|e| e.to_string())?;
+
+        for (col, row, glyph) in screen.update(vm.pixels()) {
+            write!(out, "{}{}", cursor::Goto(col as u16 + 1, row as u16 + 1), glyph).unwrap();
+        }
+        out.flush().unwrap();
+
+        let elapsed = now.elapsed();
+        if elapsed < frame {
+            thread::sleep(frame - elapsed);
+        }
+    }
+}
+
+fn chip8_key_from_key(key: Key) -> Option<Chip8Key> {
+    if let Key::Char(c) = key {
+        return match c.to_ascii_lowercase() {
+            '1' => Some(Chip8Key::D1),
+            '2' => Some(Chip8Key::D2),
+            '3' => Some(Chip8Key::D3),
+            'q' => Some(Chip8Key::D4),
+            'w' => Some(Chip8Key::D5),
+            'e' => Some(Chip8Key::D6),
+            'a' => Some(Chip8Key::D7),
+            's' => Some(Chip8Key::D8),
+            'd' => Some(Chip8Key::D9),
+            'z' => Some(Chip8Key::A),
+            'x' => Some(Chip8Key::D0),
+            'c' => Some(Chip8Key::B),
+            '4' => Some(Chip8Key::C),
+            'r' => Some(Chip8Key::D),
+            'f' => Some(Chip8Key::E),
+            'v' => Some(Chip8Key::F),
+            _ => None,
+        }
+    }
+    None
+}
@@ -0,0 +1,165 @@
+use std::slice::Chunks;
+
+const GFX_W: usize = 64;
+const GFX_H: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Glyphs {
+    // Two vertically stacked pixels per cell using half block characters
+    HalfBlock,
+    // A 2x4 block of pixels per cell using braille patterns
+    Braille,
+}
+
+impl Glyphs {
+    // Number of pixels (columns, rows) covered by a single terminal cell
+    fn cell_size(self) -> (usize, usize) {
+        match self {
+            Glyphs::HalfBlock => (1, 2),
+            Glyphs::Braille   => (2, 4),
+        }
+    }
+
+    fn dot(self, x: usize, y: usize) -> u8 {
+        match self {
+            Glyphs::HalfBlock => 1 << y,
+            Glyphs::Braille   => match (x, y) {
+                (0, 3) => 0x40,
+                (1, 3) => 0x80,
+                _      => 1 << (x * 3 + y),
+            },
+        }
+    }
+
+    fn glyph(self, dots: u8) -> char {
+        match self {
+            Glyphs::HalfBlock => match dots {
+                0b00 => ' ',
+                0b01 => '▀',
+                0b10 => '▄',
+                _    => '█',
+            },
+            Glyphs::Braille => ::std::char::from_u32(0x2800 + dots as u32).unwrap(),
+        }
+    }
+}
+
+pub struct Screen {
+    glyphs: Glyphs,
+    cols: usize,
+    rows: usize,
+    cells: Vec<Option<char>>,
+}
+
+impl Screen {
+    pub fn new(glyphs: Glyphs) -> Screen {
+        let (w, h) = glyphs.cell_size();
+        let cols = GFX_W / w;
+        let rows = GFX_H / h;
+
+        Screen {
+            glyphs,
+            cols,
+            rows,
+            cells: vec![None; cols * rows],
+        }
+    }
+
+    // Forget what is on the terminal so that the next update redraws every cell
+    pub fn invalidate(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = None;
+        }
+    }
+
+    // Returns the cells (column, row, glyph) that differ from the last update
+    pub fn update(&mut self, pixels: Chunks<bool>) -> Vec<(usize, usize, char)> {
+        let (w, h) = self.glyphs.cell_size();
+        let mut dots = vec![0u8; self.cols * self.rows];
+
+        for (y, row) in pixels.enumerate() {
+            for (x, on) in row.iter().enumerate() {
+                if *on {
+                    dots[(y / h) * self.cols + (x / w)] |= self.glyphs.dot(x % w, y % h);
+                }
+            }
+        }
+
+        let mut changes = Vec::new();
+        for (idx, d) in dots.iter().enumerate() {
+            let glyph = self.glyphs.glyph(*d);
+            if self.cells[idx] != Some(glyph) {
+                self.cells[idx] = Some(glyph);
+                changes.push((idx % self.cols, idx / self.cols, glyph));
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gfx() -> Vec<bool> {
+        vec![false; GFX_W * GFX_H]
+    }
+
+    #[test]
+    fn half_block_cell_covers_two_rows() {
+        let mut gfx = gfx();
+        gfx[0] = true;
+        gfx[GFX_W + 1] = true;
+        gfx[2] = true;
+        gfx[GFX_W + 2] = true;
+
+        let mut screen = Screen::new(Glyphs::HalfBlock);
+        let changes = screen.update(gfx.chunks(GFX_W));
+
+        assert_eq!((screen.cols, screen.rows), (64, 16));
+        assert_eq!(changes.len(), 64 * 16);
+        assert_eq!(changes[0], (0, 0, '▀'));
+        assert_eq!(changes[1], (1, 0, '▄'));
+        assert_eq!(changes[2], (2, 0, '█'));
+        assert_eq!(changes[3], (3, 0, ' '));
+    }
+
+    #[test]
+    fn braille_cell_covers_two_by_four() {
+        let mut gfx = gfx();
+        gfx[0] = true;
+        gfx[3 * GFX_W + 1] = true;
+
+        let mut screen = Screen::new(Glyphs::Braille);
+        let changes = screen.update(gfx.chunks(GFX_W));
+
+        assert_eq!((screen.cols, screen.rows), (32, 8));
+        assert_eq!(changes[0], (0, 0, '\u{2881}'));
+        assert_eq!(changes[1], (1, 0, '\u{2800}'));
+    }
+
+    #[test]
+    fn update_only_returns_changed_cells() {
+        let mut gfx = gfx();
+        let mut screen = Screen::new(Glyphs::HalfBlock);
+        screen.update(gfx.chunks(GFX_W));
+
+        gfx[5 * GFX_W + 10] = true;
+        let changes = screen.update(gfx.chunks(GFX_W));
+        assert_eq!(changes, vec![(10, 2, '▄')]);
+
+        let changes = screen.update(gfx.chunks(GFX_W));
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn invalidate_redraws_every_cell() {
+        let gfx = gfx();
+        let mut screen = Screen::new(Glyphs::Braille);
+        screen.update(gfx.chunks(GFX_W));
+
+        screen.invalidate();
+        let changes = screen.update(gfx.chunks(GFX_W));
+        assert_eq!(changes.len(), 32 * 8);
+    }
+}
@@ -0,0 +1,245 @@
+// The cheat menu: runs a memory search on the focused Vm, turns what it
+// finds into freeze codes and keeps the view's cheat list. Up and down pick
+// an item and Enter uses it. On a cheat Enter turns it on or off, left and
+// right change its value and Backspace removes it.
+use std::path::Path;
+use chip8cheat::{ Cheat, CheatList, Compare, Search };
+use chip8core::DebugVm;
+use overlay::{ Input, Line, Span, Style };
+
+// Candidates listed once the search is down to this many
+pub const MAX_CANDIDATES: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Item {
+    NewSearch,
+    Filter(Compare),
+    // Filters on the typed value
+    Value,
+    Candidate(usize),
+    Cheat(usize),
+    Save,
+}
+
+#[derive(Default)]
+pub struct CheatMenu {
+    selected: usize,
+    search: Option<Search>,
+    // Hex digits typed for the value filter
+    value: String,
+    status: Option<String>,
+}
+
+impl CheatMenu {
+    #[cfg(feature = "gl")]
+    pub fn reset(&mut self) {
+        *self = Default::default();
+    }
+
+    fn items(&self, cheats: &CheatList, file: Option<&Path>) -> Vec<Item> {
+        let mut items = vec![Item::NewSearch];
+        if let Some(ref search) = self.search {
+            items.extend_from_slice(&[Item::Filter(Compare::Equal), Item::Filter(Compare::Changed),
+                                      Item::Filter(Compare::Increased),
+                                      Item::Filter(Compare::Decreased), Item::Value]);
+            if search.candidates().len() <= MAX_CANDIDATES {
+                items.extend(search.candidates().iter().map(|&address| Item::Candidate(address)));
+            }
+        }
+        items.extend((0..cheats.cheats.len()).map(Item::Cheat));
+        if file.is_some() {
+            items.push(Item::Save);
+        }
+        items
+    }
+
+    // Returns whether the menu used the input. Digits are only taken while
+    // the value filter is picked so they otherwise still reach the Vm.
+    pub fn input(&mut self, vm: &mut dyn DebugVm, cheats: &mut CheatList, file: Option<&Path>,
+                 input: Input) -> bool {
+        let items = self.items(cheats, file);
+        let item = items[self.selected.min(items.len() - 1)];
+        self.status = None;
+
+        match (input, item) {
+            (Input::Up, _) => self.selected = self.selected.saturating_sub(1),
+            (Input::Down, _) => self.selected = (self.selected + 1).min(items.len() - 1),
+            (Input::Digit(d), Item::Value) => {
+                if self.value.len() == 2 {
+                    self.value.clear();
+                }
+                self.value.push_str(&format!("{:X}", d));
+            },
+            (Input::Back, Item::Value) => {
+                self.value.pop();
+            },
+            (Input::Edit, Item::NewSearch) => {
+                self.search = Some(Search::new(vm));
+                self.status = Some(format!("{} CANDIDATES", vm.memory().len()));
+            },
+            (Input::Edit, Item::Filter(compare)) => self.filter(vm, compare),
+            (Input::Edit, Item::Value) => match u8::from_str_radix(&self.value, 16) {
+                Ok(value) => self.filter(vm, Compare::Value(value)),
+                Err(_) => self.status = Some("TYPE A VALUE FIRST".to_string()),
+            },
+            (Input::Edit, Item::Candidate(address)) => {
+                cheats.cheats.push(Cheat::new(address, vm.memory()[address]));
+                self.status = Some(format!("FROZE {:04X}", address));
+            },
+            (Input::Edit, Item::Cheat(index)) => {
+                let cheat = &mut cheats.cheats[index];
+                cheat.enabled = !cheat.enabled;
+            },
+            (Input::Left, Item::Cheat(index)) => {
+                let cheat = &mut cheats.cheats[index];
+                cheat.value = cheat.value.wrapping_sub(1);
+            },
+            (Input::Right, Item::Cheat(index)) => {
+                let cheat = &mut cheats.cheats[index];
+                cheat.value = cheat.value.wrapping_add(1);
+            },
+            (Input::Back, Item::Cheat(index)) => {
+                cheats.cheats.remove(index);
+            },
+            (Input::Edit, Item::Save) => {
+                self.status = Some(match cheats.save(file.unwrap()) {
+                    Ok(()) => "SAVED".to_string(),
+                    Err(e) => e.to_string(),
+                });
+            },
+            (Input::Digit(_), _) | (Input::Back, _) | (Input::Follow, _) => return false,
+            _ => (),
+        }
+
+        // The list may have shrunk
+        let len = self.items(cheats, file).len();
+        self.selected = self.selected.min(len - 1);
+        true
+    }
+
+    fn filter(&mut self, vm: &dyn DebugVm, compare: Compare) {
+        if let Some(ref mut search) = self.search {
+            search.filter(vm, compare);
+            self.status = Some(format!("{} CANDIDATES", search.candidates().len()));
+        }
+    }
+
+    pub fn lines(&self, vm: &dyn DebugVm, cheats: &CheatList, file: Option<&Path>) -> Vec<Line> {
+        let memory = vm.memory();
+        let mut lines = vec![vec![label("CHEATS")]];
+        for (index, item) in self.items(cheats, file).into_iter().enumerate() {
+            let text = match item {
+                Item::NewSearch => "NEW SEARCH".to_string(),
+                Item::Filter(Compare::Equal) => "EQUAL".to_string(),
+                Item::Filter(Compare::Changed) => "CHANGED".to_string(),
+                Item::Filter(Compare::Increased) => "INCREASED".to_string(),
+                Item::Filter(Compare::Decreased) => "DECREASED".to_string(),
+                Item::Filter(Compare::Value(value)) => format!("VALUE {:02X}", value),
+                Item::Value => format!("VALUE {:_<2}", self.value),
+                Item::Candidate(address) => {
+                    format!("FREEZE {:04X} AT {:02X}", address, memory.get(address).unwrap_or(&0))
+                },
+                Item::Cheat(index) => {
+                    let cheat = &cheats.cheats[index];
+                    format!("{} {:04X} {:02X} {}", if cheat.enabled { "ON " } else { "OFF" },
+                            cheat.address, cheat.value, cheat.name).trim_end().to_string()
+                },
+                Item::Save => "SAVE".to_string(),
+            };
+            let style = if index == self.selected { Style::Selected } else { Style::Value };
+            lines.push(vec![label(if index == self.selected { "> " } else { "  " }),
+                            Span { text, style }]);
+        }
+
+        let status = match self.status {
+            Some(ref status) => status.clone(),
+            None => match self.search {
+                Some(ref search) if search.candidates().len() > MAX_CANDIDATES => {
+                    format!("{} CANDIDATES", search.candidates().len())
+                },
+                _ => String::new(),
+            },
+        };
+        lines.push(vec![label(&status)]);
+        lines
+    }
+}
+
+fn label(text: &str) -> Span {
+    Span { text: text.to_string(), style: Style::Label }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8cheat::CheatList;
+    use chip8core::DebugVm;
+    use chip8vm::Cpu;
+    use overlay::{ Input, Line };
+    use super::*;
+
+    fn text(line: &Line) -> String {
+        line.iter().map(|span| span.text.as_str()).collect()
+    }
+
+    fn press(menu: &mut CheatMenu, cpu: &mut Cpu, cheats: &mut CheatList, inputs: &[Input]) {
+        for &input in inputs {
+            menu.input(cpu, cheats, None, input);
+        }
+    }
+
+    #[test]
+    fn search_narrows_down_to_a_freezable_address() {
+        let mut cpu = Cpu::new();
+        let mut cheats = CheatList::default();
+        let mut menu = CheatMenu::default();
+        cpu.write_memory(0x300, &[5]).unwrap();
+
+        press(&mut menu, &mut cpu, &mut cheats, &[Input::Edit]);
+        assert_eq!(text(menu.lines(&cpu, &cheats, None).last().unwrap()), "4096 CANDIDATES");
+
+        cpu.write_memory(0x300, &[4]).unwrap();
+        // Down to DECREASED
+        press(&mut menu, &mut cpu, &mut cheats, &[Input::Down; 4]);
+        press(&mut menu, &mut cpu, &mut cheats, &[Input::Edit]);
+
+        let lines = menu.lines(&cpu, &cheats, None);
+        assert_eq!(text(&lines[7]), "  FREEZE 0300 AT 04");
+        assert_eq!(text(lines.last().unwrap()), "1 CANDIDATES");
+
+        press(&mut menu, &mut cpu, &mut cheats, &[Input::Down, Input::Down, Input::Edit]);
+        assert_eq!(cheats.cheats, vec![Cheat::new(0x300, 4)]);
+        assert_eq!(text(&menu.lines(&cpu, &cheats, None)[8]), "  ON  0300 04");
+    }
+
+    #[test]
+    fn value_filter_takes_typed_digits() {
+        let mut cpu = Cpu::new();
+        let mut cheats = CheatList::default();
+        let mut menu = CheatMenu::default();
+        cpu.write_memory(0x300, &[0x2A]).unwrap();
+
+        assert!(!menu.input(&mut cpu, &mut cheats, None, Input::Digit(2)));
+        press(&mut menu, &mut cpu, &mut cheats, &[Input::Edit]);
+        press(&mut menu, &mut cpu, &mut cheats, &[Input::Down; 5]);
+        press(&mut menu, &mut cpu, &mut cheats,
+              &[Input::Digit(2), Input::Digit(0xB), Input::Back, Input::Digit(0xA)]);
+        assert_eq!(text(&menu.lines(&cpu, &cheats, None)[6]), "> VALUE 2A");
+        press(&mut menu, &mut cpu, &mut cheats, &[Input::Edit]);
+        assert_eq!(text(&menu.lines(&cpu, &cheats, None)[7]), "  FREEZE 0300 AT 2A");
+    }
+
+    #[test]
+    fn cheats_can_be_changed_and_removed() {
+        let mut cpu = Cpu::new();
+        let mut cheats = CheatList { cheats: vec![Cheat::new(0x300, 0)] };
+        let mut menu = CheatMenu::default();
+
+        press(&mut menu, &mut cpu, &mut cheats, &[Input::Down, Input::Left, Input::Edit]);
+        assert_eq!(cheats.cheats[0].value, 0xFF);
+        assert!(!cheats.cheats[0].enabled);
+
+        press(&mut menu, &mut cpu, &mut cheats, &[Input::Back]);
+        assert!(cheats.cheats.is_empty());
+        assert_eq!(text(&menu.lines(&cpu, &cheats, None)[1]), "> NEW SEARCH");
+    }
+}
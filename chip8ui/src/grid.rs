@@ -0,0 +1,76 @@
+use std::cmp;
+
+// Space between two cells in pixels
+const GAP: f64 = 4.0;
+
+// Splits the window into equally sized cells, one per view, filled row by
+// row. The layout is as close to square as the number of views allows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Grid {
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl Grid {
+    pub fn for_views(count: usize) -> Grid {
+        let count = cmp::max(count, 1);
+        let mut columns = 1;
+        while columns * columns < count {
+            columns += 1;
+        }
+        Grid { columns, rows: count.div_ceil(columns) }
+    }
+
+    // The cell's x, y, width and height in a window of the given size
+    pub fn cell(&self, index: usize, width: f64, height: f64) -> [f64; 4] {
+        let w = (width - GAP * (self.columns - 1) as f64) / self.columns as f64;
+        let h = (height - GAP * (self.rows - 1) as f64) / self.rows as f64;
+        let column = (index % self.columns) as f64;
+        let row = (index / self.columns) as f64;
+        [column * (w + GAP), row * (h + GAP), w, h]
+    }
+
+    // The cell under a point of the window, None over a gap
+    pub fn cell_at(&self, x: f64, y: f64, width: f64, height: f64) -> Option<usize> {
+        (0..self.columns * self.rows).find(|&index| {
+            let [cx, cy, w, h] = self.cell(index, width, height);
+            x >= cx && x < cx + w && y >= cy && y < cy + h
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_views_picks_near_square_layout() {
+        assert_eq!(Grid::for_views(0), Grid { columns: 1, rows: 1 });
+        assert_eq!(Grid::for_views(1), Grid { columns: 1, rows: 1 });
+        assert_eq!(Grid::for_views(2), Grid { columns: 2, rows: 1 });
+        assert_eq!(Grid::for_views(3), Grid { columns: 2, rows: 2 });
+        assert_eq!(Grid::for_views(4), Grid { columns: 2, rows: 2 });
+        assert_eq!(Grid::for_views(5), Grid { columns: 3, rows: 2 });
+    }
+
+    #[test]
+    fn single_cell_fills_window() {
+        assert_eq!(Grid::for_views(1).cell(0, 800.0, 400.0), [0.0, 0.0, 800.0, 400.0]);
+    }
+
+    #[test]
+    fn cells_are_separated_by_gap() {
+        let grid = Grid::for_views(4);
+        assert_eq!(grid.cell(0, 804.0, 404.0), [0.0, 0.0, 400.0, 200.0]);
+        assert_eq!(grid.cell(1, 804.0, 404.0), [404.0, 0.0, 400.0, 200.0]);
+        assert_eq!(grid.cell(3, 804.0, 404.0), [404.0, 204.0, 400.0, 200.0]);
+    }
+
+    #[test]
+    fn cell_at_finds_cell_under_point() {
+        let grid = Grid::for_views(4);
+        assert_eq!(grid.cell_at(10.0, 10.0, 804.0, 404.0), Some(0));
+        assert_eq!(grid.cell_at(500.0, 300.0, 804.0, 404.0), Some(3));
+        assert_eq!(grid.cell_at(402.0, 10.0, 804.0, 404.0), None);
+    }
+}
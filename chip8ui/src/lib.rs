@@ -1,111 +1,265 @@
+#[cfg(feature = "gl")]
 extern crate sdl2_window;
+#[cfg(feature = "gl")]
 extern crate opengl_graphics;
+#[cfg(feature = "gl")]
 extern crate piston;
+#[cfg(feature = "gl")]
 extern crate graphics;
 extern crate chip8core;
+extern crate chip8cheat;
+extern crate chip8rom;
+#[cfg(test)]
+extern crate chip8vm;
+// The menus, panels and overlays only exist in the window, without the gl
+// feature they are built for their tests alone
+#[cfg(any(feature = "gl", test))]
+mod cheats;
+#[cfg(any(feature = "gl", test))]
+mod grid;
+#[cfg(any(feature = "gl", test))]
+mod library;
+#[cfg(any(feature = "gl", test))]
+mod menu;
+#[cfg(any(feature = "gl", test))]
+mod overlay;
+mod palette;
 mod pixel;
+#[cfg(any(feature = "gl", test))]
+mod playback;
+mod render;
+mod screen;
+#[cfg(any(feature = "gl", test))]
+mod text;
+#[cfg(feature = "gl")]
+mod window;
 
-use sdl2_window::Sdl2Window;
-use piston::event_loop::*;
-use piston::input::*;
-use piston::window::WindowSettings;
-use graphics::Rectangle;
-use opengl_graphics::{
-    GlGraphics,
-    OpenGL,
-};
-use pixel::Pixel;
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use chip8core::Vm;
+use chip8cheat::CheatList;
+#[cfg(feature = "gl")]
 use chip8core::Key as Chip8Key;
+#[cfg(feature = "gl")]
+use library::{ Recent, Slots };
+#[cfg(feature = "gl")]
+use menu::{ Action, Options };
+pub use palette::Palette;
+use pixel::Pixel;
+pub use render::{ screenshot, Renderer, Software };
+pub use screen::Effect;
 
-pub struct Runner {}
+pub struct Settings {
+    // Send key presses to every view instead of only the focused one
+    pub broadcast: bool,
+    // Speed while the fast forward key is held
+    pub fast_forward: f64,
+    // Speed while slow motion is on
+    pub slow_motion: f64,
+    // Where the menu browses for ROMs
+    pub rom_dir: Option<PathBuf>,
+    // Keeps the recently opened ROMs between runs
+    pub recent_file: Option<PathBuf>,
+    // Keeps save states between runs, they only last until the window
+    // closes without it
+    pub state_dir: Option<PathBuf>,
+    pub palette: Palette,
+    pub fullscreen: bool,
+    // Window size in windowed mode, it can be resized from there
+    pub window_size: (u32, u32),
+    // Scale pixels by whole numbers only, for sharp pixels of equal size
+    pub integer_scale: bool,
+    pub effect: Effect,
+}
 
-impl Runner {
-    pub fn run<T: chip8core::Vm>(vm: &mut T) -> Result<(), String> {
-        let mut pixels: [Pixel; 64 * 32] = [Default::default(); 64 * 32];
-
-        vm.load_rom(&mut std::io::stdin()).unwrap();
-
-        let (width, height) = (800, 400);
-        let opengl = OpenGL::V3_2;
-
-        let mut window: Sdl2Window = WindowSettings::new("Chip8", (width, height))
-            .fullscreen(true)
-            .exit_on_esc(true)
-            .opengl(opengl)
-            .build()
-            .unwrap();
-
-        let ref mut gl = GlGraphics::new(opengl);
-
-        let mut events = window.events();
-        while let Some(e) = events.next(&mut window) {
-            if let Some(args) = e.update_args() {
-                vm.step(args.dt).unwrap();
-                for p in pixels.iter_mut() {
-                    p.update(args.dt);
-                }
-            }
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            broadcast: false,
+            fast_forward: 4.0,
+            slow_motion: 0.25,
+            rom_dir: None,
+            recent_file: None,
+            state_dir: None,
+            palette: Default::default(),
+            fullscreen: false,
+            window_size: (800, 400),
+            integer_scale: false,
+            effect: Effect::None,
+        }
+    }
+}
 
-            if let Some(args) = e.render_args() {
-                gl.draw(args.viewport(), |c, gl| {
-                        graphics::clear([1.0, 1.0, 1.0, 1.0], gl);
-                        let r = Rectangle::new([1.0, 1.0, 1.0, 1.0]);
-                        let w = args.width as f64 / 64.0;
-                        let h = args.height as f64 / 32.0;
-
-                        for (y_row, row) in vm.pixels().enumerate() {
-                            for (x_col, on) in row.iter().enumerate() {
-                                let x = x_col as f64 * w;
-                                let y = y_row as f64 * h;
-                                if *on {
-                                    pixels[y_row * 64 + x_col].turn_on();
-                                }
-                                let color = pixels[y_row * 64 + x_col].color_arr();
-                                r.color(color).draw([x, y, w, h], &c.draw_state, c.transform, gl);
-                            }
-                        }
-                    }
-                );
-            }
+// A Vm shown in one cell of the window. Only the window reads it, without
+// the gl feature a View is made and handed to Runner::run_views, which fails.
+#[cfg_attr(not(feature = "gl"), allow(dead_code))]
+pub struct View<'a> {
+    vm: &'a mut dyn Vm,
+    // Emulated time per real time, 2.0 runs twice as fast
+    speed: f64,
+    pixels: [Pixel; 64 * 32],
+    cheats: CheatList,
+    // Where the cheat menu saves the cheats
+    cheat_file: Option<PathBuf>,
+    // The ROM running, for reset and save states
+    rom: Option<Vec<u8>>,
+    rom_path: Option<PathBuf>,
+    // ROMs the Vm loaded by itself since, like a WatchVm does
+    reloads: Option<Receiver<Vec<u8>>>,
+    // Waits for a ROM from the menu without running
+    waiting: bool,
+    #[cfg(feature = "gl")]
+    slots: Slots,
+}
 
-            if let Some(button) = e.press_args() {
-                if let Some(key) = chip8_key_from_button(button) {
-                    vm.press_key(key);
-                }
-            }
+impl<'a> View<'a> {
+    pub fn new(vm: &'a mut dyn Vm) -> View<'a> {
+        View::with_speed(vm, 1.0)
+    }
 
-            if let Some(button) = e.release_args() {
-                if let Some(key) = chip8_key_from_button(button) {
-                    vm.release_key(key);
-                }
-            }
+    pub fn with_speed(vm: &'a mut dyn Vm, speed: f64) -> View<'a> {
+        View {
+            vm,
+            speed,
+            pixels: [Default::default(); 64 * 32],
+            cheats: Default::default(),
+            cheat_file: None,
+            rom: None,
+            rom_path: None,
+            reloads: None,
+            waiting: false,
+            #[cfg(feature = "gl")]
+            slots: Slots::new(None),
         }
+    }
+
+    // A view of a Vm with no ROM loaded yet. It isn't run until a ROM is
+    // opened from the menu, which the Runner starts out showing.
+    pub fn waiting(vm: &'a mut dyn Vm) -> View<'a> {
+        View { waiting: true, ..View::new(vm) }
+    }
 
-        Ok(())
+    // The ROM the Vm was started with, so the menu can reset it and keep
+    // save states for it. The path goes in the recent ROMs.
+    pub fn set_rom(&mut self, path: Option<PathBuf>, rom: Vec<u8>) {
+        self.rom_path = path;
+        self.rom = Some(rom);
+    }
+
+    // For Vms that load another ROM by themselves, e.g. when its file
+    // changes. The menu then resets to and saves states for the new ROM.
+    pub fn set_reloads(&mut self, reloads: Receiver<Vec<u8>>) {
+        self.reloads = Some(reloads);
+    }
+
+    // The cheats are applied after every step, for Vms with debug access
+    pub fn set_cheats(&mut self, cheats: CheatList, file: Option<PathBuf>) {
+        self.cheats = cheats;
+        self.cheat_file = file;
     }
 }
 
-fn chip8_key_from_button(button: Button) -> Option<Chip8Key> {
-    if let Button::Keyboard(key) = button {
-        return match key {
-            Key::D1 => Some(Chip8Key::D1),
-            Key::D2 => Some(Chip8Key::D2),
-            Key::D3 => Some(Chip8Key::D3),
-            Key::Q  => Some(Chip8Key::D4),
-            Key::W  => Some(Chip8Key::D5),
-            Key::E  => Some(Chip8Key::D6),
-            Key::A  => Some(Chip8Key::D7),
-            Key::S  => Some(Chip8Key::D8),
-            Key::D  => Some(Chip8Key::D9),
-            Key::Z  => Some(Chip8Key::A),
-            Key::X  => Some(Chip8Key::D0),
-            Key::C  => Some(Chip8Key::B),
-            Key::D4 => Some(Chip8Key::C),
-            Key::R  => Some(Chip8Key::D),
-            Key::F  => Some(Chip8Key::E),
-            Key::V  => Some(Chip8Key::F),
-            _ => None,
+// What the window does with the views, on keys and in the menu
+#[cfg(feature = "gl")]
+impl<'a> View<'a> {
+    // Takes on the last ROM the Vm reloaded, if any
+    fn follow_reloads(&mut self) {
+        let rom = self.reloads.as_ref().and_then(|reloads| reloads.try_iter().last());
+        if let Some(rom) = rom {
+            self.rom = Some(rom);
+            self.slots.clear();
+        }
+    }
+
+    fn release_all_keys(&mut self) {
+        for key in (0..16).filter_map(Chip8Key::from_index) {
+            self.vm.release_key(key);
         }
     }
-    None
+
+    fn options(&mut self, palette: Palette, slot: usize) -> Options {
+        Options {
+            speed: self.speed,
+            quirks: self.vm.debug().map(|vm| vm.quirks()),
+            palette,
+            slot,
+        }
+    }
+
+    fn set_options(&mut self, options: &Options) {
+        self.speed = options.speed;
+        if let (Some(quirks), Some(vm)) = (options.quirks, self.vm.debug()) {
+            if vm.quirks() != quirks {
+                vm.set_quirks(quirks);
+            }
+        }
+    }
+
+    // Carries out what was picked in the menu. Returns a message to show
+    // in the menu, or None when it closes.
+    fn perform(&mut self, action: Action, recent: &mut Recent, slot: usize)
+               -> Result<Option<String>, String> {
+        if action == Action::Resume {
+            return Ok(None);
+        }
+        let vm = self.vm.debug().ok_or_else(|| "NO DEBUG ACCESS".to_string())?;
+        if let Action::Open(path) = action {
+            let rom = chip8rom::load_path(&path).map_err(|e| e.to_string())?;
+            library::boot(vm, &rom.data)?;
+            self.rom = Some(rom.data);
+            self.waiting = false;
+            self.slots.clear();
+            // Not remembering the ROM doesn't stop it from running
+            let _ = recent.add(&path);
+            self.rom_path = Some(path);
+            return Ok(None);
+        }
+
+        let rom = self.rom.as_ref().ok_or_else(|| "NO ROM LOADED".to_string())?;
+        match action {
+            Action::Reset => library::boot(vm, rom).map(|_| None),
+            Action::SaveState => {
+                self.slots.save(rom, slot, vm.save_state()).map_err(|e| e.to_string())?;
+                Ok(Some(format!("SAVED SLOT {}", slot)))
+            },
+            Action::LoadState => match self.slots.load(rom, slot).map_err(|e| e.to_string())? {
+                Some(state) => vm.load_state(&state).map(|_| None).map_err(|e| e.to_string()),
+                None => Err(format!("SLOT {} IS EMPTY", slot)),
+            },
+            Action::Resume | Action::Open(_) => unreachable!(),
+        }
+    }
+}
+
+// What is shown over the focused view
+#[cfg(feature = "gl")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Panel {
+    Registers,
+    Cheats,
+    Menu,
+}
+
+// Opens a panel, or closes it when it's already open
+#[cfg(feature = "gl")]
+fn toggle(open: Option<Panel>, panel: Panel) -> Option<Panel> {
+    if open == Some(panel) { None } else { Some(panel) }
+}
+
+pub struct Runner {}
+
+impl Runner {
+    pub fn run<T: Vm>(vm: &mut T) -> Result<(), String> {
+        Runner::run_with(vm, &Default::default())
+    }
+
+    pub fn run_with<T: Vm>(vm: &mut T, settings: &Settings) -> Result<(), String> {
+        Runner::run_views(&mut [View::new(vm)], settings)
+    }
+
+    // Without the gl feature there is no window to show the views in
+    #[cfg(not(feature = "gl"))]
+    pub fn run_views(_views: &mut [View], _settings: &Settings) -> Result<(), String> {
+        Err("Built without a window, enable the gl feature of chip8ui".to_string())
+    }
 }
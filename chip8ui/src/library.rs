@@ -0,0 +1,216 @@
+// ROM files for the menu: listing a ROM directory, the recently opened
+// ROMs, save state slots and starting a ROM on a running Vm.
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::path::{ Path, PathBuf };
+use chip8cheat::rom_hash;
+use chip8core::DebugVm;
+
+pub const MAX_RECENT: usize = 10;
+pub const SLOTS: usize = 10;
+
+const ROM_EXTENSIONS: [&str; 5] = ["ch8", "sc8", "xo8", "zip", "hex"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+// Folders first, then ROMs, each sorted by name. Hidden files and files
+// that aren't ROMs are left out.
+pub fn entries(dir: &Path) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = entry.path();
+        let is_dir = path.is_dir();
+        if name.starts_with('.') || !(is_dir || is_rom(&path)) {
+            continue;
+        }
+        entries.push(Entry { name, path, is_dir });
+    }
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    Ok(entries)
+}
+
+fn is_rom(path: &Path) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => ROM_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()),
+        None => false,
+    }
+}
+
+// Most recent first, kept in a file with one path per line
+#[derive(Debug, Default)]
+pub struct Recent {
+    paths: Vec<PathBuf>,
+    file: Option<PathBuf>,
+}
+
+impl Recent {
+    // Starts empty when the file can't be read
+    pub fn load(file: Option<PathBuf>) -> Recent {
+        let paths = file.as_ref()
+            .and_then(|file| fs::read_to_string(file).ok())
+            .map(|text| text.lines().filter(|l| !l.is_empty()).map(PathBuf::from).collect())
+            .unwrap_or_default();
+        Recent { paths, file }
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    pub fn add(&mut self, path: &Path) -> io::Result<()> {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.paths.retain(|p| *p != path);
+        self.paths.insert(0, path);
+        self.paths.truncate(MAX_RECENT);
+
+        match self.file {
+            Some(ref file) => {
+                let lines: Vec<_> = self.paths.iter().map(|p| p.to_string_lossy()).collect();
+                fs::write(file, lines.join("\n") + "\n")
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+// Save states by slot for the ROM a view runs, on disk when there is a
+// state directory and only until the window closes otherwise
+#[derive(Debug)]
+pub struct Slots {
+    dir: Option<PathBuf>,
+    saved: Vec<Option<Vec<u8>>>,
+}
+
+impl Slots {
+    pub fn new(dir: Option<PathBuf>) -> Slots {
+        Slots { dir, saved: vec![None; SLOTS] }
+    }
+
+    // Another ROM doesn't see the states saved for this one
+    #[cfg(feature = "gl")]
+    pub fn clear(&mut self) {
+        self.saved = vec![None; SLOTS];
+    }
+
+    pub fn save(&mut self, rom: &[u8], slot: usize, state: Vec<u8>) -> io::Result<()> {
+        if let Some(ref dir) = self.dir {
+            fs::write(state_file(dir, rom, slot), &state)?;
+        }
+        self.saved[slot] = Some(state);
+        Ok(())
+    }
+
+    pub fn load(&self, rom: &[u8], slot: usize) -> io::Result<Option<Vec<u8>>> {
+        if let Some(ref state) = self.saved[slot] {
+            return Ok(Some(state.clone()));
+        }
+        match self.dir {
+            Some(ref dir) => match fs::read(state_file(dir, rom, slot)) {
+                Ok(state) => Ok(Some(state)),
+                Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+fn state_file(dir: &Path, rom: &[u8], slot: usize) -> PathBuf {
+    dir.join(format!("{:016x}-{}.state", rom_hash(rom), slot))
+}
+
+// Runs a ROM on the Vm as if it was just switched on with it, loaded at
+// the address and entry point the Vm was started with
+pub fn boot(vm: &mut dyn DebugVm, rom: &[u8]) -> Result<(), String> {
+    vm.reload_rom_bytes(rom).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use chip8core::{ DebugVm, Vm };
+    use chip8vm::Cpu;
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("chip8ui-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn entries_list_folders_then_roms() {
+        let dir = temp_dir("entries");
+        for name in ["pong.ch8", "Blitz.CH8", "notes.txt", ".hidden.ch8", "tetris.zip"].iter() {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        fs::create_dir(dir.join("schip")).unwrap();
+
+        let names: Vec<_> = entries(&dir).unwrap().into_iter().map(|e| e.name).collect();
+        assert_eq!(names, ["schip", "Blitz.CH8", "pong.ch8", "tetris.zip"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recent_keeps_the_latest_first() {
+        let dir = temp_dir("recent");
+        let file = dir.join("recent");
+        let mut recent = Recent::load(Some(file.clone()));
+        assert!(recent.paths().is_empty());
+
+        recent.add(Path::new("/roms/a.ch8")).unwrap();
+        recent.add(Path::new("/roms/b.ch8")).unwrap();
+        recent.add(Path::new("/roms/a.ch8")).unwrap();
+        let expected = [PathBuf::from("/roms/a.ch8"), PathBuf::from("/roms/b.ch8")];
+        assert_eq!(recent.paths(), &expected);
+        assert_eq!(Recent::load(Some(file)).paths(), &expected);
+
+        for n in 0..20 {
+            recent.add(&dir.join(format!("{}.ch8", n))).unwrap();
+        }
+        assert_eq!(recent.paths().len(), MAX_RECENT);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn slots_are_kept_per_rom_on_disk() {
+        let dir = temp_dir("slots");
+        let mut slots = Slots::new(Some(dir.clone()));
+        slots.save(b"rom", 3, vec![1, 2, 3]).unwrap();
+        assert_eq!(slots.load(b"rom", 3).unwrap(), Some(vec![1, 2, 3]));
+
+        let slots = Slots::new(Some(dir.clone()));
+        assert_eq!(slots.load(b"rom", 3).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(slots.load(b"other rom", 3).unwrap(), None);
+        assert_eq!(slots.load(b"rom", 4).unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn boot_starts_the_rom_from_power_on() {
+        let mut cpu = Cpu::new();
+        let font = cpu.memory()[..0x200].to_vec();
+        cpu.load_rom_bytes(&[0x60, 0x07, 0x61, 0x08, 0x62, 0x09]).unwrap();
+        for _ in 0..3 {
+            cpu.step_instruction().unwrap();
+        }
+
+        boot(&mut cpu, &[0x63, 0x01]).unwrap();
+        assert_eq!(cpu.registers().pc, 0x200);
+        assert_eq!(cpu.registers().v[0], 0);
+        assert_eq!(&cpu.memory()[0x200..0x204], &[0x63, 0x01, 0x00, 0x00]);
+        assert_eq!(cpu.memory()[..0x200], font[..]);
+
+        assert!(boot(&mut cpu, &[0; 4000]).is_err());
+        assert_eq!(cpu.registers().pc, 0x200);
+    }
+}
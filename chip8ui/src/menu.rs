@@ -0,0 +1,388 @@
+// The in-window menu: opening ROMs from the ROM directory or the recent
+// list, reset, speed, quirks, palette and save state slots. It only keeps
+// track of the page and the picked item. What it changes about the focused
+// view goes through Options, and the Runner carries out its Actions.
+use std::path::{ Path, PathBuf };
+use chip8core::Quirks;
+use library;
+use library::{ Entry, Recent, SLOTS };
+use overlay::{ Input, Line, Span, Style };
+use palette::{ Palette, PALETTES };
+
+// Items shown at once, longer lists scroll
+pub const VISIBLE: usize = 12;
+
+const SPEEDS: [f64; 7] = [0.25, 0.5, 1.0, 1.5, 2.0, 4.0, 8.0];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Resume,
+    Open(PathBuf),
+    Reset,
+    SaveState,
+    LoadState,
+}
+
+// The settings of the focused view the menu shows and changes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Options {
+    pub speed: f64,
+    // None for Vms without debug access
+    pub quirks: Option<Quirks>,
+    pub palette: Palette,
+    pub slot: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Page {
+    Main,
+    Browse(PathBuf),
+    Recent,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Item {
+    Resume,
+    Browse,
+    Recent,
+    Reset,
+    Speed,
+    Quirks,
+    Palette,
+    Slot,
+    SaveState,
+    LoadState,
+}
+
+pub struct Menu {
+    rom_dir: Option<PathBuf>,
+    page: Page,
+    selected: usize,
+    // The browsed folder's contents
+    entries: Vec<Entry>,
+    status: Option<String>,
+}
+
+impl Menu {
+    pub fn new(rom_dir: Option<PathBuf>) -> Menu {
+        Menu { rom_dir, page: Page::Main, selected: 0, entries: Vec::new(), status: None }
+    }
+
+    // Starts over on the main page
+    pub fn reset(&mut self) {
+        self.show(Page::Main);
+    }
+
+    // Shows the ROM directory, or the main page without one
+    pub fn browse(&mut self) {
+        match self.rom_dir.clone() {
+            Some(dir) => self.show(Page::Browse(dir)),
+            None => self.reset(),
+        }
+    }
+
+    // A message under the items until the next input
+    #[cfg(feature = "gl")]
+    pub fn set_status(&mut self, status: String) {
+        self.status = Some(status);
+    }
+
+    fn show(&mut self, page: Page) {
+        self.status = None;
+        self.selected = 0;
+        self.entries.clear();
+        if let Page::Browse(ref dir) = page {
+            match library::entries(dir) {
+                Ok(entries) => self.entries = entries,
+                Err(e) => self.status = Some(e.to_string()),
+            }
+        }
+        self.page = page;
+    }
+
+    fn items(&self, options: &Options) -> Vec<Item> {
+        let mut items = vec![Item::Resume];
+        if self.rom_dir.is_some() {
+            items.push(Item::Browse);
+        }
+        items.extend_from_slice(&[Item::Recent, Item::Reset, Item::Speed]);
+        if options.quirks.is_some() {
+            items.push(Item::Quirks);
+        }
+        items.extend_from_slice(&[Item::Palette, Item::Slot, Item::SaveState, Item::LoadState]);
+        items
+    }
+
+    fn len(&self, options: &Options, recent: &Recent) -> usize {
+        match self.page {
+            Page::Main => self.items(options).len(),
+            Page::Browse(ref dir) => self.entries.len() + self.has_parent(dir) as usize,
+            Page::Recent => recent.paths().len(),
+        }
+    }
+
+    // Browsing stays inside the ROM directory
+    fn has_parent(&self, dir: &Path) -> bool {
+        self.rom_dir.as_ref().is_some_and(|root| root != dir)
+    }
+
+    pub fn input(&mut self, input: Input, options: &mut Options, recent: &Recent)
+                 -> Option<Action> {
+        let len = self.len(options, recent);
+        self.status = None;
+        match input {
+            Input::Up => {
+                self.selected = self.selected.saturating_sub(1);
+                return None;
+            },
+            Input::Down => {
+                self.selected = (self.selected + 1).min(len.saturating_sub(1));
+                return None;
+            },
+            _ => (),
+        }
+
+        match self.page.clone() {
+            Page::Main => self.main_input(input, options),
+            Page::Browse(dir) => {
+                let parent = self.has_parent(&dir);
+                match input {
+                    Input::Edit if parent && self.selected == 0 => {
+                        self.show(Page::Browse(dir.parent().unwrap().to_path_buf()));
+                    },
+                    Input::Edit if len > 0 => {
+                        let entry = self.entries[self.selected - parent as usize].clone();
+                        if entry.is_dir {
+                            self.show(Page::Browse(entry.path));
+                        } else {
+                            return Some(Action::Open(entry.path));
+                        }
+                    },
+                    Input::Back if parent => {
+                        self.show(Page::Browse(dir.parent().unwrap().to_path_buf()));
+                    },
+                    Input::Back => self.show(Page::Main),
+                    _ => (),
+                }
+                None
+            },
+            Page::Recent => {
+                match input {
+                    Input::Edit if len > 0 => {
+                        return Some(Action::Open(recent.paths()[self.selected].clone()));
+                    },
+                    Input::Back => self.show(Page::Main),
+                    _ => (),
+                }
+                None
+            },
+        }
+    }
+
+    fn main_input(&mut self, input: Input, options: &mut Options) -> Option<Action> {
+        let items = self.items(options);
+        let item = items[self.selected.min(items.len() - 1)];
+        let step = match input {
+            Input::Left => -1,
+            Input::Right => 1,
+            _ => 0,
+        };
+
+        match (input, item) {
+            (Input::Edit, Item::Resume) | (Input::Back, _) => return Some(Action::Resume),
+            (Input::Edit, Item::Browse) => self.browse(),
+            (Input::Edit, Item::Recent) => self.show(Page::Recent),
+            (Input::Edit, Item::Reset) => return Some(Action::Reset),
+            (Input::Edit, Item::SaveState) => return Some(Action::SaveState),
+            (Input::Edit, Item::LoadState) => return Some(Action::LoadState),
+            (_, Item::Speed) if step != 0 => {
+                let current = SPEEDS.iter().position(|&s| s >= options.speed).unwrap_or(2);
+                options.speed = SPEEDS[cycle(current, step, SPEEDS.len())];
+            },
+            (_, Item::Quirks) if step != 0 => {
+                let presets = quirk_presets();
+                let current = options.quirks
+                    .and_then(|quirks| presets.iter().position(|&(_, q)| q == quirks))
+                    .unwrap_or(0);
+                options.quirks = Some(presets[cycle(current, step, presets.len())].1);
+            },
+            (_, Item::Palette) if step != 0 => {
+                let current = PALETTES.iter().position(|&p| p == options.palette).unwrap_or(0);
+                options.palette = PALETTES[cycle(current, step, PALETTES.len())];
+            },
+            (_, Item::Slot) if step != 0 => options.slot = cycle(options.slot, step, SLOTS),
+            _ => (),
+        }
+        None
+    }
+
+    pub fn lines(&self, options: &Options, recent: &Recent) -> Vec<Line> {
+        let (title, items): (String, Vec<String>) = match self.page {
+            Page::Main => ("MENU".to_string(), self.items(options).into_iter()
+                .map(|item| item_text(item, options))
+                .collect()),
+            Page::Browse(ref dir) => {
+                let mut items = Vec::new();
+                if self.has_parent(dir) {
+                    items.push("..".to_string());
+                }
+                items.extend(self.entries.iter().map(|entry| {
+                    if entry.is_dir { format!("{}/", entry.name) } else { entry.name.clone() }
+                }));
+                let name = dir.file_name().map(|name| name.to_string_lossy().into_owned());
+                (format!("ROMS {}", name.unwrap_or_default()), items)
+            },
+            Page::Recent => ("RECENT".to_string(), recent.paths().iter()
+                .map(|path| path.file_name().unwrap_or_default().to_string_lossy().into_owned())
+                .collect()),
+        };
+
+        let mut lines = vec![vec![label(&title)]];
+        // Keep the picked item in view
+        let first = self.selected.saturating_sub(VISIBLE - 1);
+        for (index, text) in items.into_iter().enumerate().skip(first).take(VISIBLE) {
+            let selected = index == self.selected;
+            lines.push(vec![label(if selected { "> " } else { "  " }), Span {
+                text,
+                style: if selected { Style::Selected } else { Style::Value },
+            }]);
+        }
+        if lines.len() == 1 {
+            lines.push(vec![label("  EMPTY")]);
+        }
+        lines.push(vec![label(self.status.as_ref().map_or("", |status| status.as_str()))]);
+        lines
+    }
+}
+
+fn item_text(item: Item, options: &Options) -> String {
+    match item {
+        Item::Resume => "RESUME".to_string(),
+        Item::Browse => "OPEN ROM".to_string(),
+        Item::Recent => "RECENT ROMS".to_string(),
+        Item::Reset => "RESET".to_string(),
+        Item::Speed => format!("SPEED < {}X >", options.speed),
+        Item::Quirks => {
+            let name = options.quirks.and_then(|quirks| {
+                quirk_presets().iter().find(|&&(_, q)| q == quirks).map(|&(name, _)| name)
+            });
+            format!("QUIRKS < {} >", name.unwrap_or("CUSTOM"))
+        },
+        Item::Palette => format!("PALETTE < {} >", options.palette.name),
+        Item::Slot => format!("SLOT < {} >", options.slot),
+        Item::SaveState => "SAVE STATE".to_string(),
+        Item::LoadState => "LOAD STATE".to_string(),
+    }
+}
+
+fn quirk_presets() -> [(&'static str, Quirks); 4] {
+    [
+        ("CHIP-8", Quirks::chip8()),
+        ("SCHIP", Quirks::superchip()),
+        ("XO-CHIP", Quirks::xochip()),
+        ("NONE", Quirks::default()),
+    ]
+}
+
+// Moves through a list of choices, wrapping at both ends
+fn cycle(index: usize, step: isize, len: usize) -> usize {
+    ((index as isize + step).rem_euclid(len as isize)) as usize
+}
+
+fn label(text: &str) -> Span {
+    Span { text: text.to_string(), style: Style::Label }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use super::*;
+
+    fn text(line: &Line) -> String {
+        line.iter().map(|span| span.text.as_str()).collect()
+    }
+
+    fn options() -> Options {
+        Options { speed: 1.0, quirks: Some(Quirks::default()), palette: Palette::default(),
+                  slot: 0 }
+    }
+
+    fn press(menu: &mut Menu, options: &mut Options, recent: &Recent, inputs: &[Input])
+             -> Option<Action> {
+        inputs.iter().map(|&input| menu.input(input, options, recent)).last().unwrap()
+    }
+
+    #[test]
+    fn main_page_changes_options() {
+        let mut menu = Menu::new(None);
+        let mut options = options();
+        let recent = Recent::default();
+        let lines = menu.lines(&options, &recent);
+        assert_eq!(text(&lines[1]), "> RESUME");
+        assert_eq!(text(&lines[4]), "  SPEED < 1X >");
+        assert_eq!(text(&lines[5]), "  QUIRKS < NONE >");
+
+        press(&mut menu, &mut options, &recent, &[Input::Down, Input::Down, Input::Down,
+                                                  Input::Right, Input::Right]);
+        assert_eq!(options.speed, 2.0);
+        press(&mut menu, &mut options, &recent, &[Input::Down, Input::Right]);
+        assert_eq!(options.quirks, Some(Quirks::chip8()));
+        press(&mut menu, &mut options, &recent, &[Input::Down, Input::Left]);
+        assert_eq!(options.palette.name, "lcd");
+        press(&mut menu, &mut options, &recent, &[Input::Down, Input::Left]);
+        assert_eq!(options.slot, 9);
+        assert_eq!(text(&menu.lines(&options, &recent)[7]), "> SLOT < 9 >");
+
+        assert_eq!(press(&mut menu, &mut options, &recent, &[Input::Down, Input::Edit]),
+                   Some(Action::SaveState));
+        assert_eq!(press(&mut menu, &mut options, &recent, &[Input::Back]),
+                   Some(Action::Resume));
+    }
+
+    #[test]
+    fn browser_opens_roms_inside_the_rom_directory() {
+        let dir = env::temp_dir().join(format!("chip8ui-menu-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("games")).unwrap();
+        fs::write(dir.join("games").join("pong.ch8"), b"").unwrap();
+
+        let mut menu = Menu::new(Some(dir.clone()));
+        let mut options = options();
+        let recent = Recent::default();
+        menu.browse();
+        let lines = menu.lines(&options, &recent);
+        assert_eq!(text(&lines[1]), "> games/");
+        assert_eq!(lines.len(), 3);
+
+        press(&mut menu, &mut options, &recent, &[Input::Edit]);
+        assert_eq!(text(&menu.lines(&options, &recent)[1]), "> ..");
+        assert_eq!(press(&mut menu, &mut options, &recent, &[Input::Down, Input::Edit]),
+                   Some(Action::Open(dir.join("games").join("pong.ch8"))));
+
+        // Back leaves the folder, then the browser
+        press(&mut menu, &mut options, &recent, &[Input::Back]);
+        assert_eq!(text(&menu.lines(&options, &recent)[1]), "> games/");
+        press(&mut menu, &mut options, &recent, &[Input::Back]);
+        assert_eq!(text(&menu.lines(&options, &recent)[0]), "MENU");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recent_page_lists_file_names() {
+        let mut menu = Menu::new(None);
+        let mut options = Options { quirks: None, ..options() };
+        let mut recent = Recent::default();
+        recent.add(Path::new("/roms/a.ch8")).unwrap();
+        recent.add(Path::new("/roms/b.ch8")).unwrap();
+
+        // No ROM directory and no quirks for this Vm
+        assert_eq!(text(&menu.lines(&options, &recent)[2]), "  RECENT ROMS");
+        assert_eq!(text(&menu.lines(&options, &recent)[5]), "  PALETTE < white >");
+
+        press(&mut menu, &mut options, &recent, &[Input::Down, Input::Edit]);
+        assert_eq!(text(&menu.lines(&options, &recent)[2]), "  a.ch8");
+        assert_eq!(press(&mut menu, &mut options, &recent, &[Input::Edit]),
+                   Some(Action::Open(PathBuf::from("/roms/b.ch8"))));
+    }
+}
@@ -0,0 +1,475 @@
+// The memory watch overlay: registers, stack, keys and a hex view of memory
+// that follows PC or I, with bytes lighting up when the program changes
+// them. Any register or byte can be selected with the arrow keys and
+// overwritten by typing hex digits.
+use chip8core::{ DebugVm, Register };
+
+// Bytes per row of the hex view
+pub const ROW_BYTES: usize = 8;
+pub const ROWS: usize = 8;
+// Rows shown above the followed address
+const ROWS_ABOVE: usize = 2;
+
+// Seconds a changed byte stays highlighted
+const HIGHLIGHT_TIME: f32 = 1.0;
+
+// The registers as laid out on screen
+const REGISTER_ROWS: [&[Register]; 6] = [
+    &[Register::V(0x0), Register::V(0x1), Register::V(0x2), Register::V(0x3)],
+    &[Register::V(0x4), Register::V(0x5), Register::V(0x6), Register::V(0x7)],
+    &[Register::V(0x8), Register::V(0x9), Register::V(0xA), Register::V(0xB)],
+    &[Register::V(0xC), Register::V(0xD), Register::V(0xE), Register::V(0xF)],
+    &[Register::I, Register::Pc, Register::Sp],
+    &[Register::DelayTimer, Register::SoundTimer],
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+    Pc,
+    I,
+    // Stays put, holds the first address shown
+    Fixed(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    Register(Register),
+    Memory(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Input {
+    Left,
+    Right,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    // Cycles the hex view between following PC, following I and staying put
+    Follow,
+    // Starts editing the selected field, or writes the typed value
+    Edit,
+    // Removes the last typed digit, or stops editing
+    Back,
+    Digit(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    Label,
+    Value,
+    // How recently the byte changed, from 1.0 down to 0.0
+    Changed(f32),
+    Selected,
+    Editing,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+pub type Line = Vec<Span>;
+
+pub struct Overlay {
+    anchor: Anchor,
+    selected: Field,
+    // Digits typed so far while editing the selected field
+    edit: Option<String>,
+    // The last edit's error, shown until the next input
+    status: Option<String>,
+    previous: Vec<u8>,
+    heat: Vec<f32>,
+}
+
+impl Default for Overlay {
+    fn default() -> Overlay {
+        Overlay {
+            anchor: Anchor::Pc,
+            selected: Field::Register(Register::V(0)),
+            edit: None,
+            status: None,
+            previous: Vec::new(),
+            heat: Vec::new(),
+        }
+    }
+}
+
+impl Overlay {
+    // Forgets the watched memory, e.g. when another Vm is shown
+    #[cfg(feature = "gl")]
+    pub fn reset(&mut self) {
+        self.edit = None;
+        self.status = None;
+        self.previous.clear();
+        self.heat.clear();
+    }
+
+    pub fn update(&mut self, vm: &dyn DebugVm, dt: f64) {
+        let memory = vm.memory();
+        if self.previous.len() != memory.len() {
+            self.previous = memory.to_vec();
+            self.heat = vec![0.0; memory.len()];
+            return;
+        }
+
+        let fade = dt as f32 / HIGHLIGHT_TIME;
+        for ((old, &new), heat) in self.previous.iter_mut().zip(memory).zip(self.heat.iter_mut()) {
+            if *old != new {
+                *old = new;
+                *heat = 1.0;
+            } else {
+                *heat = (*heat - fade).max(0.0);
+            }
+        }
+    }
+
+    // The first address of the hex view, always a whole view inside memory
+    pub fn first_address(&self, vm: &dyn DebugVm) -> usize {
+        let registers = vm.registers();
+        let followed = match self.anchor {
+            Anchor::Pc => registers.pc as usize,
+            Anchor::I => registers.i as usize,
+            Anchor::Fixed(address) => return address,
+        };
+        let first = (followed / ROW_BYTES).saturating_sub(ROWS_ABOVE) * ROW_BYTES;
+        first.min(last_view(vm))
+    }
+
+    #[cfg(test)]
+    pub fn selected(&self) -> Field {
+        self.selected
+    }
+
+    // Returns whether the overlay used the input. Digits are only taken
+    // while editing so they otherwise still reach the Vm as keys.
+    pub fn input(&mut self, vm: &mut dyn DebugVm, input: Input) -> bool {
+        if self.edit.is_some() {
+            self.edit_input(vm, input);
+            return true;
+        }
+
+        self.status = None;
+        match input {
+            Input::Left => self.move_selection(vm, 0, -1),
+            Input::Right => self.move_selection(vm, 0, 1),
+            Input::Up => self.move_selection(vm, -1, 0),
+            Input::Down => self.move_selection(vm, 1, 0),
+            Input::PageUp => {
+                let first = self.first_address(vm).saturating_sub(ROWS * ROW_BYTES);
+                self.anchor = Anchor::Fixed(first);
+            },
+            Input::PageDown => {
+                let first = (self.first_address(vm) + ROWS * ROW_BYTES).min(last_view(vm));
+                self.anchor = Anchor::Fixed(first);
+            },
+            Input::Follow => {
+                self.anchor = match self.anchor {
+                    Anchor::Pc => Anchor::I,
+                    Anchor::I => Anchor::Fixed(self.first_address(vm)),
+                    Anchor::Fixed(_) => Anchor::Pc,
+                };
+            },
+            Input::Edit => self.edit = Some(String::new()),
+            Input::Back | Input::Digit(_) => return false,
+        }
+        true
+    }
+
+    fn edit_input(&mut self, vm: &mut dyn DebugVm, input: Input) {
+        let typed = self.edit.take().unwrap_or_default();
+        match input {
+            Input::Digit(d) if typed.len() < width(self.selected) => {
+                self.edit = Some(format!("{}{:X}", typed, d));
+            },
+            Input::Back if !typed.is_empty() => {
+                self.edit = Some(typed[..(typed.len() - 1)].to_string());
+            },
+            Input::Back => (),
+            Input::Edit if typed.is_empty() => (),
+            Input::Edit => {
+                let value = u16::from_str_radix(&typed, 16).unwrap();
+                let result = match self.selected {
+                    Field::Register(register) => {
+                        vm.set_register(register, value).map_err(|e| e.to_string())
+                    },
+                    Field::Memory(address) => {
+                        vm.write_memory(address, &[value as u8]).map_err(|e| e.to_string())
+                    },
+                };
+                self.status = result.err();
+            },
+            _ => self.edit = Some(typed),
+        }
+    }
+
+    // Rows and columns follow the layout on screen. Down from the last
+    // register row enters the hex view and up from its top row leaves it.
+    fn move_selection(&mut self, vm: &dyn DebugVm, rows: isize, columns: isize) {
+        let first = self.first_address(vm);
+        self.selected = match self.selected {
+            Field::Register(register) => {
+                let (row, column) = register_position(register);
+                let row = row as isize + rows;
+                if row >= REGISTER_ROWS.len() as isize {
+                    Field::Memory(first)
+                } else {
+                    let row = REGISTER_ROWS[row.max(0) as usize];
+                    let column = (column as isize + columns).max(0) as usize;
+                    Field::Register(row[column.min(row.len() - 1)])
+                }
+            },
+            Field::Memory(address) if rows < 0 && address < first + ROW_BYTES => {
+                Field::Register(REGISTER_ROWS[REGISTER_ROWS.len() - 1][0])
+            },
+            Field::Memory(address) => {
+                let address = address as isize + rows * ROW_BYTES as isize + columns;
+                Field::Memory(address.max(0).min(vm.memory().len() as isize - 1) as usize)
+            },
+        };
+
+        // Keep a selected byte in view
+        if let Field::Memory(address) = self.selected {
+            if address < first || address >= first + ROWS * ROW_BYTES {
+                let first = (address / ROW_BYTES).saturating_sub(ROWS_ABOVE) * ROW_BYTES;
+                self.anchor = Anchor::Fixed(first.min(last_view(vm)));
+            }
+        }
+    }
+
+    pub fn lines(&self, vm: &dyn DebugVm) -> Vec<Line> {
+        let registers = vm.registers();
+        let mut lines = Vec::new();
+
+        for row in REGISTER_ROWS.iter() {
+            let mut line = Vec::new();
+            for &register in row.iter() {
+                let value = match register {
+                    Register::V(x) => registers.v[x as usize] as u16,
+                    Register::I => registers.i,
+                    Register::Pc => registers.pc,
+                    Register::Sp => registers.sp,
+                    Register::DelayTimer => registers.delay_timer as u16,
+                    Register::SoundTimer => registers.sound_timer as u16,
+                };
+                line.push(label(&format!("{} ", register)));
+                line.push(self.field(Field::Register(register), value));
+                line.push(label(" "));
+            }
+            lines.push(line);
+        }
+
+        let stack: Vec<String> = registers.stack[..(registers.sp as usize)].iter()
+            .map(|address| format!("{:04X}", address))
+            .collect();
+        lines.push(vec![label("STACK "), value(&list_or_dash(&stack))]);
+
+        let keys: Vec<String> = vm.keys().iter().enumerate()
+            .filter(|&(_, &pressed)| pressed)
+            .map(|(key, _)| format!("{:X}", key))
+            .collect();
+        lines.push(vec![label("KEYS "), value(&list_or_dash(&keys))]);
+
+        lines.push(vec![label(match self.anchor {
+            Anchor::Pc => "MEMORY AT PC",
+            Anchor::I => "MEMORY AT I",
+            Anchor::Fixed(_) => "MEMORY",
+        })]);
+        let first = self.first_address(vm);
+        let memory = vm.memory();
+        for row in 0..ROWS {
+            let start = first + row * ROW_BYTES;
+            let mut line = vec![label(&format!("{:04X}", start))];
+            let end = (start + ROW_BYTES).min(memory.len());
+            for (address, &byte) in memory[start..end].iter().enumerate() {
+                line.push(label(" "));
+                line.push(self.field(Field::Memory(start + address), byte as u16));
+            }
+            lines.push(line);
+        }
+
+        lines.push(match self.status {
+            Some(ref status) => vec![value(status)],
+            None if self.edit.is_some() => vec![label("ENTER WRITE  BACKSPACE UNDO")],
+            None => vec![label("ENTER EDIT  F3 FOLLOW")],
+        });
+        lines
+    }
+
+    fn field(&self, field: Field, value: u16) -> Span {
+        let width = width(field);
+        if field == self.selected {
+            return match self.edit {
+                Some(ref typed) => Span {
+                    text: format!("{:_<width$}", typed, width = width),
+                    style: Style::Editing,
+                },
+                None => Span { text: format!("{:0width$X}", value, width = width),
+                               style: Style::Selected },
+            };
+        }
+
+        let style = match field {
+            Field::Memory(address) => match self.heat.get(address) {
+                Some(&heat) if heat > 0.0 => Style::Changed(heat),
+                _ => Style::Value,
+            },
+            Field::Register(_) => Style::Value,
+        };
+        Span { text: format!("{:0width$X}", value, width = width), style }
+    }
+}
+
+// Hex digits shown for a field
+fn width(field: Field) -> usize {
+    match field {
+        Field::Register(Register::I) | Field::Register(Register::Pc) => 4,
+        _ => 2,
+    }
+}
+
+fn register_position(register: Register) -> (usize, usize) {
+    REGISTER_ROWS.iter().enumerate()
+        .filter_map(|(row, registers)| {
+            registers.iter().position(|&r| r == register).map(|column| (row, column))
+        })
+        .next()
+        .unwrap_or((0, 0))
+}
+
+fn last_view(vm: &dyn DebugVm) -> usize {
+    vm.memory().len().saturating_sub(ROWS * ROW_BYTES)
+}
+
+fn list_or_dash(items: &[String]) -> String {
+    if items.is_empty() { "-".to_string() } else { items.join(" ") }
+}
+
+fn label(text: &str) -> Span {
+    Span { text: text.to_string(), style: Style::Label }
+}
+
+fn value(text: &str) -> Span {
+    Span { text: text.to_string(), style: Style::Value }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8core::{ DebugVm, Key, Register, Vm };
+    use chip8vm::Cpu;
+    use super::*;
+
+    fn text(line: &Line) -> String {
+        line.iter().map(|span| span.text.as_str()).collect()
+    }
+
+    fn cpu() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes(&[0x60, 0x2A, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x06]).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn lines_show_registers_keys_and_memory() {
+        let mut cpu = cpu();
+        cpu.step_instruction().unwrap();
+        cpu.press_key(Key::B);
+        let lines = Overlay::default().lines(&cpu);
+
+        assert_eq!(text(&lines[0]), "V0 2A V1 00 V2 00 V3 00 ");
+        assert_eq!(text(&lines[4]), "I 0000 PC 0202 SP 00 ");
+        assert_eq!(text(&lines[6]), "STACK -");
+        assert_eq!(text(&lines[7]), "KEYS B");
+        assert_eq!(text(&lines[8]), "MEMORY AT PC");
+        // PC is two rows down
+        assert_eq!(text(&lines[9]), "01F0 00 00 00 00 00 00 00 00");
+        assert_eq!(text(&lines[11]), "0200 60 2A A3 00 F0 55 12 06");
+        assert_eq!(lines[0][1].style, Style::Selected);
+    }
+
+    #[test]
+    fn changed_bytes_are_highlighted_until_they_fade() {
+        let mut cpu = cpu();
+        let mut overlay = Overlay::default();
+        overlay.update(&cpu, 0.0);
+        for _ in 0..3 {
+            cpu.step_instruction().unwrap();
+        }
+        overlay.input(&mut cpu, Input::Follow);
+        overlay.update(&cpu, 0.1);
+
+        // LD [I], V0 stored 2A at 300, shown third from the left on the third row
+        let lines = overlay.lines(&cpu);
+        assert_eq!(text(&lines[11]), "0300 2A 00 00 00 00 00 00 00");
+        assert_eq!(lines[11][2].style, Style::Changed(1.0));
+        assert_eq!(lines[11][4].style, Style::Value);
+
+        overlay.update(&cpu, 2.0);
+        assert_eq!(overlay.lines(&cpu)[11][2].style, Style::Value);
+    }
+
+    #[test]
+    fn typed_digits_write_registers_and_memory() {
+        let mut cpu = cpu();
+        let mut overlay = Overlay::default();
+        overlay.input(&mut cpu, Input::Right);
+        assert_eq!(overlay.selected(), Field::Register(Register::V(1)));
+        assert!(!overlay.input(&mut cpu, Input::Digit(7)));
+
+        for input in [Input::Edit, Input::Digit(1), Input::Digit(2), Input::Digit(3),
+                      Input::Back, Input::Digit(0xF), Input::Edit].iter() {
+            assert!(overlay.input(&mut cpu, *input));
+        }
+        assert_eq!(cpu.registers().v[1], 0x1F);
+
+        for _ in 0..7 {
+            overlay.input(&mut cpu, Input::Down);
+        }
+        overlay.input(&mut cpu, Input::Up);
+        assert_eq!(overlay.selected(), Field::Memory(0x1F0));
+        for input in [Input::Edit, Input::Digit(0xA), Input::Digit(0xB), Input::Edit].iter() {
+            overlay.input(&mut cpu, *input);
+        }
+        assert_eq!(cpu.memory()[0x1F0], 0xAB);
+    }
+
+    #[test]
+    fn refused_values_are_reported() {
+        let mut cpu = cpu();
+        let mut overlay = Overlay::default();
+        for _ in 0..4 {
+            overlay.input(&mut cpu, Input::Down);
+        }
+        overlay.input(&mut cpu, Input::Right);
+        assert_eq!(overlay.selected(), Field::Register(Register::Pc));
+        for input in [Input::Edit, Input::Digit(0xF), Input::Digit(0xF), Input::Digit(0xF),
+                      Input::Digit(0xF), Input::Edit].iter() {
+            overlay.input(&mut cpu, *input);
+        }
+        assert_eq!(cpu.registers().pc, 0x200);
+        assert_eq!(text(overlay.lines(&cpu).last().unwrap()), "0xFFFF doesn't fit in PC");
+    }
+
+    #[test]
+    fn hex_view_follows_and_scrolls() {
+        let mut cpu = cpu();
+        let mut overlay = Overlay::default();
+        assert_eq!(overlay.first_address(&cpu), 0x1F0);
+
+        overlay.input(&mut cpu, Input::Follow);
+        assert_eq!(overlay.first_address(&cpu), 0x000);
+        overlay.input(&mut cpu, Input::Follow);
+        overlay.input(&mut cpu, Input::PageDown);
+        assert_eq!(overlay.first_address(&cpu), 0x040);
+
+        for _ in 0..100 {
+            overlay.input(&mut cpu, Input::PageDown);
+        }
+        assert_eq!(overlay.first_address(&cpu), 0xFC0);
+        overlay.input(&mut cpu, Input::PageUp);
+        assert_eq!(overlay.first_address(&cpu), 0xF80);
+        overlay.input(&mut cpu, Input::Follow);
+        assert_eq!(overlay.first_address(&cpu), 0x1F0);
+    }
+}
@@ -0,0 +1,50 @@
+// Screen colours. Pixels fade from the on colour to the off colour as they
+// turn off.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub name: &'static str,
+    pub on: [f32; 3],
+    pub off: [f32; 3],
+}
+
+pub const PALETTES: [Palette; 4] = [
+    Palette { name: "white", on: [1.0, 1.0, 1.0], off: [0.0, 0.0, 0.0] },
+    Palette { name: "amber", on: [1.0, 0.7, 0.0], off: [0.1, 0.05, 0.0] },
+    Palette { name: "green", on: [0.2, 1.0, 0.3], off: [0.0, 0.08, 0.02] },
+    // The Game Boy's greens
+    Palette { name: "lcd", on: [0.06, 0.22, 0.06], off: [0.61, 0.74, 0.06] },
+];
+
+impl Default for Palette {
+    fn default() -> Palette {
+        PALETTES[0]
+    }
+}
+
+impl Palette {
+    pub fn from_name(name: &str) -> Option<Palette> {
+        PALETTES.iter().find(|palette| palette.name == name).cloned()
+    }
+
+    // The colour of a pixel lit from 0.0 to 1.0
+    pub fn color(&self, brightness: f32) -> [f32; 4] {
+        let mix = |channel: usize| {
+            self.off[channel] + (self.on[channel] - self.off[channel]) * brightness
+        };
+        [mix(0), mix(1), mix(2), 1.0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_fades_from_on_to_off() {
+        let amber = Palette::from_name("amber").unwrap();
+        assert_eq!(amber.color(1.0), [1.0, 0.7, 0.0, 1.0]);
+        assert_eq!(amber.color(0.0), [0.1, 0.05, 0.0, 1.0]);
+        assert_eq!(Palette::default().color(0.5), [0.5, 0.5, 0.5, 1.0]);
+        assert_eq!(Palette::from_name("pink"), None);
+    }
+}
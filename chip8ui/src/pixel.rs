@@ -4,6 +4,7 @@ pub struct Pixel {
 }
 
 impl Pixel {
+    #[cfg(feature = "gl")]
     pub fn update(&mut self, dt: f64) {
         if self.color > 0.0 {
             self.color -= dt as f32 * 8.0;
@@ -17,7 +18,8 @@ impl Pixel {
         self.color = 1.0;
     }
 
-    pub fn color_arr(self) -> [f32; 4] {
-        [self.color, self.color, self.color, 1.0]
+    // From 1.0 when just turned on down to 0.0
+    pub fn brightness(self) -> f32 {
+        self.color
     }
 }
@@ -0,0 +1,145 @@
+// Pause, frame advance, fast forward and slow motion. The Runner asks how
+// much emulated time to step for each update, so the Vms keep running
+// through their own stepping and their timers stay in line with the CPU.
+
+// Frame advance runs one frame of the 60 Hz timer
+pub const FRAME: f64 = 1.0 / 60.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Running,
+    Paused,
+    FastForward,
+    SlowMotion,
+}
+
+pub struct Playback {
+    fast_forward_speed: f64,
+    slow_motion_speed: f64,
+    paused: bool,
+    slow_motion: bool,
+    // Fast forward lasts while its key is held
+    fast_forward: bool,
+    // Frames to run before staying paused again
+    advance: u32,
+}
+
+impl Playback {
+    pub fn new(fast_forward_speed: f64, slow_motion_speed: f64) -> Playback {
+        Playback {
+            fast_forward_speed,
+            slow_motion_speed,
+            paused: false,
+            slow_motion: false,
+            fast_forward: false,
+            advance: 0,
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.advance = 0;
+    }
+
+    // Pauses when running, otherwise runs one more frame
+    pub fn advance_frame(&mut self) {
+        if self.paused {
+            self.advance += 1;
+        } else {
+            self.paused = true;
+        }
+    }
+
+    pub fn set_fast_forward(&mut self, held: bool) {
+        self.fast_forward = held;
+    }
+
+    pub fn toggle_slow_motion(&mut self) {
+        self.slow_motion = !self.slow_motion;
+    }
+
+    // Pausing wins over fast forward, which wins over slow motion
+    pub fn mode(&self) -> Mode {
+        if self.paused {
+            Mode::Paused
+        } else if self.fast_forward {
+            Mode::FastForward
+        } else if self.slow_motion {
+            Mode::SlowMotion
+        } else {
+            Mode::Running
+        }
+    }
+
+    // Emulated time to step for dt seconds of real time
+    pub fn time(&mut self, dt: f64) -> f64 {
+        match self.mode() {
+            Mode::Running => dt,
+            Mode::Paused if self.advance > 0 => {
+                self.advance -= 1;
+                FRAME
+            },
+            Mode::Paused => 0.0,
+            Mode::FastForward => dt * self.fast_forward_speed,
+            Mode::SlowMotion => dt * self.slow_motion_speed,
+        }
+    }
+
+    // What to show on screen, nothing while running normally
+    pub fn indicator(&self) -> Option<String> {
+        match self.mode() {
+            Mode::Running => None,
+            Mode::Paused => Some("PAUSED".to_string()),
+            Mode::FastForward => Some(format!(">> {}X", self.fast_forward_speed)),
+            Mode::SlowMotion => Some(format!("SLOW {}X", self.slow_motion_speed)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes_scale_the_time_stepped() {
+        let mut playback = Playback::new(4.0, 0.25);
+        assert_eq!(playback.time(0.5), 0.5);
+        assert_eq!(playback.indicator(), None);
+
+        playback.toggle_slow_motion();
+        assert_eq!(playback.time(0.5), 0.125);
+        assert_eq!(playback.indicator(), Some("SLOW 0.25X".to_string()));
+
+        playback.set_fast_forward(true);
+        assert_eq!(playback.mode(), Mode::FastForward);
+        assert_eq!(playback.time(0.5), 2.0);
+        assert_eq!(playback.indicator(), Some(">> 4X".to_string()));
+
+        playback.toggle_pause();
+        assert_eq!(playback.time(0.5), 0.0);
+        assert_eq!(playback.indicator(), Some("PAUSED".to_string()));
+
+        playback.set_fast_forward(false);
+        playback.toggle_pause();
+        assert_eq!(playback.mode(), Mode::SlowMotion);
+    }
+
+    #[test]
+    fn frame_advance_steps_one_frame_at_a_time() {
+        let mut playback = Playback::new(4.0, 0.25);
+        playback.advance_frame();
+        assert_eq!(playback.mode(), Mode::Paused);
+        assert_eq!(playback.time(0.5), 0.0);
+
+        playback.advance_frame();
+        playback.advance_frame();
+        assert_eq!(playback.time(0.001), FRAME);
+        assert_eq!(playback.time(0.001), FRAME);
+        assert_eq!(playback.time(0.001), 0.0);
+
+        playback.advance_frame();
+        playback.toggle_pause();
+        playback.toggle_pause();
+        assert_eq!(playback.time(0.5), 0.0);
+    }
+}
@@ -0,0 +1,248 @@
+// Drawing the window's contents out of filled rectangles, so any backend
+// that can fill a rectangle can show it. Software draws into an RGBA buffer
+// in memory and needs no GPU, the OpenGL backend is behind the gl feature.
+use chip8core::Vm;
+#[cfg(any(feature = "gl", test))]
+use overlay::{ Line, Style };
+use palette::Palette;
+use pixel::Pixel;
+use screen::{ Effect, Screen, HEIGHT, WIDTH };
+#[cfg(any(feature = "gl", test))]
+use text;
+
+pub trait Renderer {
+    // Covers the rectangle with the colour, blended by its alpha
+    fn fill(&mut self, rect: [f64; 4], color: [f32; 4]);
+}
+
+// An RGBA buffer with 8 bits per channel and rows from the top. A rectangle
+// covers the pixels whose centres lie inside it, so rectangles that share
+// an edge neither overlap nor leave a gap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Software {
+    width: usize,
+    height: usize,
+    buffer: Vec<u8>,
+}
+
+impl Software {
+    // Starts out opaque black
+    pub fn new(width: usize, height: usize) -> Software {
+        Software { width, height, buffer: [0, 0, 0, 255].repeat(width * height) }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * self.width + x) * 4;
+        [self.buffer[i], self.buffer[i + 1], self.buffer[i + 2], self.buffer[i + 3]]
+    }
+}
+
+impl Renderer for Software {
+    fn fill(&mut self, rect: [f64; 4], color: [f32; 4]) {
+        let [x, y, w, h] = rect;
+        let (x0, x1) = (edge(x, self.width), edge(x + w, self.width));
+        let (y0, y1) = (edge(y, self.height), edge(y + h, self.height));
+        let alpha = color[3].clamp(0.0, 1.0);
+
+        for row in y0..y1 {
+            for column in x0..x1 {
+                let i = (row * self.width + column) * 4;
+                let dst = &mut self.buffer[i..i + 4];
+                for channel in 0..3 {
+                    let src = color[channel].clamp(0.0, 1.0);
+                    let out = src * alpha + dst[channel] as f32 / 255.0 * (1.0 - alpha);
+                    dst[channel] = (out * 255.0).round() as u8;
+                }
+                let out = alpha + dst[3] as f32 / 255.0 * (1.0 - alpha);
+                dst[3] = (out * 255.0).round() as u8;
+            }
+        }
+    }
+}
+
+// The first pixel whose centre is past the coordinate
+fn edge(v: f64, size: usize) -> usize {
+    (v - 0.5).ceil().clamp(0.0, size as f64) as usize
+}
+
+// The Vm's screen with pixels of scale by scale, lit pixels at full
+// brightness rather than fading
+pub fn screenshot(vm: &dyn Vm, scale: usize, palette: Palette, effect: Effect) -> Software {
+    let mut pixels = [Pixel::default(); WIDTH * HEIGHT];
+    for (y, row) in vm.pixels().enumerate() {
+        for (x, &on) in row.iter().enumerate() {
+            if on {
+                pixels[y * WIDTH + x].turn_on();
+            }
+        }
+    }
+
+    let mut software = Software::new(WIDTH * scale, HEIGHT * scale);
+    let screen = Screen { x: 0.0, y: 0.0, scale: scale as f64, effect };
+    draw_screen(&mut software, &screen, palette, &pixels);
+    software
+}
+
+pub fn draw_screen(r: &mut dyn Renderer, screen: &Screen, palette: Palette, pixels: &[Pixel]) {
+    for (index, pixel) in pixels.iter().enumerate() {
+        let rect = screen.pixel(index % WIDTH, index / WIDTH);
+        r.fill(rect, palette.color(pixel.brightness()));
+    }
+}
+
+// A line of the given width just inside the rectangle
+#[cfg(feature = "gl")]
+pub fn draw_border(r: &mut dyn Renderer, rect: [f64; 4], width: f64, color: [f32; 4]) {
+    let [x, y, w, h] = rect;
+    r.fill([x, y, w, width], color);
+    r.fill([x, y + h - width, w, width], color);
+    r.fill([x, y + width, width, h - 2.0 * width], color);
+    r.fill([x + w - width, y + width, width, h - 2.0 * width], color);
+}
+
+// Draws text over a cell, as large as fits, on a dimmed background
+#[cfg(any(feature = "gl", test))]
+pub fn draw_lines(r: &mut dyn Renderer, lines: &[Line], cell: [f64; 4]) {
+    let [left, top, cell_w, cell_h] = cell;
+    r.fill(cell, [0.0, 0.0, 0.0, 0.8]);
+
+    let columns = lines.iter()
+        .map(|line| line.iter().map(|span| span.text.chars().count()).sum())
+        .max()
+        .unwrap_or(0);
+    let (text_w, text_h) = text::size(columns + 2, lines.len() + 1);
+    let scale = (cell_w / text_w as f64).min(cell_h / text_h as f64).floor().max(1.0);
+
+    for (row, line) in lines.iter().enumerate() {
+        let y = top + (text::LINE_HEIGHT * row + text::LINE_HEIGHT / 2) as f64 * scale;
+        let mut column = 1;
+        for span in line {
+            let x = left + (column * text::ADVANCE) as f64 * scale;
+            let length = span.text.chars().count();
+            let color = match span.style {
+                Style::Label => [0.6, 0.6, 0.6, 1.0],
+                Style::Value | Style::Selected | Style::Editing => [1.0, 1.0, 1.0, 1.0],
+                // Fades from orange back to white
+                Style::Changed(heat) => [1.0, 1.0 - 0.4 * heat, 1.0 - heat, 1.0],
+            };
+            let background = match span.style {
+                Style::Selected => Some([0.2, 0.3, 0.8, 1.0]),
+                Style::Editing => Some([0.8, 0.3, 0.1, 1.0]),
+                _ => None,
+            };
+            if let Some(background) = background {
+                let (w, h) = text::size(length, 1);
+                r.fill([x - scale, y - scale, w as f64 * scale, h as f64 * scale], background);
+            }
+            for (px, py) in text::pixels(&span.text) {
+                r.fill([x + px as f64 * scale, y + py as f64 * scale, scale, scale], color);
+            }
+            column += length;
+        }
+    }
+}
+
+// Shows the playback mode in the window's top left corner
+#[cfg(feature = "gl")]
+pub fn draw_indicator(r: &mut dyn Renderer, indicator: &str, size: [f64; 2]) {
+    let scale = (size[1] / 100.0).floor().max(2.0);
+    let (w, h) = text::size(indicator.chars().count() + 2, 1);
+    r.fill([0.0, 0.0, w as f64 * scale, (h + 2) as f64 * scale], [0.0, 0.0, 0.0, 0.8]);
+
+    for (x, y) in text::pixels(indicator) {
+        let x = (x + text::ADVANCE) as f64 * scale;
+        let y = (y + 2) as f64 * scale;
+        r.fill([x, y, scale, scale], [1.0, 0.6, 0.0, 1.0]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use overlay::Span;
+    use super::*;
+
+    fn rgba(pixels: &[[u8; 4]]) -> Vec<u8> {
+        pixels.iter().flat_map(|p| p.iter().cloned()).collect()
+    }
+
+    #[test]
+    fn fill_covers_pixel_centres_and_blends() {
+        const B: [u8; 4] = [0, 0, 0, 255];
+        const W: [u8; 4] = [255, 255, 255, 255];
+        const P: [u8; 4] = [255, 128, 128, 255];
+        const R: [u8; 4] = [128, 0, 0, 255];
+
+        let mut software = Software::new(4, 2);
+        software.fill([0.6, 0.0, 2.0, 2.0], [1.0, 1.0, 1.0, 1.0]);
+        software.fill([-1.0, 0.0, 10.0, 1.4], [1.0, 0.0, 0.0, 0.5]);
+        assert_eq!(software.buffer(), &rgba(&[R, P, P, R, B, W, W, B])[..]);
+    }
+
+    #[test]
+    fn adjacent_rectangles_tile_exactly() {
+        let mut software = Software::new(10, 1);
+        for i in 0..3 {
+            software.fill([i as f64 * 10.0 / 3.0, 0.0, 10.0 / 3.0, 1.0], [0.0, 0.0, 1.0, 0.5]);
+        }
+        // Blending twice anywhere would make a pixel brighter
+        assert!((0..10).all(|x| software.pixel(x, 0) == [0, 0, 128, 255]));
+    }
+
+    #[test]
+    fn screen_uses_palette_and_effect() {
+        let palette = Palette::from_name("green").unwrap();
+        let on = palette.color(1.0);
+        let off = palette.color(0.0);
+        let byte = |c: f32| (c * 255.0).round() as u8;
+        let lit = [byte(on[0]), byte(on[1]), byte(on[2]), 255];
+        let unlit = [byte(off[0]), byte(off[1]), byte(off[2]), 255];
+
+        let mut pixels = [Pixel::default(); WIDTH * HEIGHT];
+        pixels[1].turn_on();
+        let mut software = Software::new(WIDTH * 4, HEIGHT * 4);
+        let screen = Screen { x: 0.0, y: 0.0, scale: 4.0, effect: Effect::Scanlines };
+        draw_screen(&mut software, &screen, palette, &pixels);
+
+        let mut expected = Vec::new();
+        for y in 0..HEIGHT * 4 {
+            for x in 0..WIDTH * 4 {
+                expected.push(match (y % 4, x / 4, y / 4) {
+                    (3, _, _) => [0, 0, 0, 255],
+                    (_, 1, 0) => lit,
+                    _ => unlit,
+                });
+            }
+        }
+        assert_eq!(software.buffer(), &rgba(&expected)[..]);
+    }
+
+    #[test]
+    fn lines_draw_the_font() {
+        let lines = vec![vec![Span { text: "1".to_string(), style: Style::Value }]];
+        let mut software = Software::new(12, 14);
+        draw_lines(&mut software, &lines, [0.0, 0.0, 12.0, 14.0]);
+
+        // Text starts one character in and half a line down, at scale 1
+        let lit: Vec<_> = text::pixels("1").into_iter()
+            .map(|(x, y)| (x + text::ADVANCE, y + text::LINE_HEIGHT / 2))
+            .collect();
+        for y in 0..14 {
+            for x in 0..12 {
+                let expected = if lit.contains(&(x, y)) { [255, 255, 255, 255] } else { [0, 0, 0, 255] };
+                assert_eq!(software.pixel(x, y), expected, "pixel {}, {}", x, y);
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+// Fits the 64x32 screen into a cell with square pixels, letterboxed and
+// centred, and where each pixel is drawn for the display effects.
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Effect {
+    None,
+    // A dark line around every pixel
+    Grid,
+    // A dark line under every row of pixels
+    Scanlines,
+}
+
+impl Effect {
+    pub fn from_name(name: &str) -> Option<Effect> {
+        match name {
+            "none" => Some(Effect::None),
+            "grid" => Some(Effect::Grid),
+            "scanlines" => Some(Effect::Scanlines),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Screen {
+    pub x: f64,
+    pub y: f64,
+    // Size of one pixel
+    pub scale: f64,
+    pub effect: Effect,
+}
+
+impl Screen {
+    // As large as fits in the cell. Integer scaling rounds the pixels down
+    // to a whole size, unless the cell is too small for even one.
+    #[cfg(any(feature = "gl", test))]
+    pub fn fit(cell: [f64; 4], integer_scale: bool, effect: Effect) -> Screen {
+        let [left, top, width, height] = cell;
+        let mut scale = (width / WIDTH as f64).min(height / HEIGHT as f64).max(0.0);
+        if integer_scale && scale >= 1.0 {
+            scale = scale.floor();
+        }
+        let mut x = left + (width - WIDTH as f64 * scale) / 2.0;
+        let mut y = top + (height - HEIGHT as f64 * scale) / 2.0;
+        if integer_scale {
+            x = x.floor();
+            y = y.floor();
+        }
+        Screen { x, y, scale, effect }
+    }
+
+    #[cfg(test)]
+    pub fn rect(&self) -> [f64; 4] {
+        [self.x, self.y, WIDTH as f64 * self.scale, HEIGHT as f64 * self.scale]
+    }
+
+    // The part of the pixel's square that is lit. Effects leave a line
+    // unlit once pixels are large enough to still show through it.
+    pub fn pixel(&self, column: usize, row: usize) -> [f64; 4] {
+        let x = self.x + column as f64 * self.scale;
+        let y = self.y + row as f64 * self.scale;
+        let line = (self.scale / 4.0).floor().max(1.0);
+        match self.effect {
+            Effect::Grid if self.scale >= 3.0 => {
+                [x, y, self.scale - line, self.scale - line]
+            },
+            Effect::Scanlines if self.scale >= 2.0 => [x, y, self.scale, self.scale - line],
+            _ => [x, y, self.scale, self.scale],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_letterboxes_to_two_by_one() {
+        let screen = Screen::fit([0.0, 0.0, 800.0, 600.0], false, Effect::None);
+        assert_eq!(screen.rect(), [0.0, 100.0, 800.0, 400.0]);
+
+        let screen = Screen::fit([10.0, 0.0, 1000.0, 200.0], false, Effect::None);
+        assert_eq!(screen.rect(), [310.0, 0.0, 400.0, 200.0]);
+    }
+
+    #[test]
+    fn integer_scale_rounds_down_and_centres() {
+        let screen = Screen::fit([0.0, 0.0, 1000.0, 500.0], true, Effect::None);
+        assert_eq!(screen.scale, 15.0);
+        assert_eq!(screen.rect(), [20.0, 10.0, 960.0, 480.0]);
+
+        // Too small for whole pixels
+        let screen = Screen::fit([0.0, 0.0, 32.0, 16.0], true, Effect::None);
+        assert_eq!(screen.scale, 0.5);
+    }
+
+    #[test]
+    fn effects_leave_lines_between_pixels() {
+        let screen = Screen { x: 0.0, y: 0.0, scale: 8.0, effect: Effect::Grid };
+        assert_eq!(screen.pixel(1, 2), [8.0, 16.0, 6.0, 6.0]);
+
+        let screen = Screen { effect: Effect::Scanlines, ..screen };
+        assert_eq!(screen.pixel(1, 2), [8.0, 16.0, 8.0, 6.0]);
+
+        let screen = Screen { scale: 2.0, effect: Effect::Grid, ..screen };
+        assert_eq!(screen.pixel(1, 2), [2.0, 4.0, 2.0, 2.0]);
+        assert_eq!(Effect::from_name("scanlines"), Some(Effect::Scanlines));
+        assert_eq!(Effect::from_name("crt"), None);
+    }
+}
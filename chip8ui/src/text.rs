@@ -0,0 +1,108 @@
+// A 3x5 bitmap font for the overlays, drawn with the same rectangles as the
+// screen so no font files are needed. Lowercase letters are shown as
+// uppercase.
+
+pub const GLYPH_W: usize = 3;
+pub const GLYPH_H: usize = 5;
+// Glyph width plus one column of space
+pub const ADVANCE: usize = GLYPH_W + 1;
+pub const LINE_HEIGHT: usize = GLYPH_H + 2;
+
+// One row per byte, the highest of the three bits is the leftmost pixel
+pub fn glyph(c: char) -> Option<[u8; GLYPH_H]> {
+    let rows = match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '*' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        _ => return None,
+    };
+    Some(rows)
+}
+
+// The lit pixels of a line of text as x, y offsets in font pixels. Unknown
+// characters are left blank.
+pub fn pixels(text: &str) -> Vec<(usize, usize)> {
+    let mut pixels = Vec::new();
+    for (index, c) in text.chars().enumerate() {
+        if let Some(rows) = glyph(c) {
+            for (y, row) in rows.iter().enumerate() {
+                for x in 0..GLYPH_W {
+                    if row >> (GLYPH_W - 1 - x) & 1 == 1 {
+                        pixels.push((index * ADVANCE + x, y));
+                    }
+                }
+            }
+        }
+    }
+    pixels
+}
+
+// Size of a block of text in font pixels
+pub fn size(columns: usize, lines: usize) -> (usize, usize) {
+    (columns * ADVANCE, lines * LINE_HEIGHT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyphs_cover_hex_digits_and_letters() {
+        for c in "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ .:-_<>/*',".chars() {
+            assert!(glyph(c).is_some(), "no glyph for {:?}", c);
+        }
+        assert_eq!(glyph('a'), glyph('A'));
+        assert_eq!(glyph('~'), None);
+    }
+
+    #[test]
+    fn pixels_advance_per_character() {
+        assert_eq!(pixels("-"), vec![(0, 2), (1, 2), (2, 2)]);
+        assert_eq!(pixels(" -"), vec![(4, 2), (5, 2), (6, 2)]);
+        assert_eq!(pixels("~-"), pixels(" -"));
+    }
+}
@@ -0,0 +1,357 @@
+// The window, drawn with OpenGL, and the keyboard, mouse and gamepad input
+// that drives the views in it.
+use sdl2_window::Sdl2Window;
+use piston::event_loop::*;
+use piston::input::*;
+use piston::window::WindowSettings;
+use graphics::{ Context, Rectangle };
+use opengl_graphics::{
+    GlGraphics,
+    OpenGL,
+};
+use chip8core::Key as Chip8Key;
+use cheats::CheatMenu;
+use grid::Grid;
+use library::{ Recent, Slots };
+use menu::Menu;
+use overlay::{ Input, Overlay, Span, Style };
+use playback::Playback;
+use render;
+use render::Renderer;
+use screen::Screen;
+use { toggle, Panel, Runner, Settings, View };
+
+struct GlRenderer<'a> {
+    c: &'a Context,
+    gl: &'a mut GlGraphics,
+}
+
+impl<'a> Renderer for GlRenderer<'a> {
+    fn fill(&mut self, rect: [f64; 4], color: [f32; 4]) {
+        Rectangle::new(color).draw(rect, &self.c.draw_state, self.c.transform, self.gl);
+    }
+}
+
+impl Runner {
+    // Runs several VMs side by side in a grid. Tab moves the keyboard focus
+    // to the next view, as does clicking a view, and F1 toggles sending
+    // key presses to all of them. F2 shows the registers and memory of the
+    // focused view over it, F3 switches the memory shown between PC, I and
+    // a fixed address, and Enter edits the value picked with the arrows.
+    // F4 opens the cheat menu of the focused view instead.
+    //
+    // F5 pauses and resumes every view, F6 runs one frame at a time, Space
+    // fast forwards while held and F7 toggles slow motion.
+    //
+    // F10, or Start on a gamepad, opens the menu of the focused view, which
+    // opens ROMs, resets, saves and loads states and changes the speed,
+    // quirks and palette. The views stop while it's open. The arrows or the
+    // d-pad move through it, Enter or A picks and Backspace or B goes back.
+    pub fn run_views(views: &mut [View], settings: &Settings) -> Result<(), String> {
+        if views.is_empty() {
+            return Err("No VMs to run".to_string());
+        }
+        let grid = Grid::for_views(views.len());
+        let mut focus = 0;
+        let mut broadcast = settings.broadcast;
+        let mut cursor = [0.0, 0.0];
+        let mut size = [0.0, 0.0];
+        let mut overlay = Overlay::default();
+        let mut cheat_menu = CheatMenu::default();
+        let mut panel = None;
+        let mut playback = Playback::new(settings.fast_forward, settings.slow_motion);
+        let mut menu = Menu::new(settings.rom_dir.clone());
+        let mut recent = Recent::load(settings.recent_file.clone());
+        let mut palette = settings.palette;
+        let mut slot = 0;
+
+        for view in views.iter_mut() {
+            view.slots = Slots::new(settings.state_dir.clone());
+            if let Some(ref path) = view.rom_path {
+                let _ = recent.add(path);
+            }
+        }
+        if views[focus].waiting {
+            panel = Some(Panel::Menu);
+            menu.browse();
+        }
+
+        let opengl = OpenGL::V3_2;
+
+        let mut window: Sdl2Window = WindowSettings::new("Chip8", settings.window_size)
+            .fullscreen(settings.fullscreen)
+            .resizable(true)
+            .exit_on_esc(true)
+            .opengl(opengl)
+            .build()
+            .unwrap();
+
+        let ref mut gl = GlGraphics::new(opengl);
+
+        let mut events = window.events();
+        while let Some(e) = events.next(&mut window) {
+            if let Some(args) = e.update_args() {
+                // Paused Vms are still stepped, by no time, so wrappers
+                // like the remote control keep answering
+                let time = if panel == Some(Panel::Menu) { 0.0 } else { playback.time(args.dt) };
+                for view in views.iter_mut() {
+                    let time = if view.waiting { 0.0 } else { time };
+                    view.vm.step(time * view.speed).map_err(|e| e.to_string())?;
+                    view.follow_reloads();
+                    if let Some(vm) = view.vm.debug() {
+                        view.cheats.apply(vm);
+                    }
+                    for p in view.pixels.iter_mut() {
+                        p.update(time);
+                    }
+                }
+                if panel == Some(Panel::Registers) {
+                    if let Some(vm) = views[focus].vm.debug() {
+                        overlay.update(vm, args.dt);
+                    }
+                }
+            }
+
+            if let Some(args) = e.render_args() {
+                size = [args.width as f64, args.height as f64];
+                gl.draw(args.viewport(), |c, gl| {
+                        graphics::clear([0.0, 0.0, 0.0, 1.0], gl);
+                        let r = &mut GlRenderer { c: &c, gl };
+
+                        for (index, view) in views.iter_mut().enumerate() {
+                            let cell = grid.cell(index, size[0], size[1]);
+                            let screen = Screen::fit(cell, settings.integer_scale, settings.effect);
+
+                            for (y_row, row) in view.vm.pixels().enumerate() {
+                                for (x_col, on) in row.iter().enumerate() {
+                                    if *on {
+                                        view.pixels[y_row * 64 + x_col].turn_on();
+                                    }
+                                }
+                            }
+                            render::draw_screen(r, &screen, palette, &view.pixels);
+
+                            // Outline the views that receive input when there is a choice
+                            if grid.columns * grid.rows > 1 && (broadcast || index == focus) {
+                                render::draw_border(r, cell, 1.0, [1.0, 0.6, 0.0, 1.0]);
+                            }
+
+                            if let Some(panel) = panel.filter(|_| index == focus) {
+                                let options = view.options(palette, slot);
+                                let file = view.cheat_file.as_deref();
+                                let lines = match (view.vm.debug(), panel) {
+                                    (_, Panel::Menu) => menu.lines(&options, &recent),
+                                    (Some(vm), Panel::Registers) => overlay.lines(vm),
+                                    (Some(vm), Panel::Cheats) => {
+                                        cheat_menu.lines(vm, &view.cheats, file)
+                                    },
+                                    (None, _) => vec![vec![Span {
+                                        text: "NO DEBUG ACCESS".to_string(),
+                                        style: Style::Value,
+                                    }]],
+                                };
+                                render::draw_lines(r, &lines, cell);
+                            }
+                        }
+
+                        if let Some(indicator) = playback.indicator() {
+                            render::draw_indicator(r, &indicator, size);
+                        }
+                    }
+                );
+            }
+
+            if let Some(position) = e.mouse_cursor_args() {
+                cursor = position;
+            }
+
+            if let Some(button) = e.press_args() {
+                if button == Button::Keyboard(Key::F10) || controller_button(button) == Some(6) {
+                    panel = toggle(panel, Panel::Menu);
+                    menu.reset();
+                    continue;
+                }
+                // The menu takes every key while it's open
+                if panel == Some(Panel::Menu) {
+                    let input = match overlay_input(button) {
+                        Some(input) => Some(input),
+                        None => controller_input(button),
+                    };
+                    if let Some(input) = input {
+                        let view = &mut views[focus];
+                        let mut options = view.options(palette, slot);
+                        let action = menu.input(input, &mut options, &recent);
+                        view.set_options(&options);
+                        palette = options.palette;
+                        slot = options.slot;
+
+                        if let Some(action) = action {
+                            view.release_all_keys();
+                            match view.perform(action, &mut recent, slot) {
+                                Ok(None) => panel = None,
+                                Ok(Some(status)) | Err(status) => menu.set_status(status),
+                            }
+                        }
+                    }
+                    continue;
+                }
+
+                let old_focus = focus;
+                match button {
+                    Button::Keyboard(Key::Tab) => focus = (focus + 1) % views.len(),
+                    Button::Keyboard(Key::F1) => {
+                        broadcast = !broadcast;
+                        for view in views.iter_mut() {
+                            view.release_all_keys();
+                        }
+                    },
+                    Button::Keyboard(Key::F2) => {
+                        panel = toggle(panel, Panel::Registers);
+                        overlay.reset();
+                    },
+                    Button::Keyboard(Key::F4) => {
+                        panel = toggle(panel, Panel::Cheats);
+                        cheat_menu.reset();
+                    },
+                    Button::Keyboard(Key::F5) => playback.toggle_pause(),
+                    Button::Keyboard(Key::F6) => playback.advance_frame(),
+                    Button::Keyboard(Key::F7) => playback.toggle_slow_motion(),
+                    Button::Keyboard(Key::Space) => playback.set_fast_forward(true),
+                    Button::Mouse(MouseButton::Left) => {
+                        let clicked = grid.cell_at(cursor[0], cursor[1], size[0], size[1]);
+                        if let Some(index) = clicked.filter(|&index| index < views.len()) {
+                            focus = index;
+                        }
+                    },
+                    _ => (),
+                }
+                // Keys held while the focus moves would otherwise stay pressed
+                if focus != old_focus && !broadcast {
+                    views[old_focus].release_all_keys();
+                }
+                if focus != old_focus {
+                    overlay.reset();
+                    cheat_menu.reset();
+                }
+
+                // Keys the panel uses, like digits typed into a value,
+                // don't reach the Vm
+                let view = &mut views[focus];
+                let file = view.cheat_file.as_deref();
+                let consumed = match (panel, overlay_input(button), view.vm.debug()) {
+                    (Some(Panel::Registers), Some(input), Some(vm)) => overlay.input(vm, input),
+                    (Some(Panel::Cheats), Some(input), Some(vm)) => {
+                        cheat_menu.input(vm, &mut view.cheats, file, input)
+                    },
+                    _ => false,
+                };
+                if consumed {
+                    continue;
+                }
+
+                if let Some(key) = chip8_key_from_button(button) {
+                    for (index, view) in views.iter_mut().enumerate() {
+                        if broadcast || index == focus {
+                            view.vm.press_key(key);
+                        }
+                    }
+                }
+            }
+
+            if let Some(button) = e.release_args() {
+                if let Button::Keyboard(Key::Space) = button {
+                    playback.set_fast_forward(false);
+                }
+                if let Some(key) = chip8_key_from_button(button) {
+                    for (index, view) in views.iter_mut().enumerate() {
+                        if broadcast || index == focus {
+                            view.vm.release_key(key);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+fn overlay_input(button: Button) -> Option<Input> {
+    if let Button::Keyboard(key) = button {
+        return match key {
+            Key::Left => Some(Input::Left),
+            Key::Right => Some(Input::Right),
+            Key::Up => Some(Input::Up),
+            Key::Down => Some(Input::Down),
+            Key::PageUp => Some(Input::PageUp),
+            Key::PageDown => Some(Input::PageDown),
+            Key::F3 => Some(Input::Follow),
+            Key::Return => Some(Input::Edit),
+            Key::Backspace => Some(Input::Back),
+            Key::D0 => Some(Input::Digit(0x0)),
+            Key::D1 => Some(Input::Digit(0x1)),
+            Key::D2 => Some(Input::Digit(0x2)),
+            Key::D3 => Some(Input::Digit(0x3)),
+            Key::D4 => Some(Input::Digit(0x4)),
+            Key::D5 => Some(Input::Digit(0x5)),
+            Key::D6 => Some(Input::Digit(0x6)),
+            Key::D7 => Some(Input::Digit(0x7)),
+            Key::D8 => Some(Input::Digit(0x8)),
+            Key::D9 => Some(Input::Digit(0x9)),
+            Key::A => Some(Input::Digit(0xA)),
+            Key::B => Some(Input::Digit(0xB)),
+            Key::C => Some(Input::Digit(0xC)),
+            Key::D => Some(Input::Digit(0xD)),
+            Key::E => Some(Input::Digit(0xE)),
+            Key::F => Some(Input::Digit(0xF)),
+            _ => None,
+        }
+    }
+    None
+}
+
+// SDL game controller buttons
+fn controller_button(button: Button) -> Option<u8> {
+    match button {
+        Button::Controller(ControllerButton { button, .. }) => Some(button),
+        _ => None,
+    }
+}
+
+fn controller_input(button: Button) -> Option<Input> {
+    match controller_button(button) {
+        Some(0) => Some(Input::Edit),
+        Some(1) => Some(Input::Back),
+        Some(11) => Some(Input::Up),
+        Some(12) => Some(Input::Down),
+        Some(13) => Some(Input::Left),
+        Some(14) => Some(Input::Right),
+        _ => None,
+    }
+}
+
+fn chip8_key_from_button(button: Button) -> Option<Chip8Key> {
+    if let Button::Keyboard(key) = button {
+        return match key {
+            Key::D1 => Some(Chip8Key::D1),
+            Key::D2 => Some(Chip8Key::D2),
+            Key::D3 => Some(Chip8Key::D3),
+            Key::Q  => Some(Chip8Key::D4),
+            Key::W  => Some(Chip8Key::D5),
+            Key::E  => Some(Chip8Key::D6),
+            Key::A  => Some(Chip8Key::D7),
+            Key::S  => Some(Chip8Key::D8),
+            Key::D  => Some(Chip8Key::D9),
+            Key::Z  => Some(Chip8Key::A),
+            Key::X  => Some(Chip8Key::D0),
+            Key::C  => Some(Chip8Key::B),
+            Key::D4 => Some(Chip8Key::C),
+            Key::R  => Some(Chip8Key::D),
+            Key::F  => Some(Chip8Key::E),
+            Key::V  => Some(Chip8Key::F),
+            _ => None,
+        }
+    }
+    None
+}
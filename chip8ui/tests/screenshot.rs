@@ -0,0 +1,46 @@
+// Renders a running Vm's screen without a window and compares the RGBA
+// buffer with the one expected from the font.
+extern crate chip8core;
+extern crate chip8ui;
+extern crate chip8vm;
+
+use chip8core::Vm;
+use chip8ui::{ screenshot, Effect, Palette };
+use chip8vm::Cpu;
+
+// Draws the digit 0 in the top left corner
+const ROM: [u8; 8] = [
+    0x60, 0x00, // LD V0, 0
+    0xA0, 0x00, // LD I, 0
+    0xD0, 0x05, // DRW V0, V0, 5
+    0x12, 0x06, // JP 206
+];
+
+const GLYPH: [u8; 5] = [0xF0, 0x90, 0x90, 0x90, 0xF0];
+
+fn rgba(color: [f32; 4]) -> [u8; 4] {
+    let byte = |c: f32| (c * 255.0).round() as u8;
+    [byte(color[0]), byte(color[1]), byte(color[2]), 255]
+}
+
+#[test]
+fn screenshot_matches_the_drawn_digit() {
+    let mut cpu = Cpu::new();
+    cpu.load_rom_bytes(&ROM).unwrap();
+    cpu.step(0.01).unwrap();
+
+    let palette = Palette::from_name("amber").unwrap();
+    let image = screenshot(&cpu, 2, palette, Effect::None);
+    assert_eq!((image.width(), image.height()), (128, 64));
+
+    let (on, off) = (rgba(palette.color(1.0)), rgba(palette.color(0.0)));
+    let mut expected = Vec::new();
+    for y in 0..64 {
+        for x in 0..128 {
+            let (column, row) = (x / 2, y / 2);
+            let lit = row < 5 && column < 8 && GLYPH[row] & (0x80 >> column) != 0;
+            expected.extend_from_slice(if lit { &on } else { &off });
+        }
+    }
+    assert_eq!(image.buffer(), &expected[..]);
+}
@@ -0,0 +1,173 @@
+// Baseline numbers for the interpreter: instruction throughput of a few
+// ROMs, DXYN for different sprite sizes and positions, loading a ROM and
+// walking the framebuffer through Vm::pixels. Run these before and after a
+// change to the Cpu internals, e.g. the framebuffer layout.
+#[macro_use]
+extern crate criterion;
+extern crate chip8core;
+extern crate chip8vm;
+
+use std::fs::File;
+use std::io::{ Cursor, Read };
+use std::path::PathBuf;
+use chip8core::{ Vm, PROGRAM_START };
+use criterion::{ BatchSize, BenchmarkId, Criterion, Throughput };
+use chip8vm::{ Cpu, LoadOptions, Quirks, XorShift };
+
+const FRAME: f64 = 1.0 / 60.0;
+const FRAMES: u64 = 60;
+// At 540 instructions per second
+const INSTRUCTIONS: u64 = FRAMES * 9;
+const SPRITE: u16 = 0x800;
+
+const ROMS: [&str; 3] = [
+    "benches/roms/bounce.ch8",
+    "tests/roms/opcode.ch8",
+    "tests/roms/flags.ch8",
+];
+
+fn read_rom(path: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), path].iter().collect();
+    File::open(path).and_then(|mut f| f.read_to_end(&mut data)).unwrap();
+    data
+}
+
+fn rom(ops: &[u16]) -> Vec<u8> {
+    ops.iter().flat_map(|op| vec![(op >> 8) as u8, *op as u8]).collect()
+}
+
+// The first second of emulated time, driven the way the frontends drive it.
+// Every iteration starts from a freshly loaded Cpu, so it runs the same part
+// of the ROM rather than wherever the previous iteration stopped.
+fn throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("throughput");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+
+    for path in ROMS.iter() {
+        let rom = read_rom(path);
+        let name = path.rsplit('/').next().unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(name), &rom, |b, rom| {
+            let setup = || {
+                let mut cpu = Cpu::with_random(XorShift::new(1));
+                cpu.load_rom_bytes(rom).unwrap();
+                cpu
+            };
+            b.iter_batched(setup, |mut cpu| {
+                for _ in 0..FRAMES {
+                    cpu.step(FRAME).unwrap();
+                }
+                cpu
+            }, BatchSize::SmallInput);
+        });
+    }
+    group.finish();
+}
+
+// Sets up V0, V1 and I, then loops over `DRW V0, V1, n` and a jump back to
+// it. Every iteration runs the draw and the jump.
+fn draw_cpu(x: u8, y: u8, n: u16, quirks: Quirks) -> Cpu<XorShift> {
+    let ops = [
+        0x6000 | x as u16, 0x6100 | y as u16, 0xA000 | SPRITE,
+        0xD010 | n, 0x1000 | (PROGRAM_START as u16 + 6),
+    ];
+    let sprite = [0xFF; 15];
+    let preload: [(u16, &[u8]); 1] = [(SPRITE, &sprite)];
+    let options = LoadOptions { preload: &preload, ..LoadOptions::default() };
+
+    let mut cpu = Cpu::with_random(XorShift::new(1));
+    cpu.set_quirks(quirks);
+    cpu.load_rom_bytes_with(&rom(&ops), &options).unwrap();
+    for _ in 0..3 {
+        cpu.step_instruction().unwrap();
+    }
+    cpu
+}
+
+fn draw(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw");
+
+    for &n in [1, 5, 8, 15].iter() {
+        group.bench_function(BenchmarkId::new("aligned", n), |b| {
+            let mut cpu = draw_cpu(8, 8, n, Quirks::default());
+            b.iter(|| {
+                cpu.step_instruction().unwrap();
+                cpu.step_instruction().unwrap();
+            });
+        });
+    }
+
+    // Sprites straddling the bottom right corner, wrapped by default and
+    // cut off with the clipping quirk
+    let cases = [
+        ("unaligned", 13, 9, Quirks::default()),
+        ("wrap_x", 60, 8, Quirks::default()),
+        ("wrap_xy", 60, 28, Quirks::default()),
+        ("clip_xy", 60, 28, Quirks { clip_sprites: true, ..Quirks::default() }),
+    ];
+    for &(name, x, y, quirks) in cases.iter() {
+        group.bench_function(BenchmarkId::new(name, 15), |b| {
+            let mut cpu = draw_cpu(x, y, 15, quirks);
+            b.iter(|| {
+                cpu.step_instruction().unwrap();
+                cpu.step_instruction().unwrap();
+            });
+        });
+    }
+    group.finish();
+}
+
+fn load_rom(c: &mut Criterion) {
+    let mut group = c.benchmark_group("load_rom");
+    let small = read_rom("benches/roms/bounce.ch8");
+    let full = vec![0xAA; 0x1000 - PROGRAM_START];
+
+    for &(name, rom) in [("small", &small), ("full", &full)].iter() {
+        group.throughput(Throughput::Bytes(rom.len() as u64));
+        group.bench_with_input(BenchmarkId::new("bytes", name), rom, |b, rom| {
+            let mut cpu = Cpu::with_random(XorShift::new(1));
+            b.iter(|| cpu.load_rom_bytes(rom).unwrap());
+        });
+        group.bench_with_input(BenchmarkId::new("reader", name), rom, |b, rom| {
+            let mut cpu = Cpu::with_random(XorShift::new(1));
+            b.iter(|| cpu.load_rom(&mut Cursor::new(&rom[..])).unwrap());
+        });
+    }
+    group.finish();
+}
+
+// What a frontend does every frame: visit every pixel once
+fn pixels(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pixels");
+    group.throughput(Throughput::Elements(64 * 32));
+
+    let blank = Cpu::with_random(XorShift::new(1));
+    let mut busy = Cpu::with_random(XorShift::new(1));
+    busy.load_rom_bytes(&read_rom("benches/roms/bounce.ch8")).unwrap();
+    for _ in 0..FRAMES {
+        busy.step(FRAME).unwrap();
+    }
+
+    for &(name, cpu) in [("blank", &blank), ("busy", &busy)].iter() {
+        group.bench_function(BenchmarkId::new("count_lit", name), |b| {
+            b.iter(|| cpu.pixels().map(|row| row.iter().filter(|&&p| p).count()).sum::<usize>());
+        });
+        group.bench_function(BenchmarkId::new("coordinates", name), |b| {
+            b.iter(|| {
+                let mut lit = Vec::with_capacity(64 * 32);
+                for (y, row) in cpu.pixels().enumerate() {
+                    for (x, &p) in row.iter().enumerate() {
+                        if p {
+                            lit.push((x, y));
+                        }
+                    }
+                }
+                lit
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, throughput, draw, load_rom, pixels);
+criterion_main!(benches);
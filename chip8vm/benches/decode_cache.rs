@@ -0,0 +1,56 @@
+// Compares the plain interpreter against the decoded instruction cache by
+// running a fixed number of instructions of a few ROMs.
+#[macro_use]
+extern crate criterion;
+extern crate chip8core;
+extern crate chip8vm;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use chip8core::Vm;
+use criterion::{ BenchmarkId, Criterion, Throughput };
+use chip8vm::{ Cpu, XorShift };
+
+const INSTRUCTIONS: u64 = 100_000;
+
+const ROMS: [&str; 3] = [
+    "benches/roms/bounce.ch8",
+    "tests/roms/opcode.ch8",
+    "tests/golden/arith.ch8",
+];
+
+fn read_rom(path: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), path].iter().collect();
+    File::open(path).and_then(|mut f| f.read_to_end(&mut data)).unwrap();
+    data
+}
+
+fn run(rom: &[u8], decode_cache: bool) -> Cpu<XorShift> {
+    let mut cpu = Cpu::with_random(XorShift::new(1));
+    cpu.set_decode_cache(decode_cache);
+    cpu.load_rom_bytes(rom).unwrap();
+    for _ in 0..INSTRUCTIONS {
+        cpu.step_instruction().unwrap();
+    }
+    cpu
+}
+
+fn decode_cache(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_cache");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+
+    for path in ROMS.iter() {
+        let rom = read_rom(path);
+        let name = path.rsplit('/').next().unwrap();
+        group.bench_with_input(BenchmarkId::new("interpreter", name), &rom,
+                               |b, rom| b.iter(|| run(rom, false)));
+        group.bench_with_input(BenchmarkId::new("cached", name), &rom,
+                               |b, rom| b.iter(|| run(rom, true)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode_cache);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+// Compares the interpreter against the JIT by running a few ROMs through
+// Vm::step. The VMs are warmed up first so the JIT's numbers don't include
+// compiling the blocks.
+#[macro_use]
+extern crate criterion;
+extern crate chip8core;
+extern crate chip8vm;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use chip8core::Vm;
+use criterion::{ BenchmarkId, Criterion, Throughput };
+use chip8vm::{ Cpu, JitCpu, XorShift };
+
+const FRAME: f64 = 1.0 / 60.0;
+const FRAMES: u64 = 60;
+// At 540 instructions per second
+const INSTRUCTIONS: u64 = FRAMES * 9;
+
+const ROMS: [&str; 2] = [
+    "benches/roms/bounce.ch8",
+    "tests/roms/opcode.ch8",
+];
+
+fn read_rom(path: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), path].iter().collect();
+    File::open(path).and_then(|mut f| f.read_to_end(&mut data)).unwrap();
+    data
+}
+
+fn run<V: Vm>(vm: &mut V) {
+    for _ in 0..FRAMES {
+        vm.step(FRAME).unwrap();
+    }
+}
+
+fn warmed_up<V: Vm>(mut vm: V, rom: &[u8]) -> V {
+    vm.load_rom_bytes(rom).unwrap();
+    run(&mut vm);
+    vm
+}
+
+fn jit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("jit");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+
+    for path in ROMS.iter() {
+        let rom = read_rom(path);
+        let name = path.rsplit('/').next().unwrap();
+        let mut cpu = warmed_up(Cpu::with_random(XorShift::new(1)), &rom);
+        group.bench_function(BenchmarkId::new("interpreter", name), |b| b.iter(|| run(&mut cpu)));
+        let mut jit = warmed_up(JitCpu::with_random(XorShift::new(1)), &rom);
+        group.bench_function(BenchmarkId::new("jit", name), |b| b.iter(|| run(&mut jit)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, jit);
+criterion_main!(benches);
@@ -0,0 +1,50 @@
+; Benchmark workload shaped like a game loop: a ball bouncing off the
+; screen edges with a score counter drawn through FX33 and FX29.
+;
+; VA, VB  ball x and y
+; VC, VD  ball dx and dy
+; VE      score
+        CLS
+        LD VA, 10
+        LD VB, 5
+        LD VC, 1
+        LD VD, 1
+        LD VE, 0
+        LD I, ball
+        DRW VA, VB, 4
+loop:   LD I, ball
+        DRW VA, VB, 4
+        ADD VA, VC
+        ADD VB, VD
+        SNE VA, 0
+        CALL flipx
+        SNE VA, 60
+        CALL flipx
+        SNE VB, 0
+        CALL flipy
+        SNE VB, 28
+        CALL flipy
+        DRW VA, VB, 4
+        ADD VE, 1
+        LD I, score
+        LD B, VE
+        LD V2, [I]
+        LD V3, 0
+        LD V4, 0
+        LD F, V2
+        DRW V3, V4, 5
+        DRW V3, V4, 5
+        JP loop
+
+flipx:  LD V5, 0
+        SUB V5, VC
+        LD VC, V5
+        RET
+
+flipy:  LD V5, 0
+        SUB V5, VD
+        LD VD, V5
+        RET
+
+ball:   DB 0x60, 0xF0, 0xF0, 0x60
+score:  DB 0, 0, 0
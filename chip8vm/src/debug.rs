@@ -0,0 +1,332 @@
+use chip8core::{ DebugVm, Quirks, Register, RegisterError, RegisterFile, RomError, MEMORY_SIZE };
+#[cfg(feature = "std")]
+use chip8core::StateError;
+use random::Random;
+use super::Cpu;
+
+#[cfg(feature = "std")]
+const MAGIC: &[u8; 4] = b"C8ST";
+#[cfg(feature = "std")]
+const VERSION: u8 = 1;
+#[cfg(feature = "std")]
+const NO_AWAITED_KEY: u8 = 0xFF;
+
+impl<R: Random> DebugVm for Cpu<R> {
+    fn registers(&self) -> RegisterFile {
+        RegisterFile {
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            sp: self.sp,
+            stack: self.stack,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    // I and PC must point into memory since instructions read through them
+    fn set_register(&mut self, register: Register, value: u16) -> Result<(), RegisterError> {
+        let max = match register {
+            Register::V(x) if x as usize >= self.v.len() => {
+                return Err(RegisterError::NoSuchRegister(register));
+            },
+            Register::V(_) | Register::DelayTimer | Register::SoundTimer => 0xFF,
+            Register::I => MEMORY_SIZE - 1,
+            Register::Pc => MEMORY_SIZE - 2,
+            Register::Sp => self.stack.len(),
+        };
+        if value as usize > max {
+            return Err(RegisterError::OutOfRange(register, value));
+        }
+
+        match register {
+            Register::V(x) => self.v[x as usize] = value as u8,
+            Register::I => self.i = value,
+            Register::Pc => self.pc = value,
+            Register::Sp => self.sp = value,
+            Register::DelayTimer => self.delay_timer = value as u8,
+            Register::SoundTimer => self.sound_timer = value as u8,
+        }
+        Ok(())
+    }
+
+    fn memory(&self) -> &[u8] {
+        &self.mem
+    }
+
+    fn write_memory(&mut self, address: usize, data: &[u8]) -> Result<(), RomError> {
+        if address > self.mem.len() || data.len() > self.mem.len() - address {
+            return Err(RomError::OutOfBounds);
+        }
+        self.mem[address..(address + data.len())].copy_from_slice(data);
+        self.invalidate(address, data.len());
+        Ok(())
+    }
+
+    fn keys(&self) -> &[bool; 16] {
+        &self.keys
+    }
+
+    fn quirks(&self) -> Quirks {
+        Cpu::quirks(self)
+    }
+
+    fn set_quirks(&mut self, quirks: Quirks) {
+        Cpu::set_quirks(self, quirks);
+    }
+
+    // The quirks, the font set and the random number generator are settings
+    // of the Cpu and not part of the state
+    #[cfg(feature = "std")]
+    fn save_state(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(MAGIC.len() + 1 + MEMORY_SIZE + 400);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&self.mem);
+        out.extend_from_slice(&self.v);
+        for &word in [self.i, self.pc, self.sp].iter().chain(self.stack.iter()) {
+            out.extend_from_slice(&word.to_be_bytes());
+        }
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+        out.extend(self.gfx.chunks(8).map(pack_bits));
+        out.extend(self.keys.chunks(8).map(pack_bits));
+        out.push(self.awaited_key.unwrap_or(NO_AWAITED_KEY));
+        out.extend_from_slice(&self.clock_accumulator.to_bits().to_be_bytes());
+        out.extend_from_slice(&self.tick_accumulator.to_bits().to_be_bytes());
+        out
+    }
+
+    // Leaves the Cpu untouched if the state can't be read
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut input = Input(state);
+        if input.take(MAGIC.len())? != MAGIC {
+            return Err(StateError::Malformed);
+        }
+        let version = input.byte()?;
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let mem = input.take(MEMORY_SIZE)?;
+        let v = input.take(16)?;
+        let i = input.word()?;
+        let pc = input.word()?;
+        let sp = input.word()?;
+        let mut stack = [0; 16];
+        for entry in stack.iter_mut() {
+            *entry = input.word()?;
+        }
+        let delay_timer = input.byte()?;
+        let sound_timer = input.byte()?;
+        let gfx = input.take(self.gfx.len() / 8)?;
+        let keys = input.take(2)?;
+        let awaited_key = match input.byte()? {
+            NO_AWAITED_KEY => None,
+            x if x < 16 => Some(x),
+            _ => return Err(StateError::Malformed),
+        };
+        let clock_accumulator = f64::from_bits(input.quad()?);
+        let tick_accumulator = f64::from_bits(input.quad()?);
+        if !input.0.is_empty() || sp as usize > stack.len() {
+            return Err(StateError::Malformed);
+        }
+        // The same bounds as set_register, a state that passes can't make
+        // the next fetch or return read past memory
+        let fetchable = |addr: u16| addr as usize <= MEMORY_SIZE - 2;
+        if !fetchable(pc) || !stack.iter().all(|&addr| fetchable(addr)) ||
+           i as usize > MEMORY_SIZE - 1 {
+            return Err(StateError::Malformed);
+        }
+
+        self.mem.copy_from_slice(mem);
+        self.v.copy_from_slice(v);
+        self.i = i;
+        self.pc = pc;
+        self.sp = sp;
+        self.stack = stack;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        for (pixels, &bits) in self.gfx.chunks_mut(8).zip(gfx) {
+            unpack_bits(bits, pixels);
+        }
+        for (keys, &bits) in self.keys.chunks_mut(8).zip(keys) {
+            unpack_bits(bits, keys);
+        }
+        self.awaited_key = awaited_key;
+        self.clock_accumulator = clock_accumulator;
+        self.tick_accumulator = tick_accumulator;
+        self.invalidate(0, MEMORY_SIZE);
+        Ok(())
+    }
+}
+
+// The first flag becomes the lowest bit
+#[cfg(feature = "std")]
+fn pack_bits(flags: &[bool]) -> u8 {
+    flags.iter().rev().fold(0, |bits, &on| bits << 1 | on as u8)
+}
+
+#[cfg(feature = "std")]
+fn unpack_bits(bits: u8, flags: &mut [bool]) {
+    for (n, flag) in flags.iter_mut().enumerate() {
+        *flag = bits >> n & 1 == 1;
+    }
+}
+
+#[cfg(feature = "std")]
+struct Input<'a>(&'a [u8]);
+
+#[cfg(feature = "std")]
+impl<'a> Input<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.0.len() < len {
+            return Err(StateError::Malformed);
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn word(&mut self) -> Result<u16, StateError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn quad(&mut self) -> Result<u64, StateError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8core::{ DebugVm, Key, Register, RegisterError, StateError, Vm, MEMORY_SIZE };
+    use super::super::Cpu;
+    use super::MAGIC;
+
+    // Draws the digit 5, reads a key into V1 and calls a subroutine
+    const ROM: [u8; 14] = [
+        0x60, 0x05, // LD V0, 5
+        0xF0, 0x29, // LD F, V0
+        0xD0, 0x05, // DRW V0, V0, 5
+        0xF1, 0x0A, // LD V1, K
+        0x22, 0x0C, // CALL 20C
+        0x00, 0x00,
+        0x70, 0x01, // ADD V0, 1
+    ];
+
+    fn busy_cpu() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes(&ROM).unwrap();
+        for _ in 0..4 {
+            cpu.step_instruction().unwrap();
+        }
+        cpu.press_key(Key::D3);
+        cpu.step_instruction().unwrap();
+        cpu.step(0.001).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn write_memory_rejects_data_past_the_end() {
+        let mut cpu = Cpu::new();
+        assert_eq!(cpu.write_memory(0xFFE, &[1, 2, 3]), Err(chip8core::RomError::OutOfBounds));
+        assert!(cpu.write_memory(0xFFE, &[1, 2]).is_ok());
+        assert_eq!(&cpu.memory()[0xFFE..], &[1, 2]);
+    }
+
+    #[test]
+    fn write_memory_drops_cached_instructions() {
+        let mut cpu = Cpu::new();
+        cpu.set_decode_cache(true);
+        cpu.load_rom_bytes(&[0x60, 0x01, 0x12, 0x00]).unwrap();
+        cpu.step_instruction().unwrap();
+        cpu.step_instruction().unwrap();
+
+        cpu.write_memory(0x201, &[0x02]).unwrap();
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.registers().v[0], 2);
+    }
+
+    #[test]
+    fn set_register_checks_the_range() {
+        let mut cpu = Cpu::new();
+        cpu.set_register(Register::V(0xA), 0x42).unwrap();
+        cpu.set_register(Register::Pc, 0x300).unwrap();
+        cpu.set_register(Register::Sp, 16).unwrap();
+        assert_eq!(cpu.registers().v[0xA], 0x42);
+        assert_eq!(cpu.registers().pc, 0x300);
+        assert_eq!(cpu.registers().sp, 16);
+
+        assert_eq!(cpu.set_register(Register::V(0), 0x100),
+                   Err(RegisterError::OutOfRange(Register::V(0), 0x100)));
+        assert_eq!(cpu.set_register(Register::Pc, 0xFFF),
+                   Err(RegisterError::OutOfRange(Register::Pc, 0xFFF)));
+        assert_eq!(cpu.set_register(Register::Sp, 17),
+                   Err(RegisterError::OutOfRange(Register::Sp, 17)));
+        assert_eq!(cpu.set_register(Register::V(16), 0),
+                   Err(RegisterError::NoSuchRegister(Register::V(16))));
+        assert_eq!(cpu.registers().v[0], 0);
+    }
+
+    #[test]
+    fn load_state_restores_saved_state() {
+        let mut cpu = busy_cpu();
+        let state = cpu.save_state();
+        let registers = cpu.registers();
+        let pixels: Vec<bool> = cpu.pixels().flat_map(|row| row.to_vec()).collect();
+
+        let mut other = Cpu::new();
+        other.load_state(&state).unwrap();
+        assert_eq!(other.registers(), registers);
+        assert_eq!(other.memory(), cpu.memory());
+        assert_eq!(other.pixels().flat_map(|row| row.to_vec()).collect::<Vec<_>>(), pixels);
+        assert_eq!(other.keys(), cpu.keys());
+        assert_eq!(other.save_state(), state);
+
+        cpu.step_instruction().unwrap();
+        other.step_instruction().unwrap();
+        assert_eq!(other.save_state(), cpu.save_state());
+    }
+
+    #[test]
+    fn load_state_keeps_cpu_on_bad_input() {
+        let mut cpu = busy_cpu();
+        let before = cpu.save_state();
+
+        let mut truncated = before.clone();
+        truncated.pop();
+        assert_eq!(cpu.load_state(&truncated), Err(StateError::Malformed));
+        assert_eq!(cpu.load_state(b"C8ST\x07"), Err(StateError::UnsupportedVersion(7)));
+        assert_eq!(cpu.load_state(b"nope"), Err(StateError::Malformed));
+        assert_eq!(cpu.save_state(), before);
+    }
+
+    #[test]
+    fn load_state_rejects_addresses_past_memory() {
+        let mut cpu = busy_cpu();
+        let before = cpu.save_state();
+        // I, then pc, follow the magic, the version, memory and V0-VF
+        let i_at = MAGIC.len() + 1 + MEMORY_SIZE + 16;
+        let patched = |offset: usize, value: u16| {
+            let mut state = before.clone();
+            state[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+            state
+        };
+
+        assert_eq!(cpu.load_state(&patched(i_at + 2, 0xFFF)), Err(StateError::Malformed));
+        assert_eq!(cpu.load_state(&patched(i_at, 0x1000)), Err(StateError::Malformed));
+        assert_eq!(cpu.load_state(&patched(i_at + 6, 0xFFF)), Err(StateError::Malformed));
+        assert_eq!(cpu.save_state(), before);
+
+        cpu.load_state(&patched(i_at + 2, 0xFFE)).unwrap();
+        assert_eq!(cpu.registers().pc, 0xFFE);
+    }
+}
@@ -0,0 +1,42 @@
+use std::fmt;
+use instruction::Instruction;
+use opcode::Opcode;
+
+// Formats an opcode as an assembly mnemonic, e.g. `DRW V1, V2, 5`, or as a
+// data word if it doesn't decode
+pub struct Disassembly(pub Opcode);
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Instruction::decode(self.0) {
+            Ok(instruction) => write!(f, "{}", instruction),
+            Err(_) => write!(f, "DW 0x{:04X}", self.0.bits()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disasm(bits: u16) -> String {
+        format!("{}", Disassembly(Opcode::new(bits)))
+    }
+
+    #[test]
+    fn formats_operands() {
+        assert_eq!(disasm(0x00E0), "CLS");
+        assert_eq!(disasm(0x1ABC), "JP 0xABC");
+        assert_eq!(disasm(0x6A25), "LD VA, 0x25");
+        assert_eq!(disasm(0x8AB4), "ADD VA, VB");
+        assert_eq!(disasm(0xD125), "DRW V1, V2, 5");
+        assert_eq!(disasm(0xF355), "LD [I], V3");
+    }
+
+    #[test]
+    fn illegal_opcodes_are_data_words() {
+        assert_eq!(disasm(0x5AB1), "DW 0x5AB1");
+        assert_eq!(disasm(0x8AB8), "DW 0x8AB8");
+        assert_eq!(disasm(0xFA99), "DW 0xFA99");
+    }
+}
@@ -0,0 +1,211 @@
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::io::Read;
+
+pub const SMALL_FONT_SIZE: usize = 16 * 5;
+pub const BIG_FONT_SIZE: usize = 10 * 10;
+
+const STANDARD: [u8; SMALL_FONT_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80  // F
+];
+
+const VIP: [u8; SMALL_FONT_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80  // F
+];
+
+const ETI660: [u8; SMALL_FONT_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80  // F
+];
+
+const DREAM6800: [u8; SMALL_FONT_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80  // F
+];
+
+const FISH_N_CHIPS: [u8; SMALL_FONT_SIZE] = [
+    0x60, 0xA0, 0xA0, 0xA0, 0xC0, // 0
+    0x40, 0xC0, 0x40, 0x40, 0xE0, // 1
+    0xC0, 0x20, 0x40, 0x80, 0xE0, // 2
+    0xC0, 0x20, 0x40, 0x20, 0xC0, // 3
+    0x20, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xC0, 0x20, 0xC0, // 5
+    0x40, 0x80, 0xC0, 0xA0, 0x40, // 6
+    0xE0, 0x20, 0x60, 0x40, 0x40, // 7
+    0x40, 0xA0, 0x40, 0xA0, 0x40, // 8
+    0x40, 0xA0, 0x60, 0x20, 0x40, // 9
+    0x40, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xC0, 0xA0, 0xC0, // B
+    0x60, 0x80, 0x80, 0x80, 0x60, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xC0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80  // F
+];
+
+// 8x10 digits used by the SUPER-CHIP FX30 instruction
+pub const SCHIP_BIG_FONT: [u8; BIG_FONT_SIZE] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C  // 9
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SmallFont {
+    Standard,
+    Vip,
+    Eti660,
+    Dream6800,
+    FishNChips,
+}
+
+impl SmallFont {
+    pub fn data(self) -> &'static [u8; SMALL_FONT_SIZE] {
+        match self {
+            SmallFont::Standard   => &STANDARD,
+            SmallFont::Vip        => &VIP,
+            SmallFont::Eti660     => &ETI660,
+            SmallFont::Dream6800  => &DREAM6800,
+            SmallFont::FishNChips => &FISH_N_CHIPS,
+        }
+    }
+}
+
+// The fonts copied into memory. The big font, if any, follows directly after
+// the small one.
+#[derive(Clone, Copy)]
+pub struct FontSet {
+    pub small: [u8; SMALL_FONT_SIZE],
+    pub big: Option<[u8; BIG_FONT_SIZE]>,
+    pub base_address: u16,
+}
+
+impl FontSet {
+    pub fn new(small: SmallFont) -> FontSet {
+        FontSet {
+            small: *small.data(),
+            big: None,
+            base_address: 0,
+        }
+    }
+
+    pub fn with_big_font(mut self) -> FontSet {
+        self.big = Some(SCHIP_BIG_FONT);
+        self
+    }
+
+    pub fn at(mut self, base_address: u16) -> FontSet {
+        self.base_address = base_address;
+        self
+    }
+
+    // Reads a custom font, either 80 bytes of small font or 80 bytes of
+    // small font followed by 100 bytes of big font
+    #[cfg(feature = "std")]
+    pub fn from_reader<T: Read>(reader: &mut T) -> io::Result<FontSet> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        let mut font = FontSet::new(SmallFont::Standard);
+        match data.len() {
+            SMALL_FONT_SIZE => (),
+            n if n == SMALL_FONT_SIZE + BIG_FONT_SIZE => {
+                let mut big = [0; BIG_FONT_SIZE];
+                big.copy_from_slice(&data[SMALL_FONT_SIZE..]);
+                font.big = Some(big);
+            },
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                           "Font must be 80 or 180 bytes")),
+        }
+        font.small.copy_from_slice(&data[..SMALL_FONT_SIZE]);
+        Ok(font)
+    }
+
+    pub fn size(&self) -> usize {
+        SMALL_FONT_SIZE + if self.big.is_some() { BIG_FONT_SIZE } else { 0 }
+    }
+
+    pub fn small_glyph_address(&self, digit: u8) -> u16 {
+        self.base_address + (digit & 0xF) as u16 * 5
+    }
+
+    // The big font only has the digits 0 to 9
+    pub fn big_glyph_address(&self, digit: u8) -> Option<u16> {
+        let digit = digit & 0xF;
+        if digit > 9 {
+            return None;
+        }
+        self.big.map(|_| self.base_address + SMALL_FONT_SIZE as u16 + digit as u16 * 10)
+    }
+}
+
+impl Default for FontSet {
+    fn default() -> FontSet {
+        FontSet::new(SmallFont::Standard)
+    }
+}
@@ -0,0 +1,273 @@
+#[cfg(feature = "std")]
+use std::error::Error;
+use std::fmt;
+use chip8core::InstructionError;
+use opcode::Opcode;
+
+// A decoded instruction, named after the Cpu handler that executes it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Sys { addr: u16 },
+    Clear,
+    Return,
+    Jump { addr: u16 },
+    Call { addr: u16 },
+    SkipEqByte { x: u8, byte: u8 },
+    SkipNeqByte { x: u8, byte: u8 },
+    SkipEq { x: u8, y: u8 },
+    SetByte { x: u8, byte: u8 },
+    AddByte { x: u8, byte: u8 },
+    Set { x: u8, y: u8 },
+    Or { x: u8, y: u8 },
+    And { x: u8, y: u8 },
+    Xor { x: u8, y: u8 },
+    Add { x: u8, y: u8 },
+    Sub { x: u8, y: u8 },
+    // Y only matters with the shift quirk
+    ShiftRight { x: u8, y: u8 },
+    Subn { x: u8, y: u8 },
+    ShiftLeft { x: u8, y: u8 },
+    SkipNeq { x: u8, y: u8 },
+    SetI { addr: u16 },
+    JumpV0 { addr: u16 },
+    Rand { x: u8, byte: u8 },
+    Draw { x: u8, y: u8, n: u8 },
+    SkipPressed { x: u8 },
+    SkipNotPressed { x: u8 },
+    GetDelayTimer { x: u8 },
+    AwaitKeyPress { x: u8 },
+    SetDelayTimer { x: u8 },
+    SetSoundTimer { x: u8 },
+    AddI { x: u8 },
+    SetChar { x: u8 },
+    SetBigChar { x: u8 },
+    StoreBcd { x: u8 },
+    StoreRegs { x: u8 },
+    LoadRegs { x: u8 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeError(pub Opcode);
+
+impl Instruction {
+    pub fn decode(opcode: Opcode) -> Result<Instruction, DecodeError> {
+        use self::Instruction::*;
+
+        let x = opcode.x();
+        let y = opcode.y();
+        let addr = opcode.addr();
+        let byte = opcode.byte();
+        let n = opcode.nibble();
+
+        let instruction = match opcode.bits() & 0xF000 {
+            0x0000 => match addr {
+                0x0E0 => Clear,
+                0x0EE => Return,
+                _     => Sys { addr },
+            },
+            0x1000 => Jump { addr },
+            0x2000 => Call { addr },
+            0x3000 => SkipEqByte { x, byte },
+            0x4000 => SkipNeqByte { x, byte },
+            0x5000 if n == 0 => SkipEq { x, y },
+            0x6000 => SetByte { x, byte },
+            0x7000 => AddByte { x, byte },
+            0x8000 => match n {
+                0x0 => Set { x, y },
+                0x1 => Or { x, y },
+                0x2 => And { x, y },
+                0x3 => Xor { x, y },
+                0x4 => Add { x, y },
+                0x5 => Sub { x, y },
+                0x6 => ShiftRight { x, y },
+                0x7 => Subn { x, y },
+                0xE => ShiftLeft { x, y },
+                _   => return Err(DecodeError(opcode)),
+            },
+            0x9000 if n == 0 => SkipNeq { x, y },
+            0xA000 => SetI { addr },
+            0xB000 => JumpV0 { addr },
+            0xC000 => Rand { x, byte },
+            0xD000 => Draw { x, y, n },
+            0xE000 => match byte {
+                0x9E => SkipPressed { x },
+                0xA1 => SkipNotPressed { x },
+                _    => return Err(DecodeError(opcode)),
+            },
+            0xF000 => match byte {
+                0x07 => GetDelayTimer { x },
+                0x0A => AwaitKeyPress { x },
+                0x15 => SetDelayTimer { x },
+                0x18 => SetSoundTimer { x },
+                0x1E => AddI { x },
+                0x29 => SetChar { x },
+                0x30 => SetBigChar { x },
+                0x33 => StoreBcd { x },
+                0x55 => StoreRegs { x },
+                0x65 => LoadRegs { x },
+                _    => return Err(DecodeError(opcode)),
+            },
+            _ => return Err(DecodeError(opcode)),
+        };
+        Ok(instruction)
+    }
+
+    pub fn encode(&self) -> Opcode {
+        use self::Instruction::*;
+
+        let addr = |high: u16, addr: u16| high | (addr & 0x0FFF);
+        let xbyte = |high: u16, x: u8, byte: u8| high | (x as u16 & 0xF) << 8 | byte as u16;
+        let xy = |high: u16, x: u8, y: u8, n: u8| {
+            high | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | (n as u16 & 0xF)
+        };
+
+        let bits = match *self {
+            Sys { addr: a }            => addr(0x0000, a),
+            Clear                      => 0x00E0,
+            Return                     => 0x00EE,
+            Jump { addr: a }           => addr(0x1000, a),
+            Call { addr: a }           => addr(0x2000, a),
+            SkipEqByte { x, byte }     => xbyte(0x3000, x, byte),
+            SkipNeqByte { x, byte }    => xbyte(0x4000, x, byte),
+            SkipEq { x, y }            => xy(0x5000, x, y, 0x0),
+            SetByte { x, byte }        => xbyte(0x6000, x, byte),
+            AddByte { x, byte }        => xbyte(0x7000, x, byte),
+            Set { x, y }               => xy(0x8000, x, y, 0x0),
+            Or { x, y }                => xy(0x8000, x, y, 0x1),
+            And { x, y }               => xy(0x8000, x, y, 0x2),
+            Xor { x, y }               => xy(0x8000, x, y, 0x3),
+            Add { x, y }               => xy(0x8000, x, y, 0x4),
+            Sub { x, y }               => xy(0x8000, x, y, 0x5),
+            ShiftRight { x, y }        => xy(0x8000, x, y, 0x6),
+            Subn { x, y }              => xy(0x8000, x, y, 0x7),
+            ShiftLeft { x, y }         => xy(0x8000, x, y, 0xE),
+            SkipNeq { x, y }           => xy(0x9000, x, y, 0x0),
+            SetI { addr: a }           => addr(0xA000, a),
+            JumpV0 { addr: a }         => addr(0xB000, a),
+            Rand { x, byte }           => xbyte(0xC000, x, byte),
+            Draw { x, y, n }           => xy(0xD000, x, y, n),
+            SkipPressed { x }          => xbyte(0xE000, x, 0x9E),
+            SkipNotPressed { x }       => xbyte(0xE000, x, 0xA1),
+            GetDelayTimer { x }        => xbyte(0xF000, x, 0x07),
+            AwaitKeyPress { x }        => xbyte(0xF000, x, 0x0A),
+            SetDelayTimer { x }        => xbyte(0xF000, x, 0x15),
+            SetSoundTimer { x }        => xbyte(0xF000, x, 0x18),
+            AddI { x }                 => xbyte(0xF000, x, 0x1E),
+            SetChar { x }              => xbyte(0xF000, x, 0x29),
+            SetBigChar { x }           => xbyte(0xF000, x, 0x30),
+            StoreBcd { x }             => xbyte(0xF000, x, 0x33),
+            StoreRegs { x }            => xbyte(0xF000, x, 0x55),
+            LoadRegs { x }             => xbyte(0xF000, x, 0x65),
+        };
+        Opcode::new(bits)
+    }
+}
+
+// Assembly mnemonics in Cowgod's syntax, e.g. `DRW V1, V2, 5`
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Instruction::*;
+
+        match *self {
+            Sys { addr }            => write!(f, "SYS 0x{:03X}", addr),
+            Clear                   => write!(f, "CLS"),
+            Return                  => write!(f, "RET"),
+            Jump { addr }           => write!(f, "JP 0x{:03X}", addr),
+            Call { addr }           => write!(f, "CALL 0x{:03X}", addr),
+            SkipEqByte { x, byte }  => write!(f, "SE V{:X}, 0x{:02X}", x, byte),
+            SkipNeqByte { x, byte } => write!(f, "SNE V{:X}, 0x{:02X}", x, byte),
+            SkipEq { x, y }         => write!(f, "SE V{:X}, V{:X}", x, y),
+            SetByte { x, byte }     => write!(f, "LD V{:X}, 0x{:02X}", x, byte),
+            AddByte { x, byte }     => write!(f, "ADD V{:X}, 0x{:02X}", x, byte),
+            Set { x, y }            => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or { x, y }             => write!(f, "OR V{:X}, V{:X}", x, y),
+            And { x, y }            => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor { x, y }            => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Add { x, y }            => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub { x, y }            => write!(f, "SUB V{:X}, V{:X}", x, y),
+            ShiftRight { x, .. }    => write!(f, "SHR V{:X}", x),
+            Subn { x, y }           => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            ShiftLeft { x, .. }     => write!(f, "SHL V{:X}", x),
+            SkipNeq { x, y }        => write!(f, "SNE V{:X}, V{:X}", x, y),
+            SetI { addr }           => write!(f, "LD I, 0x{:03X}", addr),
+            JumpV0 { addr }         => write!(f, "JP V0, 0x{:03X}", addr),
+            Rand { x, byte }        => write!(f, "RND V{:X}, 0x{:02X}", x, byte),
+            Draw { x, y, n }        => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            SkipPressed { x }       => write!(f, "SKP V{:X}", x),
+            SkipNotPressed { x }    => write!(f, "SKNP V{:X}", x),
+            GetDelayTimer { x }     => write!(f, "LD V{:X}, DT", x),
+            AwaitKeyPress { x }     => write!(f, "LD V{:X}, K", x),
+            SetDelayTimer { x }     => write!(f, "LD DT, V{:X}", x),
+            SetSoundTimer { x }     => write!(f, "LD ST, V{:X}", x),
+            AddI { x }              => write!(f, "ADD I, V{:X}", x),
+            SetChar { x }           => write!(f, "LD F, V{:X}", x),
+            SetBigChar { x }        => write!(f, "LD HF, V{:X}", x),
+            StoreBcd { x }          => write!(f, "LD B, V{:X}", x),
+            StoreRegs { x }         => write!(f, "LD [I], V{:X}", x),
+            LoadRegs { x }          => write!(f, "LD V{:X}, [I]", x),
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Illegal opcode 0x{:04X}", self.0.bits())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for DecodeError {
+    fn description(&self) -> &str {
+        "Illegal opcode"
+    }
+}
+
+impl From<DecodeError> for InstructionError {
+    fn from(e: DecodeError) -> InstructionError {
+        InstructionError::Illegal(e.0.bits())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_encode_round_trips_all_opcodes() {
+        for bits in 0..=0xFFFFu16 {
+            if let Ok(instruction) = Instruction::decode(Opcode::new(bits)) {
+                assert_eq!(instruction.encode().bits(), bits, "{:?}", instruction);
+                assert_eq!(Instruction::decode(instruction.encode()), Ok(instruction));
+            }
+        }
+    }
+
+    #[test]
+    fn decode_rejects_exactly_the_illegal_opcodes() {
+        let illegal = (0..=0xFFFFu16)
+            .filter(|&bits| Instruction::decode(Opcode::new(bits)).is_err())
+            .count();
+
+        // 5XY1-F and 9XY1-F, 8XY8-D and 8XYF, and the unused EX and FX bytes
+        let expected = 2 * 15 * 256 + 7 * 256 + 254 * 16 + 246 * 16;
+        assert_eq!(illegal, expected);
+    }
+
+    #[test]
+    fn decode_extracts_operands() {
+        assert_eq!(Instruction::decode(Opcode::new(0xD125)),
+                   Ok(Instruction::Draw { x: 0x1, y: 0x2, n: 0x5 }));
+        assert_eq!(Instruction::decode(Opcode::new(0x8AB6)),
+                   Ok(Instruction::ShiftRight { x: 0xA, y: 0xB }));
+        assert_eq!(Instruction::decode(Opcode::new(0x0123)),
+                   Ok(Instruction::Sys { addr: 0x123 }));
+        assert_eq!(Instruction::decode(Opcode::new(0xF3A1)),
+                   Err(DecodeError(Opcode::new(0xF3A1))));
+    }
+
+    #[test]
+    fn encode_masks_out_of_range_operands() {
+        assert_eq!(Instruction::Jump { addr: 0xFABC }.encode().bits(), 0x1ABC);
+        assert_eq!(Instruction::Draw { x: 0x11, y: 0x12, n: 0x15 }.encode().bits(), 0xD125);
+    }
+}
@@ -0,0 +1,758 @@
+// A Vm that translates basic blocks of CHIP-8 code into native code with
+// Cranelift. Register and timer instructions are compiled inline, the rest
+// call back into Cpu's handlers. Blocks keep a copy of the bytes they were
+// compiled from and are recompiled when memory changes under them, code
+// that keeps changing is left to the interpreter.
+use std::any::Any;
+use std::cell::RefCell;
+use std::mem;
+use std::ops::Deref;
+use std::panic::{ self, AssertUnwindSafe };
+use std::slice::Chunks;
+use chip8core::{ DebugVm, Vm, InstructionError, Key, Register, RegisterError, RegisterFile,
+                 RomError, StateError, MEMORY_SIZE };
+use cranelift_codegen::ir::{ types, AbiParam, FuncRef, InstBuilder, MemFlags, Value };
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_frontend::{ FunctionBuilder, FunctionBuilderContext, Variable };
+use cranelift_jit::{ JITBuilder, JITModule };
+use cranelift_module::{ default_libcall_names, FuncId, Linkage, Module };
+use super::{ get_opcode, Cpu, DefaultRandom, FontSet, Instruction, LoadOptions, Opcode, Quirks,
+             Random, CLOCK_PERIOD, F, TICK_PERIOD };
+
+const HELPER: &str = "chip8_execute";
+const MAX_BLOCK_LEN: usize = 64;
+// Times a block may be recompiled before its address is only interpreted
+const MAX_REWRITES: u8 = 4;
+// Returned by a block when a handler it called failed
+const FAULTED: u32 = u32::MAX;
+
+// Takes the Cpu and the number of instructions it may run, returns the
+// number it ran
+type BlockFn = unsafe extern "C" fn(*mut u8, u32) -> u32;
+
+enum Fault {
+    Error(InstructionError),
+    Panic(Box<dyn Any + Send>),
+}
+
+thread_local! {
+    static FAULT: RefCell<Option<Fault>> = const { RefCell::new(None) };
+}
+
+// Runs one instruction through Cpu's handlers on behalf of compiled code
+extern "C" fn execute<R: Random>(cpu: *mut u8, bits: u32) -> u32 {
+    let cpu = unsafe { &mut *(cpu as *mut Cpu<R>) };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        match Instruction::decode(Opcode::new(bits as u16)) {
+            Ok(instruction) => cpu.execute(instruction),
+            Err(err) => Err(err.into()),
+        }
+    }));
+
+    let fault = match result {
+        Ok(Ok(())) => return 0,
+        Ok(Err(err)) => Fault::Error(err),
+        Err(payload) => Fault::Panic(payload),
+    };
+    FAULT.with(|f| *f.borrow_mut() = Some(fault));
+    1
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    // Compiled inline
+    Inline,
+    // Calls the handler and carries on
+    Helper,
+    // Compiled inline, ends the block
+    Branch,
+    // Calls the handler and ends the block, for control flow and memory
+    // writes that might change the code that follows
+    Exit,
+    // Left to the interpreter
+    Interpret,
+}
+
+fn kind(instruction: Instruction) -> Kind {
+    use super::Instruction::*;
+
+    match instruction {
+        SetByte { .. } | AddByte { .. } | Set { .. } | Or { .. } | And { .. } | Xor { .. } |
+        Add { .. } | Sub { .. } | ShiftRight { .. } | Subn { .. } | ShiftLeft { .. } |
+        SetI { .. } | AddI { .. } | GetDelayTimer { .. } | SetDelayTimer { .. } |
+        SetSoundTimer { .. } => Kind::Inline,
+        Clear | Draw { .. } | Rand { .. } | SetChar { .. } | LoadRegs { .. } => Kind::Helper,
+        Jump { .. } | JumpV0 { .. } | SkipEqByte { .. } | SkipNeqByte { .. } | SkipEq { .. } |
+        SkipNeq { .. } => Kind::Branch,
+        Call { .. } | Return | SkipPressed { .. } | SkipNotPressed { .. } | StoreBcd { .. } |
+        StoreRegs { .. } => Kind::Exit,
+        Sys { .. } | SetBigChar { .. } | AwaitKeyPress { .. } => Kind::Interpret,
+    }
+}
+
+struct Block {
+    func: BlockFn,
+    source: Vec<u8>,
+}
+
+// Where compiled code finds the Cpu's registers
+#[derive(Clone, Copy)]
+struct Offsets {
+    v: i32,
+    i: i32,
+    pc: i32,
+    delay_timer: i32,
+    sound_timer: i32,
+}
+
+// Owns the memory the blocks live in
+struct Compiler {
+    module: Option<JITModule>,
+    helper: FuncId,
+    builder_context: FunctionBuilderContext,
+}
+
+impl Drop for Compiler {
+    fn drop(&mut self) {
+        if let Some(module) = self.module.take() {
+            unsafe { module.free_memory() };
+        }
+    }
+}
+
+pub struct JitCpu<R: Random = DefaultRandom> {
+    cpu: Cpu<R>,
+    compiler: Compiler,
+    blocks: Vec<Option<Block>>,
+    rewrites: Vec<u8>,
+    compiled: usize,
+}
+
+impl JitCpu {
+    pub fn new() -> JitCpu {
+        JitCpu::from_cpu(Cpu::new())
+    }
+}
+
+impl Default for JitCpu {
+    fn default() -> JitCpu {
+        JitCpu::new()
+    }
+}
+
+impl<R: Random> JitCpu<R> {
+    pub fn with_random(rng: R) -> JitCpu<R> {
+        JitCpu::from_cpu(Cpu::with_random(rng))
+    }
+
+    pub fn from_cpu(cpu: Cpu<R>) -> JitCpu<R> {
+        let mut builder = JITBuilder::with_flags(&[("opt_level", "speed")], default_libcall_names())
+            .expect("Cranelift doesn't support this host");
+        builder.symbol(HELPER, execute::<R> as *const u8);
+        let mut module = JITModule::new(builder);
+
+        let mut signature = module.make_signature();
+        signature.params.push(AbiParam::new(module.target_config().pointer_type()));
+        signature.params.push(AbiParam::new(types::I32));
+        signature.returns.push(AbiParam::new(types::I32));
+        let helper = module.declare_function(HELPER, Linkage::Import, &signature)
+            .expect("Failed to declare JIT helper");
+
+        let mut blocks = Vec::with_capacity(MEMORY_SIZE);
+        blocks.resize_with(MEMORY_SIZE, || None);
+
+        JitCpu {
+            cpu,
+            compiler: Compiler {
+                module: Some(module),
+                helper,
+                builder_context: FunctionBuilderContext::new(),
+            },
+            blocks,
+            rewrites: vec![0; MEMORY_SIZE],
+            compiled: 0,
+        }
+    }
+
+    pub fn into_cpu(self) -> Cpu<R> {
+        self.cpu
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.cpu.set_quirks(quirks);
+        self.flush();
+    }
+
+    pub fn load_font(&mut self, font: FontSet) -> Result<(), RomError> {
+        self.flush();
+        self.cpu.load_font(font)
+    }
+
+    pub fn load_rom_bytes_with(&mut self, rom: &[u8], options: &LoadOptions)
+                               -> Result<(), RomError> {
+        self.flush();
+        self.cpu.load_rom_bytes_with(rom, options)
+    }
+
+    // Executes a single instruction regardless of the clock, does nothing
+    // while waiting for a key press
+    pub fn step_instruction(&mut self) -> Result<(), InstructionError> {
+        self.run(1)
+    }
+
+    // Whether a compiled block currently starts at `addr`
+    pub fn is_compiled(&self, addr: u16) -> bool {
+        self.blocks.get(addr as usize).is_some_and(|b| b.is_some())
+    }
+
+    // Blocks compiled so far. Their code is only freed with the JitCpu, so
+    // this is what its memory grows with.
+    pub fn compiled_blocks(&self) -> usize {
+        self.compiled
+    }
+
+    // Drops the blocks compiled from any of the bytes, counted like a
+    // rewrite so memory that keeps changing is left to the interpreter
+    fn invalidate(&mut self, start: usize, len: usize) {
+        let end = start + len;
+        for addr in start.saturating_sub(MAX_BLOCK_LEN * 2 - 1)..end.min(MEMORY_SIZE) {
+            let overlaps = self.blocks[addr].as_ref()
+                .is_some_and(|block| addr + block.source.len() > start);
+            if overlaps {
+                self.blocks[addr] = None;
+                self.rewrites[addr] = self.rewrites[addr].saturating_add(1);
+            }
+        }
+    }
+
+    // Drops every block and forgets which addresses were rewritten, for
+    // when memory or the quirks change from outside the program
+    fn flush(&mut self) {
+        for block in self.blocks.iter_mut() {
+            *block = None;
+        }
+        for count in self.rewrites.iter_mut() {
+            *count = 0;
+        }
+    }
+
+    // Runs up to `budget` instructions
+    fn run(&mut self, mut budget: u32) -> Result<(), InstructionError> {
+        while budget > 0 {
+            if self.cpu.awaited_key.is_some() {
+                return Ok(());
+            }
+
+            match self.block(self.cpu.pc as usize) {
+                Some(func) => {
+                    let cpu = &mut self.cpu as *mut Cpu<R> as *mut u8;
+                    let ran = unsafe { func(cpu, budget) };
+                    if ran == FAULTED {
+                        return match FAULT.with(|f| f.borrow_mut().take()) {
+                            Some(Fault::Error(err)) => Err(err),
+                            Some(Fault::Panic(payload)) => panic::resume_unwind(payload),
+                            None => unreachable!(),
+                        };
+                    }
+                    budget -= ran;
+                },
+                None => {
+                    self.cpu.cycle()?;
+                    budget -= 1;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, pc: usize) -> Option<BlockFn> {
+        if pc + 1 >= MEMORY_SIZE || self.rewrites[pc] >= MAX_REWRITES {
+            return None;
+        }
+
+        if let Some(ref block) = self.blocks[pc] {
+            if self.cpu.mem[pc..].starts_with(&block.source) {
+                return Some(block.func);
+            }
+        }
+        if self.blocks[pc].take().is_some() {
+            self.rewrites[pc] += 1;
+            if self.rewrites[pc] >= MAX_REWRITES {
+                return None;
+            }
+        }
+
+        let block = self.compile(pc)?;
+        self.compiled += 1;
+        let func = block.func;
+        self.blocks[pc] = Some(block);
+        Some(func)
+    }
+
+    fn scan(&self, start: usize) -> Vec<(u16, Instruction)> {
+        let mut code = Vec::new();
+        let mut addr = start;
+
+        while addr + 1 < MEMORY_SIZE && code.len() < MAX_BLOCK_LEN {
+            let instruction = match Instruction::decode(get_opcode(&self.cpu.mem, addr as u16)) {
+                Ok(instruction) => instruction,
+                Err(_) => break,
+            };
+            match kind(instruction) {
+                Kind::Interpret => break,
+                Kind::Branch | Kind::Exit => {
+                    code.push((addr as u16, instruction));
+                    break;
+                },
+                Kind::Inline | Kind::Helper => code.push((addr as u16, instruction)),
+            }
+            addr += 2;
+        }
+        code
+    }
+
+    fn compile(&mut self, start: usize) -> Option<Block> {
+        let code = self.scan(start);
+        if code.is_empty() {
+            return None;
+        }
+
+        let offsets = Offsets {
+            v: mem::offset_of!(Cpu<R>, v) as i32,
+            i: mem::offset_of!(Cpu<R>, i) as i32,
+            pc: mem::offset_of!(Cpu<R>, pc) as i32,
+            delay_timer: mem::offset_of!(Cpu<R>, delay_timer) as i32,
+            sound_timer: mem::offset_of!(Cpu<R>, sound_timer) as i32,
+        };
+        let quirks = self.cpu.quirks;
+        let compiler = &mut self.compiler;
+        let module = compiler.module.as_mut().unwrap();
+
+        let mut context = module.make_context();
+        let pointer = module.target_config().pointer_type();
+        context.func.signature.params.push(AbiParam::new(pointer));
+        context.func.signature.params.push(AbiParam::new(types::I32));
+        context.func.signature.returns.push(AbiParam::new(types::I32));
+        let helper = module.declare_func_in_func(compiler.helper, &mut context.func);
+
+        {
+            let builder = FunctionBuilder::new(&mut context.func, &mut compiler.builder_context);
+            Translator::new(builder, helper, offsets, quirks).translate(&code);
+        }
+
+        let id = module.declare_anonymous_function(&context.func.signature).ok()?;
+        module.define_function(id, &mut context).ok()?;
+        module.clear_context(&mut context);
+        module.finalize_definitions().ok()?;
+
+        let func = unsafe { mem::transmute::<*const u8, BlockFn>(module.get_finalized_function(id)) };
+        let end = start + code.len() * 2;
+        Some(Block { func, source: self.cpu.mem[start..end].to_vec() })
+    }
+}
+
+// Read access to the registers and memory, writes go through JitCpu so the
+// compiled blocks stay valid
+impl<R: Random> Deref for JitCpu<R> {
+    type Target = Cpu<R>;
+
+    fn deref(&self) -> &Cpu<R> {
+        &self.cpu
+    }
+}
+
+impl<R: Random> Vm for JitCpu<R> {
+    // Same clock as Cpu::step, but runs all instructions between two timer
+    // ticks in one go
+    fn step(&mut self, time: f64) -> Result<(), InstructionError> {
+        self.cpu.clock_accumulator += time;
+
+        let mut budget = 0;
+        while self.cpu.clock_accumulator > CLOCK_PERIOD {
+            self.cpu.clock_accumulator -= CLOCK_PERIOD;
+
+            self.cpu.tick_accumulator += CLOCK_PERIOD;
+            if self.cpu.tick_accumulator > TICK_PERIOD {
+                self.run(budget)?;
+                budget = 0;
+                self.cpu.tick_accumulator -= TICK_PERIOD;
+                self.cpu.tick_timers();
+            }
+            budget += 1;
+        }
+        self.run(budget)
+    }
+
+    fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        self.flush();
+        self.cpu.load_rom_bytes(rom)
+    }
+
+    fn reset(&mut self, keep_memory: bool) {
+        self.flush();
+        self.cpu.reset(keep_memory);
+    }
+
+    fn reload_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        self.flush();
+        self.cpu.reload_rom_bytes(rom)
+    }
+
+    fn pixels<'a>(&'a self) -> Chunks<'a, bool> {
+        self.cpu.pixels()
+    }
+
+    fn press_key(&mut self, key: Key) {
+        self.cpu.press_key(key);
+    }
+
+    fn release_key(&mut self, key: Key) {
+        self.cpu.release_key(key);
+    }
+
+    fn debug(&mut self) -> Option<&mut dyn DebugVm> {
+        Some(self)
+    }
+}
+
+// Loading a state counts as a fresh start, writes to memory only drop the
+// blocks they change
+impl<R: Random> DebugVm for JitCpu<R> {
+    fn registers(&self) -> RegisterFile {
+        self.cpu.registers()
+    }
+
+    fn set_register(&mut self, register: Register, value: u16) -> Result<(), RegisterError> {
+        self.cpu.set_register(register, value)
+    }
+
+    fn memory(&self) -> &[u8] {
+        self.cpu.memory()
+    }
+
+    fn write_memory(&mut self, address: usize, data: &[u8]) -> Result<(), RomError> {
+        let unchanged = self.cpu.memory().get(address..).is_some_and(|mem| mem.starts_with(data));
+        self.cpu.write_memory(address, data)?;
+        if !unchanged {
+            self.invalidate(address, data.len());
+        }
+        Ok(())
+    }
+
+    fn keys(&self) -> &[bool; 16] {
+        self.cpu.keys()
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.cpu.quirks()
+    }
+
+    fn set_quirks(&mut self, quirks: Quirks) {
+        JitCpu::set_quirks(self, quirks);
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        self.cpu.save_state()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        self.flush();
+        self.cpu.load_state(state)
+    }
+}
+
+// V0-VF are variables 0-15, I is 16
+const I: usize = 16;
+
+fn var(n: usize) -> Variable {
+    Variable::from_u32(n as u32)
+}
+
+struct Translator<'a> {
+    b: FunctionBuilder<'a>,
+    helper: FuncRef,
+    offsets: Offsets,
+    quirks: Quirks,
+    cpu: Value,
+    budget: Value,
+    // V0-VF and I, stored back to the Cpu when leaving the block
+    written: [bool; 17],
+}
+
+impl<'a> Translator<'a> {
+    fn new(mut b: FunctionBuilder<'a>, helper: FuncRef, offsets: Offsets, quirks: Quirks)
+           -> Translator<'a> {
+        let entry = b.create_block();
+        b.append_block_params_for_function_params(entry);
+        b.switch_to_block(entry);
+        b.seal_block(entry);
+        let cpu = b.block_params(entry)[0];
+        let budget = b.block_params(entry)[1];
+
+        for reg in 0..16 {
+            b.declare_var(var(reg), types::I8);
+        }
+        b.declare_var(var(I), types::I16);
+
+        let mut translator = Translator {
+            b,
+            helper,
+            offsets,
+            quirks,
+            cpu,
+            budget,
+            written: [false; 17],
+        };
+        translator.load_registers();
+        translator
+    }
+
+    fn translate(mut self, code: &[(u16, Instruction)]) {
+        for (n, &(addr, instruction)) in code.iter().enumerate() {
+            if n > 0 {
+                self.exit_if_out_of_budget(n, addr);
+            }
+
+            match kind(instruction) {
+                Kind::Inline => self.inline(instruction),
+                Kind::Helper => {
+                    self.call_handler(addr, instruction);
+                    self.load_registers();
+                },
+                Kind::Branch => {
+                    let pc = self.branch(addr, instruction);
+                    self.leave(pc, n + 1);
+                },
+                Kind::Exit => {
+                    self.call_handler(addr, instruction);
+                    let ran = self.b.ins().iconst(types::I32, n as i64 + 1);
+                    self.b.ins().return_(&[ran]);
+                },
+                Kind::Interpret => unreachable!(),
+            }
+        }
+
+        let &(last, instruction) = code.last().unwrap();
+        if kind(instruction) == Kind::Inline || kind(instruction) == Kind::Helper {
+            let pc = self.b.ins().iconst(types::I16, last as i64 + 2);
+            self.leave(pc, code.len());
+        }
+        self.b.finalize();
+    }
+
+    fn v(&mut self, reg: u8) -> Value {
+        self.b.use_var(var(reg as usize))
+    }
+
+    fn set_v(&mut self, reg: u8, value: Value) {
+        self.b.def_var(var(reg as usize), value);
+        self.written[reg as usize] = true;
+    }
+
+    fn load_registers(&mut self) {
+        let flags = MemFlags::trusted();
+        for reg in 0..16 {
+            let value = self.b.ins().load(types::I8, flags, self.cpu, self.offsets.v + reg as i32);
+            self.b.def_var(var(reg), value);
+        }
+        let i = self.b.ins().load(types::I16, flags, self.cpu, self.offsets.i);
+        self.b.def_var(var(I), i);
+    }
+
+    fn store_registers(&mut self) {
+        let flags = MemFlags::trusted();
+        for reg in 0..16 {
+            if self.written[reg] {
+                let value = self.b.use_var(var(reg));
+                self.b.ins().store(flags, value, self.cpu, self.offsets.v + reg as i32);
+            }
+        }
+        if self.written[I] {
+            let i = self.b.use_var(var(I));
+            self.b.ins().store(flags, i, self.cpu, self.offsets.i);
+        }
+    }
+
+    fn store_pc(&mut self, pc: Value) {
+        self.b.ins().store(MemFlags::trusted(), pc, self.cpu, self.offsets.pc);
+    }
+
+    // Stores the registers and pc, and returns how many instructions ran
+    fn leave(&mut self, pc: Value, ran: usize) {
+        self.store_registers();
+        self.store_pc(pc);
+        let ran = self.b.ins().iconst(types::I32, ran as i64);
+        self.b.ins().return_(&[ran]);
+    }
+
+    fn exit_if_out_of_budget(&mut self, ran: usize, addr: u16) {
+        let exit = self.b.create_block();
+        let next = self.b.create_block();
+        let done = self.b.ins().icmp_imm(IntCC::UnsignedLessThanOrEqual, self.budget, ran as i64);
+        self.b.ins().brif(done, exit, &[], next, &[]);
+
+        self.b.switch_to_block(exit);
+        self.b.seal_block(exit);
+        let pc = self.b.ins().iconst(types::I16, addr as i64);
+        self.leave(pc, ran);
+
+        self.b.switch_to_block(next);
+        self.b.seal_block(next);
+    }
+
+    // Hands the instruction to Cpu with its registers up to date and pc
+    // pointing past the instruction, as if the interpreter had fetched it
+    fn call_handler(&mut self, addr: u16, instruction: Instruction) {
+        self.store_registers();
+        let pc = self.b.ins().iconst(types::I16, addr as i64 + 2);
+        self.store_pc(pc);
+
+        let bits = self.b.ins().iconst(types::I32, instruction.encode().bits() as i64);
+        let call = self.b.ins().call(self.helper, &[self.cpu, bits]);
+        let failed = self.b.inst_results(call)[0];
+
+        let fault = self.b.create_block();
+        let next = self.b.create_block();
+        self.b.ins().brif(failed, fault, &[], next, &[]);
+
+        self.b.switch_to_block(fault);
+        self.b.seal_block(fault);
+        let faulted = self.b.ins().iconst(types::I32, FAULTED as i64);
+        self.b.ins().return_(&[faulted]);
+
+        self.b.switch_to_block(next);
+        self.b.seal_block(next);
+    }
+
+    fn inline(&mut self, instruction: Instruction) {
+        use super::Instruction::*;
+
+        let flags = MemFlags::trusted();
+        let f = F as u8;
+
+        match instruction {
+            SetByte { x, byte } => {
+                let value = self.b.ins().iconst(types::I8, byte as i64);
+                self.set_v(x, value);
+            },
+            AddByte { x, byte } => {
+                let vx = self.v(x);
+                let sum = self.b.ins().iadd_imm(vx, byte as i64);
+                self.set_v(x, sum);
+            },
+            Set { x, y } => {
+                let vy = self.v(y);
+                self.set_v(x, vy);
+            },
+            Or { x, y } | And { x, y } | Xor { x, y } => {
+                let vx = self.v(x);
+                let vy = self.v(y);
+                let result = match instruction {
+                    Or { .. } => self.b.ins().bor(vx, vy),
+                    And { .. } => self.b.ins().band(vx, vy),
+                    _ => self.b.ins().bxor(vx, vy),
+                };
+                self.set_v(x, result);
+                if self.quirks.logic_resets_vf {
+                    let zero = self.b.ins().iconst(types::I8, 0);
+                    self.set_v(f, zero);
+                }
+            },
+            Add { x, y } => {
+                let vx = self.v(x);
+                let vy = self.v(y);
+                let sum = self.b.ins().iadd(vx, vy);
+                let carry = self.b.ins().icmp(IntCC::UnsignedLessThan, sum, vx);
+                self.set_v(x, sum);
+                self.set_v(f, carry);
+            },
+            Sub { x, y } | Subn { x, y } => {
+                let (a, b) = match instruction {
+                    Sub { .. } => (self.v(x), self.v(y)),
+                    _ => (self.v(y), self.v(x)),
+                };
+                let difference = self.b.ins().isub(a, b);
+                let no_borrow = self.b.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, a, b);
+                self.set_v(x, difference);
+                self.set_v(f, no_borrow);
+            },
+            ShiftRight { x, y } | ShiftLeft { x, y } => {
+                let src = self.v(if self.quirks.shift_uses_vy { y } else { x });
+                let (result, flag) = match instruction {
+                    ShiftRight { .. } => (self.b.ins().ushr_imm(src, 1), self.b.ins().band_imm(src, 1)),
+                    _ => (self.b.ins().ishl_imm(src, 1), self.b.ins().ushr_imm(src, 7)),
+                };
+                self.set_v(x, result);
+                self.set_v(f, flag);
+            },
+            SetI { addr } => {
+                let i = self.b.ins().iconst(types::I16, addr as i64);
+                self.b.def_var(var(I), i);
+                self.written[I] = true;
+            },
+            AddI { x } => {
+                let vx = self.v(x);
+                let vx = self.b.ins().uextend(types::I16, vx);
+                let i = self.b.use_var(var(I));
+                let i = self.b.ins().iadd(i, vx);
+                self.b.def_var(var(I), i);
+                self.written[I] = true;
+            },
+            GetDelayTimer { x } => {
+                let timer = self.b.ins().load(types::I8, flags, self.cpu, self.offsets.delay_timer);
+                self.set_v(x, timer);
+            },
+            SetDelayTimer { x } => {
+                let vx = self.v(x);
+                self.b.ins().store(flags, vx, self.cpu, self.offsets.delay_timer);
+            },
+            SetSoundTimer { x } => {
+                let vx = self.v(x);
+                self.b.ins().store(flags, vx, self.cpu, self.offsets.sound_timer);
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    // Returns the pc after the instruction
+    fn branch(&mut self, addr: u16, instruction: Instruction) -> Value {
+        use super::Instruction::*;
+
+        let skip = |t: &mut Translator, a: Value, b: Value, cc: IntCC| {
+            let equal = t.b.ins().icmp(cc, a, b);
+            let skipped = t.b.ins().iconst(types::I16, addr as i64 + 4);
+            let next = t.b.ins().iconst(types::I16, addr as i64 + 2);
+            t.b.ins().select(equal, skipped, next)
+        };
+
+        match instruction {
+            Jump { addr } => self.b.ins().iconst(types::I16, addr as i64),
+            JumpV0 { addr } => {
+                let reg = if self.quirks.jump_uses_vx { (addr >> 8) as u8 & 0xF } else { 0 };
+                let offset = self.v(reg);
+                let offset = self.b.ins().uextend(types::I16, offset);
+                let target = self.b.ins().iadd_imm(offset, addr as i64);
+                self.b.ins().band_imm(target, 0x0FFF)
+            },
+            SkipEqByte { x, byte } | SkipNeqByte { x, byte } => {
+                let vx = self.v(x);
+                let byte = self.b.ins().iconst(types::I8, byte as i64);
+                let cc = match instruction {
+                    SkipEqByte { .. } => IntCC::Equal,
+                    _ => IntCC::NotEqual,
+                };
+                skip(self, vx, byte, cc)
+            },
+            SkipEq { x, y } | SkipNeq { x, y } => {
+                let vx = self.v(x);
+                let vy = self.v(y);
+                let cc = match instruction {
+                    SkipEq { .. } => IntCC::Equal,
+                    _ => IntCC::NotEqual,
+                };
+                skip(self, vx, vy, cc)
+            },
+            _ => unreachable!(),
+        }
+    }
+}
@@ -1,15 +1,58 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate core as std;
 extern crate chip8core;
+#[cfg(feature = "jit")]
+extern crate cranelift_codegen;
+#[cfg(feature = "jit")]
+extern crate cranelift_frontend;
+#[cfg(feature = "jit")]
+extern crate cranelift_jit;
+#[cfg(feature = "jit")]
+extern crate cranelift_module;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 extern crate rand;
+mod debug;
+mod disasm;
+mod font;
+mod instruction;
+#[cfg(feature = "jit")]
+mod jit;
+mod load;
 mod opcode;
-
-use chip8core::{ Vm, InstructionError, Key };
-use opcode::Opcode;
+mod random;
+#[cfg(feature = "std")]
+mod trace;
+
+use chip8core::{ DebugVm, Vm, InstructionError, Key, RomError, PROGRAM_START };
+#[cfg(feature = "std")]
+use chip8core::MEMORY_SIZE;
+#[cfg(feature = "std")]
+use std::cmp;
 use std::default::Default;
+#[cfg(feature = "std")]
 use std::io::Read;
+#[cfg(feature = "std")]
 use std::io;
 use std::slice::Chunks;
 
-const PROGRAM_START: usize = 0x200;
+pub use disasm::Disassembly;
+pub use instruction::{ Instruction, DecodeError };
+#[cfg(feature = "jit")]
+pub use jit::JitCpu;
+pub use font::{ FontSet, SmallFont, SCHIP_BIG_FONT, SMALL_FONT_SIZE, BIG_FONT_SIZE };
+pub use load::{ LoadOptions, ETI660_PROGRAM_START };
+pub use opcode::Opcode;
+pub use chip8core::Quirks;
+pub use random::{ Random, XorShift, DefaultRandom };
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub use random::ThreadRandom;
+#[cfg(feature = "std")]
+pub use trace::{ Registers, TraceEntry, TraceSink, TraceFilter, WriterSink, RingBufferSink,
+                 RING_BUFFER_SIZE };
+#[cfg(feature = "std")]
+use trace::Tracer;
 
 const GFX_W: usize = 64;
 const GFX_H: usize = 32;
@@ -17,30 +60,12 @@ const GFX_H: usize = 32;
 const F: usize = 0xF;
 
 const CLOCK_FREQ: i32 = 540;
-const CLOCK_PERIOD: f64 = 1.0 / CLOCK_FREQ as f64;
+// Seconds per clock cycle, each cycle executes at most one instruction
+pub const CLOCK_PERIOD: f64 = 1.0 / CLOCK_FREQ as f64;
 const TICK_FREQ: i32 = 60;
 const TICK_PERIOD: f64 = 1.0 / TICK_FREQ as f64;
 
-const FONT: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80  // F
-];
-
-pub struct Cpu {
+pub struct Cpu<R: Random = DefaultRandom> {
     mem: [u8; 4096],
     v: [u8; 16],
     i: u16,
@@ -55,10 +80,120 @@ pub struct Cpu {
     clock_accumulator: f64,
     tick_accumulator: f64,
     awaited_key: Option<u8>,
+    font: FontSet,
+    quirks: Quirks,
+    rng: R,
+    // Where the last ROM was loaded and started, for resets and reloads.
+    // Without std the preloads aren't kept and reloads leave them out.
+    load_address: u16,
+    entry_point: u16,
+    #[cfg(feature = "std")]
+    preload: Vec<(u16, Vec<u8>)>,
+    #[cfg(feature = "std")]
+    tracer: Option<Tracer>,
+    // Decoded instructions by address, cleared wherever memory is written
+    #[cfg(feature = "std")]
+    decode_cache: Option<Box<[Option<Instruction>]>>,
+}
+
+impl<R: Random + Default> Default for Cpu<R> {
+    fn default() -> Cpu<R> {
+        Cpu::with_random(Default::default())
+    }
+}
+
+impl<R: Random> Vm for Cpu<R> {
+    fn step(&mut self, time: f64) -> Result<(), InstructionError> {
+        self.clock_accumulator += time;
+
+        while self.clock_accumulator > CLOCK_PERIOD {
+            self.clock_accumulator -= CLOCK_PERIOD;
+            self.step_cycle()?;
+        }
+
+        Ok(())
+    }
+
+    fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        self.load_rom_bytes_with(rom, &LoadOptions::default())
+    }
+
+    // Quirks, the font set and tracing carry over, as do keys still held
+    fn reset(&mut self, keep_memory: bool) {
+        if !keep_memory {
+            self.mem = [0; 4096];
+            self.invalidate(0, self.mem.len());
+        }
+        let font = self.font;
+        self.write_font(&font);
+
+        self.v = [0; 16];
+        self.i = 0;
+        self.pc = self.entry_point;
+        self.gfx = [false; 64 * 32];
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.stack = [0; 16];
+        self.sp = 0;
+        self.clock_accumulator = 0.0;
+        self.tick_accumulator = 0.0;
+        self.awaited_key = None;
+    }
+
+    fn reload_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        #[cfg(feature = "std")]
+        let blobs = self.preload.clone();
+        #[cfg(feature = "std")]
+        let preload: Vec<(u16, &[u8])> = blobs.iter()
+            .map(|&(addr, ref blob)| (addr, &blob[..]))
+            .collect();
+        #[cfg(not(feature = "std"))]
+        let preload: [(u16, &[u8]); 0] = [];
+        let options = LoadOptions {
+            load_address: self.load_address,
+            entry_point: Some(self.entry_point),
+            preload: &preload,
+        };
+
+        self.check_load(rom.len(), &options)?;
+        self.reset(false);
+        self.load_rom_bytes_with(rom, &options)
+    }
+
+    #[cfg(feature = "std")]
+    fn load_rom<T: Read>(&mut self, reader: &mut T) -> io::Result<()> {
+        self.load_rom_with(reader, &LoadOptions::default())
+    }
+
+    fn pixels<'a>(&'a self) -> Chunks<'a, bool> {
+        self.gfx.chunks(GFX_W)
+    }
+
+    fn press_key(&mut self, key: Key) {
+        self.keys[key as usize] = true;
+        if let Some(x) = self.awaited_key {
+            self.v[x as usize] = key as u8;
+            self.awaited_key = None;
+        }
+    }
+
+    fn release_key(&mut self, key: Key) {
+        self.keys[key as usize] = false;
+    }
+
+    fn debug(&mut self) -> Option<&mut dyn DebugVm> {
+        Some(self)
+    }
 }
 
-impl Default for Cpu {
-    fn default() -> Cpu {
+impl Cpu {
+    pub fn new() -> Cpu {
+        Default::default()
+    }
+}
+
+impl<R: Random> Cpu<R> {
+    pub fn with_random(rng: R) -> Cpu<R> {
         let mut cpu = Cpu {
             mem: [0; 4096],
             v: [0; 16],
@@ -73,77 +208,237 @@ impl Default for Cpu {
             clock_accumulator: 0.0,
             tick_accumulator: 0.0,
             awaited_key: None,
+            font: FontSet::default(),
+            quirks: Quirks::default(),
+            rng,
+            load_address: PROGRAM_START as u16,
+            entry_point: PROGRAM_START as u16,
+            #[cfg(feature = "std")]
+            preload: Vec::new(),
+            #[cfg(feature = "std")]
+            tracer: None,
+            #[cfg(feature = "std")]
+            decode_cache: None,
         };
 
-        (&mut cpu.mem[..FONT.len()]).copy_from_slice(&FONT);
+        let font = cpu.font;
+        cpu.write_font(&font);
 
         cpu
     }
-}
 
-impl Vm for Cpu {
-    fn step(&mut self, time: f64) -> Result<(), InstructionError> {
-        self.clock_accumulator += time;
+    // Replaces the font in memory, FX29 and FX30 will point into the new set
+    pub fn load_font(&mut self, font: FontSet) -> Result<(), RomError> {
+        if font.base_address as usize + font.size() > self.mem.len() {
+            return Err(RomError::OutOfBounds);
+        }
 
-        while self.clock_accumulator > CLOCK_PERIOD {
-            self.clock_accumulator -= CLOCK_PERIOD;
+        let old = self.font;
+        let old_start = old.base_address as usize;
+        for b in self.mem[old_start..(old_start + old.size())].iter_mut() {
+            *b = 0;
+        }
+        self.invalidate(old_start, old.size());
 
-            self.tick_accumulator += CLOCK_PERIOD;
-            if self.tick_accumulator > TICK_PERIOD {
-                self.tick_accumulator -= TICK_PERIOD;
-                self.tick_timers();
-            }
+        self.write_font(&font);
+        self.font = font;
+        Ok(())
+    }
 
-            if !self.awaited_key.is_some() {
-                try!(self.cycle());
-            }
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    fn write_font(&mut self, font: &FontSet) {
+        let start = font.base_address as usize;
+        self.mem[start..(start + SMALL_FONT_SIZE)].copy_from_slice(&font.small);
+        if let Some(big) = font.big {
+            let start = start + SMALL_FONT_SIZE;
+            self.mem[start..(start + BIG_FONT_SIZE)].copy_from_slice(&big);
         }
+        self.invalidate(font.base_address as usize, font.size());
+    }
+
+    // Memory is only written once the preloads, the ROM and the entry
+    // point are all known to fit
+    pub fn load_rom_bytes_with(&mut self, rom: &[u8], options: &LoadOptions)
+                               -> Result<(), RomError> {
+        self.check_load(rom.len(), options)?;
 
+        for &(addr, blob) in options.preload {
+            self.write_bytes(addr as usize, blob);
+        }
+        self.write_bytes(options.load_address as usize, rom);
+        self.pc = options.entry_point();
+
+        self.load_address = options.load_address;
+        self.entry_point = options.entry_point();
+        #[cfg(feature = "std")]
+        {
+            self.preload = options.preload.iter()
+                .map(|&(addr, blob)| (addr, blob.to_vec()))
+                .collect();
+        }
         Ok(())
     }
 
-    fn load_rom<T: Read>(&mut self, reader: &mut T) -> io::Result<()> {
-        let mut rom_counter = PROGRAM_START;
-
-        loop {
-            let mem_rom = &mut self.mem[rom_counter..];
-            if mem_rom.len() == 0 {
-                // All rom memory used up, check if eof file has been reached
-                let mut eof_check = [0u8, 1];
-                if try!(reader.read(&mut eof_check)) != 0 {
-                    return Err(io::Error::new(io::ErrorKind::InvalidData,
-                                              "ROM exceeds maximum size"));
-                }
-                break;
-            }
+    #[cfg(feature = "std")]
+    pub fn load_rom_with<T: Read>(&mut self, reader: &mut T, options: &LoadOptions)
+                                  -> io::Result<()> {
+        // One byte more than fits is enough to tell the ROM is too large
+        let max = self.mem.len().saturating_sub(options.load_address as usize);
+        let mut rom = Vec::new();
+        reader.take(max as u64 + 1).read_to_end(&mut rom)?;
+
+        self.load_rom_bytes_with(&rom, options).map_err(|e| {
+            let kind = match e {
+                RomError::TooLarge => io::ErrorKind::InvalidData,
+                RomError::OutOfBounds => io::ErrorKind::InvalidInput,
+            };
+            io::Error::new(kind, e)
+        })
+    }
 
-            let n = try!(reader.read(mem_rom));
-            if n == 0 { break;}
-            rom_counter += n;
+    fn check_load(&self, rom_len: usize, options: &LoadOptions) -> Result<(), RomError> {
+        let size = self.mem.len();
+        let fits = |start: usize, len: usize| start <= size && len <= size - start;
+
+        if !options.preload.iter().all(|&(addr, blob)| fits(addr as usize, blob.len())) {
+            return Err(RomError::OutOfBounds);
+        }
+        let start = options.load_address as usize;
+        if start > size {
+            return Err(RomError::OutOfBounds);
+        }
+        if !fits(start, rom_len) {
+            return Err(RomError::TooLarge);
+        }
+        // The first instruction has to be fetched from memory
+        if options.entry_point() as usize > size - 2 {
+            return Err(RomError::OutOfBounds);
         }
         Ok(())
     }
 
-    fn pixels<'a>(&'a self) -> Chunks<'a, bool> {
-        self.gfx.chunks(GFX_W)
+    fn write_bytes(&mut self, start: usize, data: &[u8]) {
+        self.mem[start..(start + data.len())].copy_from_slice(data);
+        self.invalidate(start, data.len());
     }
 
-    fn press_key(&mut self, key: Key) {
-        self.keys[key as usize] = true;
-        if let Some(x) = self.awaited_key {
-            self.v[x as usize] = key as u8;
-            self.awaited_key = None;
+    // Sends every executed instruction that passes the filter to the sink
+    #[cfg(feature = "std")]
+    pub fn start_trace(&mut self, sink: Box<dyn TraceSink>, filter: TraceFilter) {
+        self.stop_trace();
+        self.tracer = Some(Tracer::new(sink, filter));
+    }
+
+    #[cfg(feature = "std")]
+    pub fn stop_trace(&mut self) -> Option<Box<dyn TraceSink>> {
+        self.tracer.take().map(|tracer| {
+            let mut sink = tracer.into_sink();
+            sink.finish();
+            sink
+        })
+    }
+
+    // Caches decoded instructions so code that runs repeatedly is only decoded
+    // once, worth it for long headless runs
+    #[cfg(feature = "std")]
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decode_cache = if enabled {
+            Some(vec![None; MEMORY_SIZE].into_boxed_slice())
+        } else {
+            None
+        };
+    }
+
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    fn invalidate(&mut self, start: usize, len: usize) {
+        #[cfg(feature = "std")]
+        {
+            if let Some(ref mut cache) = self.decode_cache {
+                // An instruction starting one byte earlier overlaps the write too
+                let end = cmp::min(start + len, cache.len());
+                for entry in cache[start.saturating_sub(1)..end].iter_mut() {
+                    *entry = None;
+                }
+            }
         }
     }
 
-    fn release_key(&mut self, key: Key) {
-        self.keys[key as usize] = false;
+    #[cfg(feature = "std")]
+    fn trace_registers(&self) -> Registers {
+        Registers { v: self.v, i: self.i }
     }
-}
 
-impl Cpu {
-    pub fn new() -> Cpu {
-        Default::default()
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn v(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    pub fn mem(&self) -> &[u8] {
+        &self.mem
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    // The whole stack, entries at and above sp are stale
+    pub fn stack(&self) -> &[u16; 16] {
+        &self.stack
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn keys(&self) -> &[bool; 16] {
+        &self.keys
+    }
+
+    // Executes a single instruction regardless of the clock, does nothing
+    // while waiting for a key press
+    pub fn step_instruction(&mut self) -> Result<(), InstructionError> {
+        if self.awaited_key.is_some() {
+            return Ok(());
+        }
+        self.cycle()
+    }
+
+    // Runs exactly one clock cycle, CLOCK_PERIOD seconds of step. The timers
+    // tick when due, so debuggers can go cycle by cycle and stay in time.
+    pub fn step_cycle(&mut self) -> Result<(), InstructionError> {
+        self.tick_accumulator += CLOCK_PERIOD;
+        if self.tick_accumulator > TICK_PERIOD {
+            self.tick_accumulator -= TICK_PERIOD;
+            self.tick_timers();
+        }
+        self.step_instruction()
+    }
+
+    // True while an LD Vx, K instruction holds the Cpu until a key is pressed
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.awaited_key.is_some()
     }
 
     fn tick_timers(&mut self) {
@@ -152,68 +447,110 @@ impl Cpu {
     }
 
     fn cycle(&mut self) -> Result<(), InstructionError> {
-        let opcode = get_opcode(&mut self.mem, self.pc);
-        try!(self.exec_opcode(opcode));
-        Ok(())
+        #[cfg(feature = "std")]
+        {
+            if self.tracer.is_none() && self.decode_cache.is_some() {
+                return self.cycle_cached();
+            }
+        }
+        let opcode = get_opcode(&self.mem, self.pc);
+        #[cfg(feature = "std")]
+        {
+            if self.tracer.is_some() {
+                return self.exec_traced(opcode);
+            }
+        }
+        self.exec_opcode(opcode)
+    }
+
+    #[cfg(feature = "std")]
+    fn cycle_cached(&mut self) -> Result<(), InstructionError> {
+        let pc = self.pc as usize;
+        let cached = self.decode_cache.as_ref().and_then(|cache| cache[pc]);
+        let instruction = match cached {
+            Some(instruction) => instruction,
+            None => {
+                let opcode = get_opcode(&self.mem, self.pc);
+                let instruction = match Instruction::decode(opcode) {
+                    Ok(instruction) => instruction,
+                    Err(_) => return self.exec_opcode(opcode),
+                };
+                if let Some(ref mut cache) = self.decode_cache {
+                    cache[pc] = Some(instruction);
+                }
+                instruction
+            },
+        };
+
+        self.pc += 2;
+        self.execute(instruction)
     }
 
-    fn exec_opcode(&mut self, opcode: Opcode) -> Result<(), InstructionError> {
-        let x = opcode.x();
-        let y = opcode.y();
-        let addr = opcode.addr();
-        let byte = opcode.byte();
-        let nibble = opcode.nibble();
+    #[cfg(feature = "std")]
+    fn exec_traced(&mut self, opcode: Opcode) -> Result<(), InstructionError> {
+        let pc = self.pc;
+        let before = self.trace_registers();
+        let result = self.exec_opcode(opcode);
+        let entry = TraceEntry { pc, opcode, before, after: self.trace_registers() };
+
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.record(&entry);
+            if let Err(err) = result {
+                tracer.error(err);
+            }
+        }
+        result
+    }
 
+    fn exec_opcode(&mut self, opcode: Opcode) -> Result<(), InstructionError> {
         self.pc += 2;
+        let instruction = Instruction::decode(opcode)?;
+        self.execute(instruction)
+    }
 
-        match opcode.bits() & 0xF000 {
-            0x0000 => match opcode.bits() & 0x0FFF {
-                0x00E0 => self.clear(),
-                0x00EE => self.ret(),
-                _      => return Err(InstructionError::Unsupported), // Only for RCA 1802 hw
-            },
-            0x1000 => self.jump(addr),
-            0x2000 => self.call(addr),
-            0x3000 => self.skip_eq_byte(x, byte),
-            0x4000 => self.skip_neq_byte(x, byte),
-            0x5000 => self.skip_eq(x, y),
-            0x6000 => self.set_byte(x, byte),
-            0x7000 => self.add_byte(x, byte),
-            0x8000 => match opcode.bits() & 0x000F {
-                0x0000 => self.set(x, y),
-                0x0001 => self.or(x, y),
-                0x0002 => self.and(x, y),
-                0x0003 => self.xor(x, y),
-                0x0004 => self.add(x, y),
-                0x0005 => self.sub(x, y),
-                0x0006 => self.shift_right(x),
-                0x0007 => self.subn(x, y),
-                0x000E => self.shift_left(x),
-                _      => return Err(InstructionError::Illegal),
-            },
-            0x9000 => self.skip_neq(x, y),
-            0xA000 => self.set_i(addr),
-            0xB000 => self.jump_v0(addr),
-            0xC000 => self.rand(x, byte),
-            0xD000 => self.draw(x, y, nibble),
-            0xE000 => match opcode.bits() & 0x00FF {
-                0x009E => self.skip_pressed(x),
-                0x00A1 => self.skip_not_pressed(x),
-                _      => return Err(InstructionError::Illegal),
-            },
-            0xF000 => match opcode.bits() & 0x00FF {
-                0x0007 => self.get_delay_timer(x),
-                0x000A => self.await_key_press(x),
-                0x0015 => self.set_delay_timer(x),
-                0x0018 => self.set_sound_timer(x),
-                0x001E => self.i_add(x),
-                0x0029 => self.set_char(x),
-                0x0033 => self.store_bcd(x),
-                0x0055 => self.store_regs(x),
-                0x0065 => self.load_regs(x),
-                _      => return Err(InstructionError::Illegal),
+    fn execute(&mut self, instruction: Instruction) -> Result<(), InstructionError> {
+        use Instruction::*;
+
+        match instruction {
+            // Only for RCA 1802 hw
+            Sys { .. }                => {
+                return Err(InstructionError::Unsupported(instruction.encode().bits()));
             },
-            _      => return Err(InstructionError::Illegal),
+            Clear                     => self.clear(),
+            Return                    => self.ret(),
+            Jump { addr }             => self.jump(addr),
+            Call { addr }             => self.call(addr),
+            SkipEqByte { x, byte }    => self.skip_eq_byte(x, byte),
+            SkipNeqByte { x, byte }   => self.skip_neq_byte(x, byte),
+            SkipEq { x, y }           => self.skip_eq(x, y),
+            SetByte { x, byte }       => self.set_byte(x, byte),
+            AddByte { x, byte }       => self.add_byte(x, byte),
+            Set { x, y }              => self.set(x, y),
+            Or { x, y }               => self.or(x, y),
+            And { x, y }              => self.and(x, y),
+            Xor { x, y }              => self.xor(x, y),
+            Add { x, y }              => self.add(x, y),
+            Sub { x, y }              => self.sub(x, y),
+            ShiftRight { x, y }       => self.shift_right(x, y),
+            Subn { x, y }             => self.subn(x, y),
+            ShiftLeft { x, y }        => self.shift_left(x, y),
+            SkipNeq { x, y }          => self.skip_neq(x, y),
+            SetI { addr }             => self.set_i(addr),
+            JumpV0 { addr }           => self.jump_v0(addr),
+            Rand { x, byte }          => self.rand(x, byte),
+            Draw { x, y, n }          => self.draw(x, y, n),
+            SkipPressed { x }         => self.skip_pressed(x),
+            SkipNotPressed { x }      => self.skip_not_pressed(x),
+            GetDelayTimer { x }       => self.get_delay_timer(x),
+            AwaitKeyPress { x }       => self.await_key_press(x),
+            SetDelayTimer { x }       => self.set_delay_timer(x),
+            SetSoundTimer { x }       => self.set_sound_timer(x),
+            AddI { x }                => self.i_add(x),
+            SetChar { x }             => self.set_char(x),
+            SetBigChar { x }          => self.set_big_char(x)?,
+            StoreBcd { x }            => self.store_bcd(x),
+            StoreRegs { x }           => self.store_regs(x),
+            LoadRegs { x }            => self.load_regs(x),
         }
         Ok(())
     }
@@ -275,14 +612,23 @@ impl Cpu {
 
     fn or(&mut self, x: u8, y: u8) {
         self.v[x as usize] |= self.v[y as usize];
+        self.reset_vf();
     }
 
     fn and(&mut self, x: u8, y: u8) {
         self.v[x as usize] &= self.v[y as usize];
+        self.reset_vf();
     }
 
     fn xor(&mut self, x: u8, y: u8) {
         self.v[x as usize] ^= self.v[y as usize];
+        self.reset_vf();
+    }
+
+    fn reset_vf(&mut self) {
+        if self.quirks.logic_resets_vf {
+            self.v[F] = 0;
+        }
     }
 
     fn add(&mut self, x: u8, y: u8) {
@@ -298,12 +644,13 @@ impl Cpu {
         let vy = self.v[y as usize];
         self.v[x as usize] = vx.wrapping_sub(vy);
 
-        self.v[F] = (vx > vy) as u8;
+        self.v[F] = (vx >= vy) as u8;
     }
 
-    fn shift_right(&mut self, x: u8) {
-        self.v[F] = self.v[x as usize] & 0b1;
-        self.v[x as usize] >>= 1;
+    fn shift_right(&mut self, x: u8, y: u8) {
+        let src = self.shift_source(x, y);
+        self.v[x as usize] = src >> 1;
+        self.v[F] = src & 0b1;
     }
 
     fn subn(&mut self, x: u8, y: u8) {
@@ -311,12 +658,21 @@ impl Cpu {
         let vy = self.v[y as usize];
         self.v[x as usize] = vy.wrapping_sub(vx);
 
-        self.v[F] = (vy > vx) as u8;
+        self.v[F] = (vy >= vx) as u8;
+    }
+
+    fn shift_left(&mut self, x: u8, y: u8) {
+        let src = self.shift_source(x, y);
+        self.v[x as usize] = src << 1;
+        self.v[F] = src >> 7;
     }
 
-    fn shift_left(&mut self, x: u8) {
-        self.v[F] = self.v[x as usize] >> 7;
-        self.v[x as usize] <<= 1;
+    fn shift_source(&self, x: u8, y: u8) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.v[y as usize]
+        } else {
+            self.v[x as usize]
+        }
     }
 
     fn skip_neq(&mut self, x: u8, y: u8) {
@@ -332,25 +688,39 @@ impl Cpu {
     }
 
     fn jump_v0(&mut self, addr: u16) {
-        self.pc = (addr + self.v[0] as u16) & 0x0FFF;
+        let offset = if self.quirks.jump_uses_vx {
+            self.v[(addr >> 8) as usize]
+        } else {
+            self.v[0]
+        };
+        self.pc = (addr + offset as u16) & 0x0FFF;
     }
 
     fn rand(&mut self, x: u8, byte: u8) {
-        self.v[x as usize] = rand::random::<u8>() & byte;
+        self.v[x as usize] = self.rng.random_byte() & byte;
     }
 
     fn draw(&mut self, x: u8, y: u8, nibble: u8) {
-        let vx = self.v[x as usize] as usize;
-        let vy = self.v[y as usize] as usize;
+        // The starting position always wraps, the quirk only decides what
+        // happens to the part of the sprite that crosses the edge
+        let vx = self.v[x as usize] as usize % GFX_W;
+        let vy = self.v[y as usize] as usize % GFX_H;
         let n = nibble as usize;
         let i = self.i as usize;
+        let clip = self.quirks.clip_sprites;
 
         let spr = &self.mem[i..(i + n)];
         self.v[F] = 0;
 
         for (spr_y, byte) in spr.iter().enumerate() {
+            if clip && vy + spr_y >= GFX_H {
+                break;
+            }
             let gfx_y = (vy + spr_y) % GFX_H;
             for spr_x in 0..8 as usize {
+                if clip && vx + spr_x >= GFX_W {
+                    break;
+                }
                 let mask = 0b1000_0000 >> spr_x;
                 let is_sprite_pixel = (byte & mask) != 0;
 
@@ -398,8 +768,20 @@ impl Cpu {
     }
 
     fn set_char(&mut self, x: u8) {
-        let vx = self.v[x as usize] as usize;
-        self.i = FONT[vx * 5] as u16;
+        let vx = self.v[x as usize];
+        self.i = self.font.small_glyph_address(vx);
+    }
+
+    fn set_big_char(&mut self, x: u8) -> Result<(), InstructionError> {
+        let vx = self.v[x as usize];
+        match self.font.big_glyph_address(vx) {
+            Some(addr) => self.i = addr,
+            None => {
+                let opcode = Instruction::SetBigChar { x }.encode();
+                return Err(InstructionError::Unsupported(opcode.bits()));
+            },
+        }
+        Ok(())
     }
 
     fn store_bcd(&mut self, x: u8) {
@@ -408,19 +790,27 @@ impl Cpu {
 
         self.mem[i] = vx / 100;
         self.mem[i + 1] = (vx / 10) % 10;
-        self.mem[i + 2] = (vx / 100) % 10;
+        self.mem[i + 2] = vx % 10;
+        self.invalidate(i, 3);
     }
 
     fn store_regs(&mut self, x: u8) {
         for i in 0..(x + 1) as usize {
             self.mem[(self.i as usize) + i] = self.v[i];
         }
+        self.invalidate(self.i as usize, x as usize + 1);
+        if self.quirks.load_store_increments_i {
+            self.i += x as u16 + 1;
+        }
     }
 
     fn load_regs(&mut self, x: u8) {
         for i in 0..(x + 1) as usize {
              self.v[i] = self.mem[(self.i as usize) + i];
         }
+        if self.quirks.load_store_increments_i {
+            self.i += x as u16 + 1;
+        }
     }
 }
 
@@ -432,8 +822,7 @@ fn get_opcode(mem: &[u8], pc: u16) -> Opcode {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::FONT;
-    use chip8core::Vm;
+    use chip8core::{ Vm, RomError };
     use std::io::Cursor;
     use opcode::Opcode;
 
@@ -453,8 +842,9 @@ mod tests {
         assert!(cpu.keys.iter().all(|&x| x == false));
 
         // Check that the fontset was set into memory
+        let font = SmallFont::Standard.data();
         for i in 0..80 {
-            assert_eq!(FONT[i], cpu.mem[i]);
+            assert_eq!(font[i], cpu.mem[i]);
         }
 
         // Check that the rest of the memory is zero
@@ -465,7 +855,7 @@ mod tests {
         assert_eq!(cpu.clock_accumulator, 0.0);
         assert_eq!(cpu.tick_accumulator, 0.0);
 
-        assert_eq!(awaited_key, None);
+        assert_eq!(cpu.awaited_key, None);
     }
 
     #[test]
@@ -490,6 +880,368 @@ mod tests {
         cpu.load_rom(&mut rom_reader);
     }
 
+    #[test]
+    fn load_rom_bytes_copies_into_memory() {
+        let rom = [0xF5, 0xC2, 0xF5, 0xC2];
+
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes(&rom).unwrap();
+
+        assert_eq!(&cpu.mem[0x200..0x204], &rom);
+    }
+
+    #[test]
+    fn load_rom_bytes_too_large_returns_err() {
+        let rom = vec![1u8; 4096 - 0x200 + 1];
+
+        let mut cpu = Cpu::new();
+        assert_eq!(cpu.load_rom_bytes(&rom), Err(RomError::TooLarge));
+    }
+
+    #[test]
+    fn load_rom_with_eti660_start() {
+        let rom = [0x00, 0xE0];
+
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes_with(&rom, &LoadOptions::eti660()).unwrap();
+
+        assert_eq!(&cpu.mem[0x600..0x602], &rom);
+        assert_eq!(cpu.mem[0x200], 0);
+        assert_eq!(cpu.pc, 0x600);
+    }
+
+    #[test]
+    fn load_rom_with_custom_entry_point() {
+        let options = LoadOptions { entry_point: Some(0x2F0), ..LoadOptions::default() };
+
+        let mut cpu = Cpu::new();
+        cpu.load_rom_with(&mut Cursor::new(vec![0x12, 0x34]), &options).unwrap();
+
+        assert_eq!(&cpu.mem[0x200..0x202], &[0x12, 0x34]);
+        assert_eq!(cpu.pc, 0x2F0);
+    }
+
+    #[test]
+    fn load_rom_with_max_size_depends_on_load_address() {
+        let max_rom_size = 4096 - 0x600;
+
+        let mut cpu = Cpu::new();
+        let rom = vec![1u8; max_rom_size];
+        assert!(cpu.load_rom_bytes_with(&rom, &LoadOptions::eti660()).is_ok());
+        assert!(cpu.load_rom_with(&mut Cursor::new(&rom), &LoadOptions::eti660()).is_ok());
+
+        let rom = vec![1u8; max_rom_size + 1];
+        assert_eq!(cpu.load_rom_bytes_with(&rom, &LoadOptions::eti660()), Err(RomError::TooLarge));
+        let err = cpu.load_rom_with(&mut Cursor::new(&rom), &LoadOptions::eti660()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn failed_load_leaves_memory_untouched() {
+        let blob = [0xAA, 0xBB];
+        let preload = [(0x300, &blob[..])];
+        let options = LoadOptions { preload: &preload, ..LoadOptions::default() };
+
+        let mut cpu = Cpu::new();
+        let rom = vec![1u8; 4096 - 0x200 + 1];
+        assert_eq!(cpu.load_rom_bytes_with(&rom, &options), Err(RomError::TooLarge));
+        assert_eq!(&cpu.mem[0x300..0x302], &[0, 0]);
+
+        let options = LoadOptions { load_address: 0x1001, ..options };
+        assert_eq!(cpu.load_rom_bytes_with(&[], &options), Err(RomError::OutOfBounds));
+        assert_eq!(&cpu.mem[0x300..0x302], &[0, 0]);
+    }
+
+    #[test]
+    fn entry_point_must_leave_room_for_an_instruction() {
+        let mut cpu = Cpu::new();
+        let options = LoadOptions { entry_point: Some(0xFFF), ..LoadOptions::default() };
+        assert_eq!(cpu.load_rom_bytes_with(&[0x00, 0xE0], &options), Err(RomError::OutOfBounds));
+        assert_eq!(cpu.mem[0x200], 0);
+
+        let options = LoadOptions { entry_point: Some(0xFFE), ..LoadOptions::default() };
+        assert!(cpu.load_rom_bytes_with(&[0x00, 0xE0], &options).is_ok());
+        assert_eq!(cpu.pc, 0xFFE);
+    }
+
+    #[test]
+    fn load_rom_with_preloads_regions() {
+        let preload: [(u16, &[u8]); 2] = [(0x100, &[0xAA, 0xBB]), (0xF00, &[0xCC])];
+        let options = LoadOptions { preload: &preload, ..LoadOptions::default() };
+
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes_with(&[0x00, 0xE0], &options).unwrap();
+
+        assert_eq!(&cpu.mem[0x100..0x102], &[0xAA, 0xBB]);
+        assert_eq!(cpu.mem[0xF00], 0xCC);
+        assert_eq!(&cpu.mem[0x200..0x202], &[0x00, 0xE0]);
+    }
+
+    #[test]
+    fn load_rom_with_preload_outside_memory_returns_err() {
+        let preload: [(u16, &[u8]); 1] = [(0xFFF, &[0xAA, 0xBB])];
+        let options = LoadOptions { preload: &preload, ..LoadOptions::default() };
+
+        let mut cpu = Cpu::new();
+        assert_eq!(cpu.load_rom_bytes_with(&[], &options), Err(RomError::OutOfBounds));
+    }
+
+    #[test]
+    fn rand_cxnn_uses_injected_random() {
+        let mut cpu = Cpu::with_random(XorShift::new(42));
+        let mut rng = XorShift::new(42);
+
+        cpu.exec_opcode(Opcode::new(0xCA0F)).unwrap();
+
+        assert_eq!(cpu.v[0xA], rng.random_byte() & 0x0F);
+    }
+
+    #[test]
+    fn set_char_fx29_points_into_chosen_font() {
+        let fonts = [SmallFont::Standard, SmallFont::Vip, SmallFont::Eti660,
+                     SmallFont::Dream6800, SmallFont::FishNChips];
+
+        for &small in fonts.iter() {
+            let mut cpu = Cpu::new();
+            cpu.load_font(FontSet::new(small).at(0x50)).unwrap();
+
+            for digit in 0..16u8 {
+                cpu.v[0x3] = digit;
+                cpu.exec_opcode(Opcode::new(0xF329)).unwrap();
+
+                let i = cpu.i as usize;
+                let glyph = &small.data()[(digit as usize * 5)..(digit as usize * 5 + 5)];
+                assert_eq!(&cpu.mem[i..(i + 5)], glyph);
+            }
+        }
+    }
+
+    #[test]
+    fn set_big_char_fx30_points_into_big_font() {
+        let mut cpu = Cpu::new();
+        cpu.load_font(FontSet::new(SmallFont::Vip).with_big_font()).unwrap();
+
+        for digit in 0..10u8 {
+            cpu.v[0x3] = digit;
+            cpu.exec_opcode(Opcode::new(0xF330)).unwrap();
+
+            let i = cpu.i as usize;
+            let glyph = &SCHIP_BIG_FONT[(digit as usize * 10)..(digit as usize * 10 + 10)];
+            assert_eq!(&cpu.mem[i..(i + 10)], glyph);
+        }
+    }
+
+    #[test]
+    fn set_big_char_fx30_without_big_font_is_unsupported() {
+        let mut cpu = Cpu::new();
+
+        match cpu.exec_opcode(Opcode::new(0xF330)) {
+            Err(InstructionError::Unsupported(0xF330)) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn set_big_char_fx30_has_no_hex_letters() {
+        let mut cpu = Cpu::new();
+        cpu.load_font(FontSet::default().with_big_font()).unwrap();
+        cpu.v[0x3] = 0xA;
+
+        match cpu.exec_opcode(Opcode::new(0xF330)) {
+            Err(InstructionError::Unsupported(0xF330)) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn load_font_clears_previous_font() {
+        let mut cpu = Cpu::new();
+        cpu.load_font(FontSet::new(SmallFont::Eti660).at(0x100)).unwrap();
+
+        assert!(cpu.mem[..80].iter().all(|&b| b == 0));
+        assert_eq!(&cpu.mem[0x100..0x150], &SmallFont::Eti660.data()[..]);
+    }
+
+    #[test]
+    fn reset_restarts_the_rom_with_memory_kept() {
+        let mut cpu = Cpu::new();
+        // LD V0, 7; LD I, 0x300; LD DT, V0; DRW V0, V0, 1
+        cpu.load_rom_bytes(&[0x60, 0x07, 0xA3, 0x00, 0xF0, 0x15, 0xD0, 0x01]).unwrap();
+        cpu.mem[0x300] = 0xFF;
+        cpu.mem[0x10] = 0xAA;
+        for _ in 0..4 {
+            cpu.step_instruction().unwrap();
+        }
+
+        cpu.reset(true);
+        assert_eq!(cpu.pc, PROGRAM_START as u16);
+        assert_eq!(cpu.v[0], 0);
+        assert_eq!(cpu.i, 0);
+        assert_eq!(cpu.delay_timer, 0);
+        assert!(cpu.gfx.iter().all(|&on| !on));
+        assert_eq!(cpu.mem[0x200], 0x60);
+        assert_eq!(cpu.mem[0x300], 0xFF);
+        // The font is written again over whatever the ROM put there
+        assert_eq!(&cpu.mem[..80], &SmallFont::Standard.data()[..]);
+
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.v[0], 7);
+    }
+
+    #[test]
+    fn reset_without_memory_keeps_only_the_font() {
+        let mut cpu = Cpu::new();
+        cpu.load_font(FontSet::new(SmallFont::Eti660).at(0x100)).unwrap();
+        cpu.load_rom_bytes(&[0x60, 0x07]).unwrap();
+
+        cpu.reset(false);
+        assert!(cpu.mem[..0x100].iter().all(|&b| b == 0));
+        assert_eq!(&cpu.mem[0x100..0x150], &SmallFont::Eti660.data()[..]);
+        assert!(cpu.mem[0x150..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn reset_and_reload_keep_the_load_options() {
+        let blob = [0xAB, 0xCD];
+        let preload: [(u16, &[u8]); 1] = [(0x300, &blob)];
+        let options = LoadOptions { preload: &preload, entry_point: Some(0x602),
+                                    ..LoadOptions::eti660() };
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes_with(&[0x60, 0x07, 0x61, 0x08], &options).unwrap();
+        cpu.step_instruction().unwrap();
+
+        cpu.reset(true);
+        assert_eq!(cpu.pc, 0x602);
+
+        cpu.mem[0x300] = 0;
+        cpu.reload_rom_bytes(&[0x62, 0x09, 0x63, 0x0A]).unwrap();
+        assert_eq!(cpu.pc, 0x602);
+        assert_eq!(&cpu.mem[0x600..0x604], &[0x62, 0x09, 0x63, 0x0A]);
+        assert_eq!(&cpu.mem[0x300..0x302], &blob);
+        assert_eq!(cpu.mem[0x200], 0);
+
+        // Fits at PROGRAM_START but not at 0x600
+        assert_eq!(cpu.reload_rom_bytes(&[0xAA; 0xB00]), Err(RomError::TooLarge));
+        assert_eq!(&cpu.mem[0x600..0x604], &[0x62, 0x09, 0x63, 0x0A]);
+    }
+
+    #[test]
+    fn load_font_outside_memory_returns_err() {
+        let mut cpu = Cpu::new();
+        let font = FontSet::default().with_big_font().at(0xF50);
+
+        assert_eq!(cpu.load_font(font).err(), Some(RomError::OutOfBounds));
+    }
+
+    #[test]
+    fn font_from_reader_reads_small_and_big_font() {
+        let mut data = vec![0x11; 80];
+        data.extend(vec![0x22; 100]);
+
+        let font = FontSet::from_reader(&mut Cursor::new(&data[..80])).unwrap();
+        assert!(font.small.iter().all(|&b| b == 0x11));
+        assert!(font.big.is_none());
+
+        let font = FontSet::from_reader(&mut Cursor::new(&data)).unwrap();
+        assert!(font.big.unwrap().iter().all(|&b| b == 0x22));
+
+        assert!(FontSet::from_reader(&mut Cursor::new(&data[..79])).is_err());
+    }
+
+    #[test]
+    fn decode_cache_sees_self_modifying_code() {
+        let mut cpu = Cpu::new();
+        cpu.set_decode_cache(true);
+        cpu.load_rom_bytes(&[0x60, 0x05]).unwrap();
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.v[0x0], 0x05);
+
+        // Patch the low byte of the cached LD V0, 0x05 through FX55
+        cpu.v[0x0] = 0x09;
+        cpu.i = 0x201;
+        cpu.exec_opcode(Opcode::new(0xF055)).unwrap();
+        cpu.pc = 0x200;
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.v[0x0], 0x09);
+
+        // And through FX33, the hundreds digit lands in the low byte
+        cpu.v[0x1] = 200;
+        cpu.exec_opcode(Opcode::new(0xF133)).unwrap();
+        cpu.pc = 0x200;
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.v[0x0], 0x02);
+    }
+
+    #[test]
+    fn decode_cache_cleared_by_load_rom() {
+        let mut cpu = Cpu::new();
+        cpu.set_decode_cache(true);
+        cpu.load_rom_bytes(&[0x60, 0x05]).unwrap();
+        cpu.step_instruction().unwrap();
+
+        cpu.load_rom_bytes(&[0x60, 0x06]).unwrap();
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.v[0x0], 0x06);
+    }
+
+    #[test]
+    fn step_cycle_ticks_timers_at_60_hz() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes(&[0x12, 0x00]).unwrap();
+        cpu.delay_timer = 100;
+
+        for _ in 0..CLOCK_FREQ {
+            cpu.step_cycle().unwrap();
+        }
+        assert!((cpu.delay_timer as i32 - (100 - TICK_FREQ)).abs() <= 1);
+    }
+
+    #[test]
+    fn step_cycle_waits_for_key() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes(&[0xF0, 0x0A, 0x60, 0x01]).unwrap();
+        cpu.step_cycle().unwrap();
+        cpu.step_cycle().unwrap();
+        assert!(cpu.is_waiting_for_key());
+        assert_eq!(cpu.pc, 0x202);
+
+        cpu.press_key(Key::D4);
+        assert!(!cpu.is_waiting_for_key());
+        cpu.step_cycle().unwrap();
+        assert_eq!(cpu.v[0x0], 0x01);
+    }
+
+    #[test]
+    fn step_error_dumps_trace_ring_buffer() {
+        use std::cell::RefCell;
+        use std::io::Write;
+        use std::rc::Rc;
+
+        #[derive(Clone)]
+        struct Shared(Rc<RefCell<Vec<u8>>>);
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let out = Shared(Rc::new(RefCell::new(Vec::new())));
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes(&[0x6A, 0x25, 0x8A, 0xB8]).unwrap();
+        cpu.start_trace(Box::new(RingBufferSink::new(out.clone())), TraceFilter::default());
+
+        assert!(cpu.step(1.0).is_err());
+
+        let out = String::from_utf8(out.0.borrow().clone()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("0200  6A25  LD VA, 0x25"));
+        assert!(lines[2].starts_with("0202  8AB8  DW 0x8AB8"));
+    }
+
     #[test]
     fn clear_00e0() {
         let mut cpu = Cpu::new();
@@ -713,47 +1465,175 @@ mod tests {
         assert_eq!(cpu.pc, 0x0200 + 2);
     }
 
-    // #[test]
-    // fn sub_8xy5_borrow() {
-    // }
+    #[test]
+    fn store_bcd_fx33() {
+        let mut cpu = Cpu::new();
+        cpu.v[0x5] = 156;
+        cpu.i = 0x400;
 
+        cpu.exec_opcode(Opcode::new(0xF533)).unwrap();
 
-    /*
+        assert_eq!(&cpu.mem[0x400..0x403], &[1, 5, 6]);
+        assert_eq!(cpu.pc, 0x0200 + 2);
+    }
 
-    fn sub(&mut self, x: u8, y: u8) {
-        let vx = self.v[x as usize];
-        let vy = self.v[y as usize];
-        self.v[x as usize] = vx.wrapping_sub(vy);
+    #[test]
+    fn sub_8xy5_borrow() {
+        let mut cpu = Cpu::new();
+        cpu.v[0xA] = 0xA3;
+        cpu.v[0xB] = 0xA5;
+
+        cpu.exec_opcode(Opcode::new(0x8AB5)).unwrap();
 
-        self.v[F] = (vx > vy) as u8;
+        assert_eq!(cpu.v[0xA], 0xFE);
+        assert_eq!(cpu.v[0xB], 0xA5);
+        assert_eq!(cpu.v[0xF], 0);
+        assert_eq!(cpu.pc, 0x0200 + 2);
     }
 
-    fn shift_right(&mut self, x: u8) {
-        self.v[F] = self.v[x as usize] & 0b1;
-        self.v[x as usize] >>= 1;
+    #[test]
+    fn sub_8xy5_equal_is_no_borrow() {
+        let mut cpu = Cpu::new();
+        cpu.v[0xA] = 0xA5;
+        cpu.v[0xB] = 0xA5;
+
+        cpu.exec_opcode(Opcode::new(0x8AB5)).unwrap();
+
+        assert_eq!(cpu.v[0xA], 0x00);
+        assert_eq!(cpu.v[0xF], 1);
     }
 
-    fn subn(&mut self, x: u8, y: u8) {
-        let vx = self.v[x as usize];
-        let vy = self.v[y as usize];
-        self.v[x as usize] = vy.wrapping_sub(vx);
+    #[test]
+    fn shift_right_8xy6() {
+        let mut cpu = Cpu::new();
+        cpu.v[0xA] = 0b1000_0101;
+        cpu.v[0xB] = 0b0100_0000;
+
+        cpu.exec_opcode(Opcode::new(0x8AB6)).unwrap();
 
-        self.v[F] = (vy > vx) as u8;
+        assert_eq!(cpu.v[0xA], 0b0100_0010);
+        assert_eq!(cpu.v[0xF], 1);
+        assert_eq!(cpu.pc, 0x0200 + 2);
     }
 
-    fn shift_left(&mut self, x: u8) {
-        self.v[F] = self.v[x as usize] >> 7;
-        self.v[x as usize] <<= 1;
+    #[test]
+    fn subn_8xy7_borrow() {
+        let mut cpu = Cpu::new();
+        cpu.v[0xA] = 0xA5;
+        cpu.v[0xB] = 0xA3;
+
+        cpu.exec_opcode(Opcode::new(0x8AB7)).unwrap();
+
+        assert_eq!(cpu.v[0xA], 0xFE);
+        assert_eq!(cpu.v[0xB], 0xA3);
+        assert_eq!(cpu.v[0xF], 0);
+        assert_eq!(cpu.pc, 0x0200 + 2);
     }
 
-    fn skip_neq(&mut self, x: u8, y: u8) {
-        let vx = self.v[x as usize];
-        let vy = self.v[y as usize];
-        if vx != vy {
-            self.pc += 2;
-        }
+    #[test]
+    fn shift_left_8xye() {
+        let mut cpu = Cpu::new();
+        cpu.v[0xA] = 0b1000_0101;
+        cpu.v[0xB] = 0b0100_0000;
+
+        cpu.exec_opcode(Opcode::new(0x8ABE)).unwrap();
+
+        assert_eq!(cpu.v[0xA], 0b0000_1010);
+        assert_eq!(cpu.v[0xF], 1);
+        assert_eq!(cpu.pc, 0x0200 + 2);
+    }
+
+    #[test]
+    fn skip_neq_9xy0_not_equal() {
+        let mut cpu = Cpu::new();
+        cpu.v[0xA] = 0x01;
+        cpu.v[0xB] = 0x02;
+
+        cpu.exec_opcode(Opcode::new(0x9AB0)).unwrap();
+
+        assert_eq!(cpu.pc, 0x0200 + 4);
+    }
+
+    #[test]
+    fn shift_quirk_uses_vy() {
+        let mut cpu = Cpu::new();
+        cpu.set_quirks(Quirks { shift_uses_vy: true, ..Quirks::default() });
+        cpu.v[0xA] = 0b1000_0101;
+        cpu.v[0xB] = 0b0100_0000;
+
+        cpu.exec_opcode(Opcode::new(0x8AB6)).unwrap();
+
+        assert_eq!(cpu.v[0xA], 0b0010_0000);
+        assert_eq!(cpu.v[0xB], 0b0100_0000);
+        assert_eq!(cpu.v[0xF], 0);
+    }
+
+    #[test]
+    fn load_store_quirk_increments_i() {
+        let mut cpu = Cpu::new();
+        cpu.set_quirks(Quirks { load_store_increments_i: true, ..Quirks::default() });
+        cpu.i = 0x400;
+
+        cpu.exec_opcode(Opcode::new(0xF355)).unwrap();
+        assert_eq!(cpu.i, 0x404);
+
+        cpu.exec_opcode(Opcode::new(0xF165)).unwrap();
+        assert_eq!(cpu.i, 0x406);
+    }
+
+    #[test]
+    fn logic_quirk_resets_vf() {
+        let mut cpu = Cpu::new();
+        cpu.set_quirks(Quirks { logic_resets_vf: true, ..Quirks::default() });
+        cpu.v[0xF] = 0x55;
+
+        cpu.exec_opcode(Opcode::new(0x8AB1)).unwrap();
+
+        assert_eq!(cpu.v[0xF], 0);
+    }
+
+    #[test]
+    fn draw_wraps_sprites_by_default() {
+        let mut cpu = Cpu::new();
+        cpu.v[0xA] = 62;
+        cpu.v[0xB] = 0;
+        cpu.i = 0x400;
+        cpu.mem[0x400] = 0xF0;
+
+        cpu.exec_opcode(Opcode::new(0xDAB1)).unwrap();
+
+        assert_eq!(cpu.pixels().next().unwrap()[..2], [true, true]);
+        assert_eq!(cpu.pixels().next().unwrap()[62..], [true, true]);
     }
 
-*/
+    #[test]
+    fn draw_clip_quirk_cuts_sprites_at_edge() {
+        let mut cpu = Cpu::new();
+        cpu.set_quirks(Quirks { clip_sprites: true, ..Quirks::default() });
+        cpu.v[0xA] = 62 + 64;
+        cpu.v[0xB] = 31;
+        cpu.i = 0x400;
+        cpu.mem[0x400] = 0xF0;
+        cpu.mem[0x401] = 0xF0;
+
+        cpu.exec_opcode(Opcode::new(0xDAB2)).unwrap();
+
+        let rows: Vec<&[bool]> = cpu.pixels().collect();
+        assert_eq!(rows[31][62..], [true, true]);
+        assert!(rows[31][..2].iter().all(|&p| !p));
+        assert!(rows[0].iter().all(|&p| !p));
+    }
+
+    #[test]
+    fn jump_quirk_uses_vx() {
+        let mut cpu = Cpu::new();
+        cpu.set_quirks(Quirks { jump_uses_vx: true, ..Quirks::default() });
+        cpu.v[0x0] = 0x10;
+        cpu.v[0x3] = 0x02;
+
+        cpu.exec_opcode(Opcode::new(0xB300)).unwrap();
+
+        assert_eq!(cpu.pc, 0x302);
+    }
 
 }
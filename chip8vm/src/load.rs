@@ -0,0 +1,37 @@
+use chip8core::PROGRAM_START;
+
+pub const ETI660_PROGRAM_START: u16 = 0x600;
+
+// Where a ROM is placed in memory and where execution starts
+#[derive(Clone, Copy, Debug)]
+pub struct LoadOptions<'a> {
+    pub load_address: u16,
+    // Initial pc, defaults to the load address
+    pub entry_point: Option<u16>,
+    // Extra blobs copied into memory at the given addresses before the ROM
+    pub preload: &'a [(u16, &'a [u8])],
+}
+
+impl<'a> LoadOptions<'a> {
+    pub fn at(load_address: u16) -> LoadOptions<'a> {
+        LoadOptions {
+            load_address,
+            entry_point: None,
+            preload: &[],
+        }
+    }
+
+    pub fn eti660() -> LoadOptions<'a> {
+        LoadOptions::at(ETI660_PROGRAM_START)
+    }
+
+    pub fn entry_point(&self) -> u16 {
+        self.entry_point.unwrap_or(self.load_address)
+    }
+}
+
+impl<'a> Default for LoadOptions<'a> {
+    fn default() -> LoadOptions<'a> {
+        LoadOptions::at(PROGRAM_START as u16)
+    }
+}
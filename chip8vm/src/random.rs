@@ -0,0 +1,74 @@
+const DEFAULT_SEED: u32 = 0x2545_F491;
+
+// Source of the random bytes used by CXNN
+pub trait Random {
+    fn random_byte(&mut self) -> u8;
+}
+
+// Uses the thread local generator from the rand crate
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadRandom;
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl Random for ThreadRandom {
+    fn random_byte(&mut self) -> u8 {
+        ::rand::random::<u8>()
+    }
+}
+
+// A small deterministic generator for targets without an entropy source
+#[derive(Clone, Copy, Debug)]
+pub struct XorShift {
+    state: u32,
+}
+
+impl XorShift {
+    pub fn new(seed: u32) -> XorShift {
+        XorShift { state: if seed == 0 { DEFAULT_SEED } else { seed } }
+    }
+}
+
+impl Default for XorShift {
+    fn default() -> XorShift {
+        XorShift::new(DEFAULT_SEED)
+    }
+}
+
+impl Random for XorShift {
+    fn random_byte(&mut self) -> u8 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        (x >> 24) as u8
+    }
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub type DefaultRandom = ThreadRandom;
+
+#[cfg(not(all(feature = "std", not(target_arch = "wasm32"))))]
+pub type DefaultRandom = XorShift;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xorshift_is_deterministic() {
+        let mut a = XorShift::new(1234);
+        let mut b = XorShift::new(1234);
+
+        for _ in 0..100 {
+            assert_eq!(a.random_byte(), b.random_byte());
+        }
+    }
+
+    #[test]
+    fn xorshift_zero_seed_is_replaced() {
+        let mut rng = XorShift::new(0);
+        assert!((0..16).any(|_| rng.random_byte() != 0));
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::Write;
+use chip8core::InstructionError;
+use disasm::Disassembly;
+use opcode::Opcode;
+
+pub const RING_BUFFER_SIZE: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+}
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "V")?;
+        for v in self.v.iter() {
+            write!(f, " {:02X}", v)?;
+        }
+        write!(f, " I {:04X}", self.i)
+    }
+}
+
+// One executed instruction
+#[derive(Clone, Copy, Debug)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: Opcode,
+    pub before: Registers,
+    pub after: Registers,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mnemonic = format!("{}", Disassembly(self.opcode));
+        write!(f, "{:04X}  {:04X}  {:<16}  {}  ->  {}",
+               self.pc, self.opcode.bits(), mnemonic, self.before, self.after)
+    }
+}
+
+pub trait TraceSink {
+    fn trace(&mut self, entry: &TraceEntry);
+
+    // Called after the traced instruction failed to execute
+    fn error(&mut self, _err: InstructionError) {}
+
+    // Called when tracing is stopped
+    fn finish(&mut self) {}
+}
+
+// Limits which instructions reach the sink
+#[derive(Clone, Copy, Debug)]
+pub struct TraceFilter {
+    // Inclusive range of pc values
+    pub address_range: Option<(u16, u16)>,
+    // Bit n set traces opcodes of the form 0xN___
+    pub opcode_classes: u16,
+    // Stop tracing after this many instructions have been traced
+    pub first: Option<u64>,
+}
+
+impl TraceFilter {
+    pub fn matches(&self, pc: u16, opcode: Opcode) -> bool {
+        let in_range = match self.address_range {
+            Some((start, end)) => pc >= start && pc <= end,
+            None => true,
+        };
+        let class = opcode.bits() >> 12;
+        in_range && self.opcode_classes & (1 << class) != 0
+    }
+}
+
+impl Default for TraceFilter {
+    fn default() -> TraceFilter {
+        TraceFilter {
+            address_range: None,
+            opcode_classes: 0xFFFF,
+            first: None,
+        }
+    }
+}
+
+pub struct Tracer {
+    sink: Box<dyn TraceSink>,
+    filter: TraceFilter,
+    traced: u64,
+}
+
+impl Tracer {
+    pub fn new(sink: Box<dyn TraceSink>, filter: TraceFilter) -> Tracer {
+        Tracer {
+            sink,
+            filter,
+            traced: 0,
+        }
+    }
+
+    pub fn into_sink(self) -> Box<dyn TraceSink> {
+        self.sink
+    }
+
+    pub fn record(&mut self, entry: &TraceEntry) {
+        if let Some(first) = self.filter.first {
+            if self.traced >= first {
+                return;
+            }
+        }
+        if self.filter.matches(entry.pc, entry.opcode) {
+            self.traced += 1;
+            self.sink.trace(entry);
+        }
+    }
+
+    pub fn error(&mut self, err: InstructionError) {
+        self.sink.error(err);
+    }
+}
+
+// Writes one line per instruction
+pub struct WriterSink<W: Write> {
+    out: W,
+}
+
+impl<W: Write> WriterSink<W> {
+    pub fn new(out: W) -> WriterSink<W> {
+        WriterSink { out }
+    }
+}
+
+impl<W: Write> TraceSink for WriterSink<W> {
+    fn trace(&mut self, entry: &TraceEntry) {
+        let _ = writeln!(self.out, "{}", entry);
+    }
+
+    fn finish(&mut self) {
+        let _ = self.out.flush();
+    }
+}
+
+// Keeps the last instructions in memory and writes them out when an
+// instruction fails or tracing is stopped
+pub struct RingBufferSink<W: Write> {
+    out: W,
+    entries: VecDeque<TraceEntry>,
+    capacity: usize,
+}
+
+impl<W: Write> RingBufferSink<W> {
+    pub fn new(out: W) -> RingBufferSink<W> {
+        RingBufferSink::with_capacity(out, RING_BUFFER_SIZE)
+    }
+
+    pub fn with_capacity(out: W, capacity: usize) -> RingBufferSink<W> {
+        RingBufferSink {
+            out,
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn dump(&mut self) {
+        for entry in self.entries.drain(..) {
+            let _ = writeln!(self.out, "{}", entry);
+        }
+        let _ = self.out.flush();
+    }
+}
+
+impl<W: Write> TraceSink for RingBufferSink<W> {
+    fn trace(&mut self, entry: &TraceEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(*entry);
+    }
+
+    fn error(&mut self, err: InstructionError) {
+        let _ = writeln!(self.out, "{}, last {} instructions:",
+                         err, self.entries.len());
+        self.dump();
+    }
+
+    fn finish(&mut self) {
+        self.dump();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Collect(Rc<RefCell<Vec<u16>>>);
+
+    impl TraceSink for Collect {
+        fn trace(&mut self, entry: &TraceEntry) {
+            self.0.borrow_mut().push(entry.pc);
+        }
+    }
+
+    fn entry(pc: u16, bits: u16) -> TraceEntry {
+        let regs = Registers { v: [0; 16], i: 0 };
+        TraceEntry { pc, opcode: Opcode::new(bits), before: regs, after: regs }
+    }
+
+    fn traced_pcs(filter: TraceFilter, entries: &[TraceEntry]) -> Vec<u16> {
+        let pcs = Rc::new(RefCell::new(Vec::new()));
+        let mut tracer = Tracer::new(Box::new(Collect(pcs.clone())), filter);
+        for e in entries {
+            tracer.record(e);
+        }
+        let pcs = pcs.borrow().clone();
+        pcs
+    }
+
+    #[test]
+    fn entry_shows_pc_opcode_mnemonic_and_registers() {
+        let mut e = entry(0x200, 0x6A25);
+        e.after.v[0xA] = 0x25;
+
+        let line = format!("{}", e);
+        assert!(line.starts_with("0200  6A25  LD VA, 0x25"));
+        assert!(line.ends_with("->  V 00 00 00 00 00 00 00 00 00 00 25 00 00 00 00 00 I 0000"));
+    }
+
+    #[test]
+    fn filter_address_range() {
+        let filter = TraceFilter { address_range: Some((0x202, 0x204)), ..TraceFilter::default() };
+        let entries = [entry(0x200, 0x00E0), entry(0x202, 0x00E0),
+                       entry(0x204, 0x00E0), entry(0x206, 0x00E0)];
+
+        assert_eq!(traced_pcs(filter, &entries), vec![0x202, 0x204]);
+    }
+
+    #[test]
+    fn filter_opcode_class() {
+        let filter = TraceFilter { opcode_classes: 1 << 0xD, ..TraceFilter::default() };
+        let entries = [entry(0x200, 0x6A25), entry(0x202, 0xD125), entry(0x204, 0x1200)];
+
+        assert_eq!(traced_pcs(filter, &entries), vec![0x202]);
+    }
+
+    #[test]
+    fn filter_first_instructions() {
+        let filter = TraceFilter { first: Some(2), ..TraceFilter::default() };
+        let entries = [entry(0x200, 0x00E0), entry(0x202, 0x00E0), entry(0x204, 0x00E0)];
+
+        assert_eq!(traced_pcs(filter, &entries), vec![0x200, 0x202]);
+    }
+
+    #[test]
+    fn ring_buffer_dumps_last_instructions_on_error() {
+        let mut out = Vec::new();
+        {
+            let mut sink = RingBufferSink::with_capacity(&mut out, 2);
+            sink.trace(&entry(0x200, 0x00E0));
+            sink.trace(&entry(0x202, 0x00E0));
+            sink.trace(&entry(0x204, 0x8AB8));
+            sink.error(InstructionError::Illegal(0x8AB8));
+        }
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "Illegal opcode 0x8AB8, last 2 instructions:");
+        assert!(lines[1].starts_with("0202"));
+        assert!(lines[2].starts_with("0204  8AB8  DW 0x8AB8"));
+    }
+}
@@ -0,0 +1,298 @@
+// Runs the test ROMs in tests/roms headlessly for a fixed number of frames
+// and checks the result screen each ROM draws. The expected screens are
+// built here from what the ROM sources say a pass looks like, not recorded
+// from this emulator, e.g. one digit per passing check. Screens are 32
+// lines of 64 characters, `#` for a lit pixel and `.` for a dark one.
+extern crate chip8core;
+extern crate chip8vm;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use chip8core::{ DebugVm, Key, Vm };
+use chip8vm::{ Cpu, Quirks, XorShift };
+#[cfg(feature = "jit")]
+use chip8vm::JitCpu;
+
+const FRAME: f64 = 1.0 / 60.0;
+
+type Preset = (&'static str, fn() -> Quirks);
+
+const PRESETS: [Preset; 4] = [
+    ("none", Quirks::default),
+    ("chip8", Quirks::chip8),
+    ("schip", Quirks::superchip),
+    ("xochip", Quirks::xochip),
+];
+
+// A key press (true) or release (false) applied before the given frame
+type Input = (u32, u8, bool);
+
+#[derive(Clone, Copy, Debug)]
+enum Backend {
+    Interpreter,
+    #[cfg(feature = "std")]
+    DecodeCache,
+    #[cfg(feature = "jit")]
+    Jit,
+}
+
+// Every backend has to produce the same screens as the interpreter
+#[cfg(not(feature = "std"))]
+const BACKENDS: [Backend; 1] = [Backend::Interpreter];
+#[cfg(all(feature = "std", not(feature = "jit")))]
+const BACKENDS: [Backend; 2] = [Backend::Interpreter, Backend::DecodeCache];
+#[cfg(feature = "jit")]
+const BACKENDS: [Backend; 3] = [Backend::Interpreter, Backend::DecodeCache, Backend::Jit];
+
+fn test_path(dir: &str, name: &str) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests");
+    path.push(dir);
+    path.push(name);
+    path
+}
+
+fn read_test_file(dir: &str, name: &str) -> Vec<u8> {
+    let path = test_path(dir, name);
+    let mut data = Vec::new();
+    File::open(&path).and_then(|mut f| f.read_to_end(&mut data))
+        .unwrap_or_else(|e| panic!("Can't read {}: {}", path.display(), e));
+    data
+}
+
+fn run_rom(rom: &str, quirks: Quirks, frames: u32, input: &[Input], backend: Backend)
+           -> String {
+    let data = read_test_file("roms", rom);
+
+    let mut cpu = Cpu::with_random(XorShift::new(1));
+    cpu.set_quirks(quirks);
+    cpu.load_rom_bytes(&data).unwrap();
+    run_cpu(cpu, rom, frames, input, backend)
+}
+
+// Runs a Cpu with its ROM loaded on the backend
+fn run_cpu(cpu: Cpu<XorShift>, rom: &str, frames: u32, input: &[Input], backend: Backend)
+           -> String {
+    match backend {
+        Backend::Interpreter => play(cpu, rom, frames, input),
+        #[cfg(feature = "std")]
+        Backend::DecodeCache => {
+            let mut cpu = cpu;
+            cpu.set_decode_cache(true);
+            play(cpu, rom, frames, input)
+        },
+        #[cfg(feature = "jit")]
+        Backend::Jit => play(JitCpu::from_cpu(cpu), rom, frames, input),
+    }
+}
+
+fn play<V: Vm>(mut cpu: V, rom: &str, frames: u32, input: &[Input]) -> String {
+    for frame in 0..frames {
+        for &(_, key, pressed) in input.iter().filter(|&&(f, _, _)| f == frame) {
+            let key = Key::from_index(key).unwrap();
+            if pressed {
+                cpu.press_key(key);
+            } else {
+                cpu.release_key(key);
+            }
+        }
+        cpu.step(FRAME).unwrap_or_else(|e| panic!("{} failed in frame {}: {:?}", rom, frame, e));
+    }
+
+    let mut screen = String::new();
+    for row in cpu.pixels() {
+        screen.extend(row.iter().map(|&p| if p { '#' } else { '.' }));
+        screen.push('\n');
+    }
+    screen
+}
+
+// The standard CHIP-8 font the ROMs draw their results with
+const DIGITS: [[u8; 5]; 16] = [
+    [0xF0, 0x90, 0x90, 0x90, 0xF0], [0x20, 0x60, 0x20, 0x20, 0x70],
+    [0xF0, 0x10, 0xF0, 0x80, 0xF0], [0xF0, 0x10, 0xF0, 0x10, 0xF0],
+    [0x90, 0x90, 0xF0, 0x10, 0x10], [0xF0, 0x80, 0xF0, 0x10, 0xF0],
+    [0xF0, 0x80, 0xF0, 0x90, 0xF0], [0xF0, 0x10, 0x20, 0x40, 0x40],
+    [0xF0, 0x90, 0xF0, 0x90, 0xF0], [0xF0, 0x90, 0xF0, 0x10, 0xF0],
+    [0xF0, 0x90, 0xF0, 0x90, 0x90], [0xE0, 0x90, 0xE0, 0x90, 0xE0],
+    [0xF0, 0x80, 0x80, 0x80, 0xF0], [0xE0, 0x90, 0x90, 0x90, 0xE0],
+    [0xF0, 0x80, 0xF0, 0x80, 0xF0], [0xF0, 0x80, 0xF0, 0x80, 0x80],
+];
+
+struct Screen {
+    rows: Vec<Vec<bool>>,
+}
+
+impl Screen {
+    fn blank() -> Screen {
+        Screen { rows: vec![vec![false; 64]; 32] }
+    }
+
+    fn draw(&mut self, x: usize, y: usize, sprite: &[u8]) {
+        for (dy, byte) in sprite.iter().enumerate() {
+            for dx in 0..8 {
+                if byte & (0x80 >> dx) != 0 {
+                    self.rows[y + dy][x + dx] = true;
+                }
+            }
+        }
+    }
+
+    fn digit(&mut self, x: usize, y: usize, digit: usize) {
+        self.draw(x, y, &DIGITS[digit % 16]);
+    }
+
+    fn text(&self) -> String {
+        let mut text = String::new();
+        for row in self.rows.iter() {
+            text.extend(row.iter().map(|&p| if p { '#' } else { '.' }));
+            text.push('\n');
+        }
+        text
+    }
+}
+
+// The opcode and flags ROMs draw the number of every passing check in a
+// grid of 12 columns, 5 pixels apart, with rows 6 pixels apart
+fn check_cell(check: usize) -> (usize, usize) {
+    (check % 12 * 5, check / 12 * 6)
+}
+
+fn passing_checks(checks: usize) -> Screen {
+    let mut screen = Screen::blank();
+    for check in 0..checks {
+        let (x, y) = check_cell(check);
+        screen.digit(x, y, check);
+    }
+    screen
+}
+
+// Runs the ROM on every backend, they all have to agree
+fn result_screen(rom: &str, quirks: Quirks, frames: u32, input: &[Input]) -> String {
+    let screen = run_rom(rom, quirks, frames, input, Backend::Interpreter);
+    for &backend in BACKENDS[1..].iter() {
+        let other = run_rom(rom, quirks, frames, input, backend);
+        assert_eq!(screen, other, "{} differs with backend {:?}", rom, backend);
+    }
+    screen
+}
+
+fn report(rom: &str, quirks: Quirks, expected: &str, screen: &str) -> String {
+    let mut report = format!("{} with {:?} doesn't show a pass\n", rom, quirks);
+    for (n, (exp, got)) in expected.lines().zip(screen.lines()).enumerate() {
+        let marker = if exp == got { ' ' } else { '>' };
+        report.push_str(&format!("{} {:2}  {}  {}\n", marker, n, exp, got));
+    }
+    report
+}
+
+// Names the checks whose digit is missing or wrong
+fn check_grid(rom: &str, checks: usize, quirks: Quirks) {
+    let screen = result_screen(rom, quirks, 60, &[]);
+    let expected = passing_checks(checks).text();
+    if screen == expected {
+        return;
+    }
+
+    let lines: Vec<&str> = screen.lines().collect();
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let failed: Vec<usize> = (0..checks).filter(|&check| {
+        let (x, y) = check_cell(check);
+        (y..y + 5).any(|row| lines[row][x..x + 4] != expected_lines[row][x..x + 4])
+    }).collect();
+    panic!("Failing checks {:?}\n{}", failed, report(rom, quirks, &expected, &screen));
+}
+
+fn check_screen(rom: &str, expected: &Screen, quirks: Quirks, frames: u32, input: &[Input]) {
+    let screen = result_screen(rom, quirks, frames, input);
+    let expected = expected.text();
+    assert!(screen == expected, "{}", report(rom, quirks, &expected, &screen));
+}
+
+#[test]
+fn opcode_rom_passes_on_every_platform() {
+    for &(_, quirks) in PRESETS.iter() {
+        check_grid("opcode.ch8", 25, quirks());
+    }
+}
+
+#[test]
+fn flags_rom_passes_on_every_platform() {
+    for &(_, quirks) in PRESETS.iter() {
+        check_grid("flags.ch8", 16, quirks());
+    }
+}
+
+#[test]
+fn quirks_rom_detects_preset() {
+    for &(_, preset) in PRESETS.iter() {
+        let quirks = preset();
+        // In the order of the rows in quirks.asm
+        let detected = [
+            quirks.logic_resets_vf,
+            quirks.load_store_increments_i,
+            quirks.shift_uses_vy,
+            quirks.clip_sprites,
+            quirks.jump_uses_vx,
+        ];
+
+        let mut expected = Screen::blank();
+        for (row, &active) in detected.iter().enumerate() {
+            expected.digit(0, row * 6, row + 1);
+            expected.digit(8, row * 6, active as usize);
+        }
+        check_screen("quirks.ch8", &expected, quirks, 30, &[]);
+    }
+}
+
+#[test]
+fn keypad_rom_follows_key_presses() {
+    let input = [
+        (5, 0xA, true),
+        (10, 0xA, false),
+        (15, 0x3, true),
+        (20, 0x3, false),
+        (25, 0x7, true),
+    ];
+    // The third press, of key 7, is still held so there is no bar
+    let mut expected = Screen::blank();
+    expected.digit(0, 0, 7);
+    expected.digit(8, 0, 3);
+    check_screen("keypad.ch8", &expected, Quirks::chip8(), 40, &input);
+}
+
+// Timendus' CHIP-8 test suite, which includes the corax+ opcode test. The
+// ROMs and their license aren't vendored yet, see tests/roms/README.md.
+// The quirks and keypad ROMs skip their menu when 0x1FF holds the choice.
+const COMMUNITY: [(&str, u8); 6] = [
+    ("3-corax+.ch8", 0),
+    ("4-flags.ch8", 0),
+    ("5-quirks.ch8", 1),
+    ("6-keypad.ch8", 1),
+    ("6-keypad.ch8", 2),
+    ("6-keypad.ch8", 3),
+];
+
+// The screen each ROM shows for a pass is kept next to it as `.pass` text,
+// copied from the suite's documentation rather than from this emulator
+#[test]
+#[ignore = "the community ROMs aren't vendored yet, see tests/roms/README.md"]
+fn community_roms_pass() {
+    for &(rom, choice) in COMMUNITY.iter() {
+        let data = read_test_file("roms/community", rom);
+        let pass_file = format!("{}.{}.pass", rom.trim_end_matches(".ch8"), choice);
+        let expected = String::from_utf8(read_test_file("roms/community", &pass_file)).unwrap();
+
+        let mut screens = BACKENDS.iter().map(|&backend| {
+            let mut cpu = Cpu::with_random(XorShift::new(1));
+            cpu.set_quirks(Quirks::chip8());
+            cpu.load_rom_bytes(&data).unwrap();
+            cpu.write_memory(0x1FF, &[choice]).unwrap();
+            run_cpu(cpu, rom, 600, &[], backend)
+        });
+        let screen = screens.next().unwrap();
+        assert!(screens.all(|other| other == screen), "{} differs between backends", rom);
+        assert!(screen == expected, "{}", report(rom, Quirks::chip8(), &expected, &screen));
+    }
+}
@@ -0,0 +1,195 @@
+// Runs ROMs instruction by instruction and compares the machine state after
+// every instruction against a golden trace. The traces are written by
+// tests/golden/reference.py, a separate model of the original CHIP-8 that
+// shares no code with Cpu, and the ROM sources sit next to them as `.asm`.
+// Golden traces live in tests/golden as `<rom>.trace`, one instruction per
+// line:
+//
+//     <step> <pc> <opcode> <next pc> <i> <v0..vf> <framebuffer>
+//
+// All values are hex. `pc` and `opcode` describe the executed instruction,
+// the other fields the state after it. V0-VF are written as one 32 digit
+// string and the framebuffer as the 64-bit FNV-1a hash of its 64x32 pixels,
+// one byte (0 or 1) per pixel, row by row. Lines starting with `#` are
+// comments.
+extern crate chip8core;
+extern crate chip8vm;
+
+use std::fmt::Write;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use chip8core::Vm;
+use chip8core::InstructionError;
+use chip8vm::{ Cpu, Disassembly, Opcode, XorShift };
+#[cfg(feature = "jit")]
+use chip8vm::JitCpu;
+
+const CONTEXT: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct State {
+    pc: u16,
+    opcode: u16,
+    next_pc: u16,
+    i: u16,
+    v: [u8; 16],
+    fb: u64,
+}
+
+// The backends a trace is checked against
+trait Backend: Vm {
+    fn cpu(&self) -> &Cpu<XorShift>;
+    fn step_instruction(&mut self) -> Result<(), InstructionError>;
+}
+
+impl Backend for Cpu<XorShift> {
+    fn cpu(&self) -> &Cpu<XorShift> {
+        self
+    }
+
+    fn step_instruction(&mut self) -> Result<(), InstructionError> {
+        Cpu::step_instruction(self)
+    }
+}
+
+#[cfg(feature = "jit")]
+impl Backend for JitCpu<XorShift> {
+    fn cpu(&self) -> &Cpu<XorShift> {
+        self
+    }
+
+    fn step_instruction(&mut self) -> Result<(), InstructionError> {
+        JitCpu::step_instruction(self)
+    }
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests");
+    path.push("golden");
+    path.push(name);
+    path
+}
+
+fn read_file(name: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    File::open(golden_path(name)).and_then(|mut f| f.read_to_end(&mut data)).unwrap();
+    data
+}
+
+fn parse_golden(text: &str) -> Vec<State> {
+    let hex16 = |s: &str| u16::from_str_radix(s, 16).unwrap();
+
+    text.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            assert_eq!(fields.len(), 7, "Malformed golden trace line: {}", line);
+
+            let mut v = [0; 16];
+            for (n, reg) in v.iter_mut().enumerate() {
+                *reg = u8::from_str_radix(&fields[5][(n * 2)..(n * 2 + 2)], 16).unwrap();
+            }
+
+            State {
+                pc: hex16(fields[1]),
+                opcode: hex16(fields[2]),
+                next_pc: hex16(fields[3]),
+                i: hex16(fields[4]),
+                v,
+                fb: u64::from_str_radix(fields[6], 16).unwrap(),
+            }
+        })
+        .collect()
+}
+
+fn framebuffer_hash(cpu: &Cpu<XorShift>) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for row in cpu.pixels() {
+        for &on in row {
+            hash ^= on as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+fn format_state(s: &State) -> String {
+    let v: String = s.v.iter().map(|r| format!("{:02X}", r)).collect();
+    format!("{:04X} {:04X} {:04X} {:04X} {} {:016X}", s.pc, s.opcode, s.next_pc, s.i, v, s.fb)
+}
+
+fn disassemble_around(mem: &[u8], pc: u16) -> String {
+    let mut out = String::new();
+    let start = (pc as usize).saturating_sub(CONTEXT * 2);
+    let end = ((pc as usize) + CONTEXT * 2 + 2).min(mem.len() - 1);
+
+    for addr in (start..end).step_by(2) {
+        let opcode = Opcode::new((mem[addr] as u16) << 8 | mem[addr + 1] as u16);
+        let marker = if addr == pc as usize { ">" } else { " " };
+        writeln!(out, "  {} {:04X}  {:04X}  {}", marker, addr, opcode.bits(),
+                 Disassembly(opcode)).unwrap();
+    }
+    out
+}
+
+fn run_differential<B: Backend>(mut vm: B, rom: &str, golden: &str) {
+    let expected = parse_golden(&String::from_utf8(read_file(golden)).unwrap());
+    vm.load_rom_bytes(&read_file(rom)).unwrap();
+
+    let mut history: Vec<State> = Vec::new();
+    for (step, want) in expected.iter().enumerate() {
+        let pc = vm.cpu().pc();
+        let opcode = (vm.cpu().mem()[pc as usize] as u16) << 8 |
+                     vm.cpu().mem()[pc as usize + 1] as u16;
+        let result = vm.step_instruction();
+        let cpu = vm.cpu();
+
+        let got = State {
+            pc,
+            opcode,
+            next_pc: cpu.pc(),
+            i: cpu.i(),
+            v: *cpu.v(),
+            fb: framebuffer_hash(cpu),
+        };
+
+        if result.is_err() || got != *want {
+            let mut report = String::new();
+            writeln!(report, "{} diverges from {} at step {}", rom, golden, step).unwrap();
+            if let Err(err) = result {
+                writeln!(report, "  instruction failed: {:?}", err).unwrap();
+            }
+            writeln!(report, "  expected {}", format_state(want)).unwrap();
+            writeln!(report, "  got      {}", format_state(&got)).unwrap();
+            writeln!(report, "Previous steps:").unwrap();
+            for s in history.iter().rev().take(CONTEXT).rev() {
+                writeln!(report, "    {}  {}", format_state(s),
+                         Disassembly(Opcode::new(s.opcode))).unwrap();
+            }
+            writeln!(report, "Code around {:04X}:", pc).unwrap();
+            report.push_str(&disassemble_around(cpu.mem(), pc));
+            panic!("{}", report);
+        }
+
+        history.push(got);
+    }
+}
+
+#[test]
+fn arith_matches_golden_trace() {
+    run_differential(Cpu::with_random(XorShift::new(1)), "arith.ch8", "arith.trace");
+}
+
+#[test]
+#[should_panic(expected = "diverges from arith_diverged.trace at step 3")]
+fn divergence_reports_first_mismatch() {
+    run_differential(Cpu::with_random(XorShift::new(1)), "arith.ch8", "arith_diverged.trace");
+}
+
+#[cfg(feature = "jit")]
+#[test]
+fn jit_matches_golden_trace() {
+    run_differential(JitCpu::with_random(XorShift::new(1)), "arith.ch8", "arith.trace");
+}
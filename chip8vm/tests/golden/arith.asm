@@ -0,0 +1,40 @@
+; Differential test: ALU ops and their flags, BCD, the font, DXYN with
+; collisions, a subroutine and the three kinds of jump. Runs 34 steps into
+; the loop at 23E. The golden trace comes from reference.py.
+        CLS
+        LD VA, 0x25
+        LD VB, 0xF0
+        ADD VA, VB          ; carry
+        SUB VA, VB          ; borrow
+        LD VC, 0x07
+        OR VA, VC
+        AND VA, VC
+        XOR VA, VB
+        ADD VA, 0x0A
+        SUBN VB, VA
+        SE VB, 0x11
+        JP 0x300            ; only reached when SUBN is wrong
+        LD VD, 0x9C
+        LD I, 0x400
+        LD B, VD
+        LD V2, [I]
+        LD F, V0
+        LD V3, 0x10
+        LD V4, 0x08
+        DRW V3, V4, 5
+        DRW V3, V4, 5       ; erases, collision
+        DRW V3, V4, 5
+        CALL sub
+        SE V3, V4
+        SNE V3, V4
+        JP 0x234            ; skipped
+        LD V0, 0x02
+        JP V0, 0x23C        ; lands on done only when V0 is added
+        JP 0x234
+        JP 0x234
+done:   JP done
+
+; At 0x260, the bytes in between are zero
+sub:    ADD VE, 0x01
+        ADD I, VE
+        RET
@@ -0,0 +1,155 @@
+#!/usr/bin/env python3
+# A minimal CHIP-8 model written from Cowgod's technical reference, kept
+# apart from the Rust code so golden traces don't come from the emulator
+# they test. It covers the instructions the golden ROMs use, with the
+# original behaviour: shifts and logic ops leave VF alone apart from the
+# flag, FX55/FX65 leave I unchanged, sprites wrap and BNNN adds V0.
+#
+# Writes a trace in the format tests/differential.rs reads:
+#
+#     python3 reference.py arith.ch8 34 > arith.trace
+import sys
+
+FONT = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x20, 0x70,
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, 0xF0, 0x10, 0xF0, 0x10, 0xF0,
+    0x90, 0x90, 0xF0, 0x10, 0x10, 0xF0, 0x80, 0xF0, 0x10, 0xF0,
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, 0xF0, 0x10, 0x20, 0x40, 0x40,
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, 0xF0, 0x90, 0xF0, 0x10, 0xF0,
+    0xF0, 0x90, 0xF0, 0x90, 0x90, 0xE0, 0x90, 0xE0, 0x90, 0xE0,
+    0xF0, 0x80, 0x80, 0x80, 0xF0, 0xE0, 0x90, 0x90, 0x90, 0xE0,
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80,
+]
+
+
+class Chip8:
+    def __init__(self, rom):
+        self.mem = bytearray(4096)
+        self.mem[0:len(FONT)] = bytes(FONT)
+        self.mem[0x200:0x200 + len(rom)] = rom
+        self.v = [0] * 16
+        self.i = 0
+        self.pc = 0x200
+        self.stack = []
+        self.fb = [[0] * 64 for _ in range(32)]
+
+    def step(self):
+        op = self.mem[self.pc] << 8 | self.mem[self.pc + 1]
+        self.pc += 2
+        x, y = op >> 8 & 0xF, op >> 4 & 0xF
+        n, nn, nnn = op & 0xF, op & 0xFF, op & 0xFFF
+        v = self.v
+
+        if op == 0x00E0:
+            self.fb = [[0] * 64 for _ in range(32)]
+        elif op == 0x00EE:
+            self.pc = self.stack.pop()
+        elif op >> 12 == 0x1:
+            self.pc = nnn
+        elif op >> 12 == 0x2:
+            self.stack.append(self.pc)
+            self.pc = nnn
+        elif op >> 12 == 0x3:
+            if v[x] == nn:
+                self.pc += 2
+        elif op >> 12 == 0x4:
+            if v[x] != nn:
+                self.pc += 2
+        elif op >> 12 == 0x5 and n == 0:
+            if v[x] == v[y]:
+                self.pc += 2
+        elif op >> 12 == 0x6:
+            v[x] = nn
+        elif op >> 12 == 0x7:
+            v[x] = (v[x] + nn) & 0xFF
+        elif op >> 12 == 0x8:
+            self.alu(x, y, n)
+        elif op >> 12 == 0x9 and n == 0:
+            if v[x] != v[y]:
+                self.pc += 2
+        elif op >> 12 == 0xA:
+            self.i = nnn
+        elif op >> 12 == 0xB:
+            self.pc = nnn + v[0]
+        elif op >> 12 == 0xD:
+            self.draw(v[x], v[y], n)
+        elif op >> 12 == 0xF and nn == 0x1E:
+            self.i = (self.i + v[x]) & 0xFFFF
+        elif op >> 12 == 0xF and nn == 0x29:
+            self.i = (v[x] & 0xF) * 5
+        elif op >> 12 == 0xF and nn == 0x33:
+            self.mem[self.i:self.i + 3] = bytes([v[x] // 100, v[x] // 10 % 10, v[x] % 10])
+        elif op >> 12 == 0xF and nn == 0x55:
+            self.mem[self.i:self.i + x + 1] = bytes(v[:x + 1])
+        elif op >> 12 == 0xF and nn == 0x65:
+            v[:x + 1] = list(self.mem[self.i:self.i + x + 1])
+        else:
+            raise ValueError("no reference for opcode %04X" % op)
+        return op
+
+    # The flag is written last, so VF as VX ends up holding the flag
+    def alu(self, x, y, n):
+        v = self.v
+        vx, vy = v[x], v[y]
+        if n == 0x0:
+            v[x] = vy
+        elif n == 0x1:
+            v[x] = vx | vy
+        elif n == 0x2:
+            v[x] = vx & vy
+        elif n == 0x3:
+            v[x] = vx ^ vy
+        elif n == 0x4:
+            v[x] = (vx + vy) & 0xFF
+            v[0xF] = int(vx + vy > 0xFF)
+        elif n == 0x5:
+            v[x] = (vx - vy) & 0xFF
+            v[0xF] = int(vx >= vy)
+        elif n == 0x6:
+            v[x] = vx >> 1
+            v[0xF] = vx & 1
+        elif n == 0x7:
+            v[x] = (vy - vx) & 0xFF
+            v[0xF] = int(vy >= vx)
+        elif n == 0xE:
+            v[x] = vx << 1 & 0xFF
+            v[0xF] = vx >> 7
+        else:
+            raise ValueError("no reference for opcode 8%X%X%X" % (x, y, n))
+
+    def draw(self, vx, vy, n):
+        self.v[0xF] = 0
+        for row in range(n):
+            byte = self.mem[self.i + row]
+            for col in range(8):
+                if byte & 0x80 >> col:
+                    px, py = (vx + col) % 64, (vy + row) % 32
+                    if self.fb[py][px]:
+                        self.v[0xF] = 1
+                    self.fb[py][px] ^= 1
+
+    # FNV-1a over one byte per pixel, row by row
+    def fb_hash(self):
+        h = 0xcbf29ce484222325
+        for row in self.fb:
+            for p in row:
+                h = ((h ^ p) * 0x100000001b3) & 0xFFFFFFFFFFFFFFFF
+        return h
+
+
+def main():
+    path, steps = sys.argv[1], int(sys.argv[2])
+    with open(path, "rb") as f:
+        chip8 = Chip8(f.read())
+    print("# Generated with python3 reference.py %s %d" % (path, steps))
+    print("# step pc opcode next_pc i v0..vf framebuffer")
+    for step in range(steps):
+        pc = chip8.pc
+        op = chip8.step()
+        regs = "".join("%02X" % r for r in chip8.v)
+        print("%d %04X %04X %04X %04X %s %016X" % (step, pc, op, chip8.pc, chip8.i, regs,
+                                                  chip8.fb_hash()))
+
+
+if __name__ == "__main__":
+    main()
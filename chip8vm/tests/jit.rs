@@ -0,0 +1,206 @@
+// Checks the JIT against the interpreter. Random programs are run on both
+// frame by frame and the machine state has to match after every frame,
+// including when an instruction fails or panics.
+#![cfg(feature = "jit")]
+extern crate proptest;
+extern crate chip8core;
+extern crate chip8vm;
+
+use std::panic::{ self, AssertUnwindSafe };
+use chip8core::{ DebugVm, Vm };
+use chip8vm::{ Cpu, JitCpu, Quirks, XorShift };
+use proptest::prelude::*;
+
+const FRAME: f64 = 1.0 / 60.0;
+
+#[derive(Debug, PartialEq)]
+struct State {
+    pc: u16,
+    i: u16,
+    v: [u8; 16],
+    mem: Vec<u8>,
+    screen: Vec<bool>,
+}
+
+fn state(cpu: &Cpu<XorShift>) -> State {
+    State {
+        pc: cpu.pc(),
+        i: cpu.i(),
+        v: *cpu.v(),
+        mem: cpu.mem().to_vec(),
+        screen: cpu.pixels().flat_map(|row| row.to_vec()).collect(),
+    }
+}
+
+fn rom(ops: &[u16]) -> Vec<u8> {
+    ops.iter().flat_map(|op| vec![(op >> 8) as u8, *op as u8]).collect()
+}
+
+// Steps one frame, turning a panic into its message
+fn frame<V: Vm>(vm: &mut V) -> Result<(), String> {
+    panic::catch_unwind(AssertUnwindSafe(|| vm.step(FRAME)))
+        .map_err(|_| "panicked".to_string())?
+        .map_err(|e| format!("{:?}", e))
+}
+
+fn compare(ops: &[u16], quirks: Quirks, frames: u32) -> Result<(), TestCaseError> {
+    let mut cpu = Cpu::with_random(XorShift::new(1));
+    let mut jit = JitCpu::with_random(XorShift::new(1));
+    cpu.set_quirks(quirks);
+    jit.set_quirks(quirks);
+    cpu.load_rom_bytes(&rom(ops)).unwrap();
+    jit.load_rom_bytes(&rom(ops)).unwrap();
+
+    for n in 0..frames {
+        let expected = frame(&mut cpu);
+        let got = frame(&mut jit);
+        prop_assert_eq!(&got, &expected, "result of frame {}", n);
+        prop_assert!(state(&jit) == state(&cpu), "state differs after frame {}", n);
+        if expected.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn quirks() -> impl Strategy<Value = Quirks> {
+    prop_oneof![
+        Just(Quirks::default()),
+        Just(Quirks::chip8()),
+        Just(Quirks::superchip()),
+        Just(Quirks::xochip()),
+    ]
+}
+
+// Mostly instructions the JIT compiles, with jumps and calls kept inside
+// the program and I pointing above it so most programs run for a while
+fn opcode(len: u16) -> impl Strategy<Value = u16> {
+    let target = (0..len).prop_map(|n| 0x200 + n * 2);
+    prop_oneof![
+        4 => (0u16..16, any::<u8>()).prop_map(|(x, b)| 0x6000 | x << 8 | b as u16),
+        4 => (0u16..16, any::<u8>()).prop_map(|(x, b)| 0x7000 | x << 8 | b as u16),
+        8 => (0u16..16, 0u16..16, prop::sample::select(vec![0, 1, 2, 3, 4, 5, 6, 7, 0xE]))
+            .prop_map(|(x, y, n)| 0x8000 | x << 8 | y << 4 | n),
+        3 => (0u16..16, any::<u8>(), prop::sample::select(vec![0x3000u16, 0x4000]))
+            .prop_map(|(x, b, op)| op | x << 8 | b as u16),
+        3 => (0u16..16, 0u16..16, prop::sample::select(vec![0x5000u16, 0x9000]))
+            .prop_map(|(x, y, op)| op | x << 8 | y << 4),
+        2 => (0x300u16..0x400).prop_map(|addr| 0xA000 | addr),
+        2 => target.clone().prop_map(|addr| 0x1000 | addr),
+        1 => target.prop_map(|addr| 0x2000 | addr),
+        1 => Just(0x00EE),
+        1 => Just(0x00E0),
+        2 => (0u16..16, 0u16..16, 0u16..16).prop_map(|(x, y, n)| 0xD000 | x << 8 | y << 4 | n),
+        1 => (0u16..16, any::<u8>()).prop_map(|(x, b)| 0xC000 | x << 8 | b as u16),
+        3 => (0u16..16, prop::sample::select(vec![0x07u16, 0x15, 0x18, 0x1E, 0x29, 0x33, 0x55,
+                                                   0x65]))
+            .prop_map(|(x, n)| 0xF000 | x << 8 | n),
+        1 => any::<u16>(),
+    ]
+}
+
+fn program() -> impl Strategy<Value = Vec<u16>> {
+    (1u16..48).prop_flat_map(|len| prop::collection::vec(opcode(len), len as usize))
+}
+
+proptest! {
+    #[test]
+    fn jit_matches_interpreter(ops in program(), quirks in quirks()) {
+        compare(&ops, quirks, 10)?;
+    }
+}
+
+// A loop that increments the byte of its own `LD V0, NN` every iteration
+const SELF_MODIFYING: [u16; 7] = [
+    0x6000, // 200: LD V0, 0
+    0x7101, // 202: ADD V1, 1
+    0xA201, // 204: LD I, 201
+    0x8010, // 206: LD V0, V1
+    0xF055, // 208: LD [I], V0
+    0x1200, // 20A: JP 200
+    0x0000,
+];
+
+#[test]
+fn self_modifying_code_matches_interpreter() {
+    compare(&SELF_MODIFYING, Quirks::default(), 30).unwrap();
+}
+
+#[test]
+fn self_modifying_code_falls_back_to_interpreter() {
+    let mut jit = JitCpu::with_random(XorShift::new(1));
+    jit.load_rom_bytes(&rom(&SELF_MODIFYING)).unwrap();
+    for _ in 0..30 {
+        jit.step(FRAME).unwrap();
+    }
+
+    // The block starting at the rewritten instruction is given up on, the
+    // one after it stays compiled
+    assert!(!jit.is_compiled(0x200));
+    assert!(jit.is_compiled(0x202));
+}
+
+#[test]
+fn loading_a_rom_drops_compiled_blocks() {
+    let mut jit = JitCpu::with_random(XorShift::new(1));
+    jit.load_rom_bytes(&rom(&[0x6005, 0x1200])).unwrap();
+    jit.step(FRAME).unwrap();
+    assert_eq!(jit.v()[0], 5);
+
+    jit.load_rom_bytes(&rom(&[0x6007, 0x1200])).unwrap();
+    assert!(!jit.is_compiled(0x200));
+    jit.step(FRAME).unwrap();
+    assert_eq!(jit.v()[0], 7);
+}
+
+#[test]
+fn debugger_writes_drop_compiled_blocks() {
+    let mut jit = JitCpu::with_random(XorShift::new(1));
+    jit.load_rom_bytes(&rom(&[0x6005, 0x1200])).unwrap();
+    jit.step(FRAME).unwrap();
+    let state = jit.save_state();
+
+    jit.write_memory(0x201, &[0x07]).unwrap();
+    assert!(!jit.is_compiled(0x200));
+    jit.step(FRAME).unwrap();
+    assert_eq!(jit.v()[0], 7);
+
+    jit.step(FRAME).unwrap();
+    jit.load_state(&state).unwrap();
+    assert!(!jit.is_compiled(0x200));
+    jit.step(FRAME).unwrap();
+    assert_eq!(jit.v()[0], 5);
+}
+
+#[test]
+fn writes_outside_a_block_or_of_the_same_bytes_keep_it() {
+    let mut jit = JitCpu::with_random(XorShift::new(1));
+    jit.load_rom_bytes(&rom(&[0x6005, 0x1200])).unwrap();
+    // Both blocks of the loop are compiled by the second frame
+    jit.step(FRAME).unwrap();
+    jit.step(FRAME).unwrap();
+    let compiled = jit.compiled_blocks();
+
+    jit.write_memory(0x300, &[0x09]).unwrap();
+    jit.write_memory(0x1FF, &[0xAA]).unwrap();
+    jit.write_memory(0x200, &[0x60, 0x05]).unwrap();
+    assert!(jit.is_compiled(0x200));
+    jit.step(FRAME).unwrap();
+    assert_eq!(jit.compiled_blocks(), compiled);
+
+    jit.write_memory(0x1FF, &[0x00, 0x61]).unwrap();
+    assert!(!jit.is_compiled(0x200));
+}
+
+#[test]
+fn errors_stop_the_frame() {
+    let mut jit = JitCpu::with_random(XorShift::new(1));
+    let mut cpu = Cpu::with_random(XorShift::new(1));
+    // LD HF, V0 without a big font, after a compiled block
+    let ops = [0x6001, 0x6102, 0xF030];
+    jit.load_rom_bytes(&rom(&ops)).unwrap();
+    cpu.load_rom_bytes(&rom(&ops)).unwrap();
+
+    assert_eq!(format!("{:?}", jit.step(FRAME)), format!("{:?}", cpu.step(FRAME)));
+    assert_eq!(state(&jit), state(&cpu));
+}
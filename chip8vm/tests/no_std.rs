@@ -0,0 +1,30 @@
+use std::env;
+use std::process::Command;
+
+const TARGET: &str = "thumbv7em-none-eabi";
+
+fn target_installed() -> bool {
+    Command::new("rustup")
+        .args(["target", "list", "--installed"])
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).lines().any(|t| t.trim() == TARGET))
+        .unwrap_or(false)
+}
+
+#[test]
+fn builds_for_bare_metal_target() {
+    if !target_installed() {
+        println!("skipping, {} is not installed", TARGET);
+        return;
+    }
+
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .current_dir(manifest_dir)
+        .args(["build", "--lib", "--no-default-features", "--target", TARGET])
+        .env("CARGO_TARGET_DIR", format!("{}/target/no_std", manifest_dir))
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+}
@@ -0,0 +1,197 @@
+// Property based tests for the instruction handlers. Every case builds a
+// small program that sets up registers with 6XNN, runs the instruction
+// under test and checks the result through Cpu's public accessors.
+extern crate proptest;
+extern crate chip8core;
+extern crate chip8vm;
+
+use chip8core::Vm;
+use chip8vm::{ Cpu, LoadOptions, Quirks, XorShift };
+use proptest::prelude::*;
+
+const SPRITE: u16 = 0x800;
+const BACKGROUND: u16 = 0x900;
+const SCRATCH: u16 = 0xA00;
+
+fn set(x: u8, byte: u8) -> u16 {
+    0x6000 | (x as u16) << 8 | byte as u16
+}
+
+fn alu(x: u8, y: u8, n: u16) -> u16 {
+    0x8000 | (x as u16) << 8 | (y as u16) << 4 | n
+}
+
+fn run_with(ops: &[u16], quirks: Quirks, preload: &[(u16, &[u8])]) -> Cpu<XorShift> {
+    let rom: Vec<u8> = ops.iter().flat_map(|op| vec![(op >> 8) as u8, *op as u8]).collect();
+    let options = LoadOptions { preload, ..LoadOptions::default() };
+
+    let mut cpu = Cpu::with_random(XorShift::new(1));
+    cpu.set_quirks(quirks);
+    cpu.load_rom_bytes_with(&rom, &options).unwrap();
+    for _ in ops {
+        cpu.step_instruction().unwrap();
+    }
+    cpu
+}
+
+fn run(ops: &[u16]) -> Cpu<XorShift> {
+    run_with(ops, Quirks::default(), &[])
+}
+
+fn screen(cpu: &Cpu<XorShift>) -> Vec<bool> {
+    cpu.pixels().flat_map(|row| row.to_vec()).collect()
+}
+
+fn quirks() -> impl Strategy<Value = Quirks> {
+    prop_oneof![
+        Just(Quirks::default()),
+        Just(Quirks::chip8()),
+        Just(Quirks::superchip()),
+        Just(Quirks::xochip()),
+    ]
+}
+
+// Two distinct registers, neither of them VF
+fn operands() -> impl Strategy<Value = (u8, u8)> {
+    (0u8..15, 0u8..15).prop_filter("distinct registers", |&(x, y)| x != y)
+}
+
+// Register values, equal often enough to exercise the no borrow edge case
+fn values() -> impl Strategy<Value = (u8, u8)> {
+    prop_oneof![
+        any::<(u8, u8)>(),
+        any::<u8>().prop_map(|a| (a, a)),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn add_sets_vf_on_carry((x, y) in operands(), a: u8, b: u8) {
+        let cpu = run(&[set(x, a), set(y, b), alu(x, y, 0x4)]);
+
+        prop_assert_eq!(cpu.v()[x as usize], a.wrapping_add(b));
+        prop_assert_eq!(cpu.v()[y as usize], b);
+        prop_assert_eq!(cpu.v()[0xF], (a as u16 + b as u16 > 0xFF) as u8);
+    }
+
+    #[test]
+    fn sub_clears_vf_on_borrow((x, y) in operands(), (a, b) in values()) {
+        let cpu = run(&[set(x, a), set(y, b), alu(x, y, 0x5)]);
+
+        prop_assert_eq!(cpu.v()[x as usize], a.wrapping_sub(b));
+        prop_assert_eq!(cpu.v()[0xF], (a >= b) as u8);
+    }
+
+    #[test]
+    fn subn_clears_vf_on_borrow((x, y) in operands(), (a, b) in values()) {
+        let cpu = run(&[set(x, a), set(y, b), alu(x, y, 0x7)]);
+
+        prop_assert_eq!(cpu.v()[x as usize], b.wrapping_sub(a));
+        prop_assert_eq!(cpu.v()[0xF], (b >= a) as u8);
+    }
+
+    #[test]
+    fn shift_right_moves_lsb_into_vf((x, y) in operands(), a: u8, b: u8, quirks in quirks()) {
+        let cpu = run_with(&[set(x, a), set(y, b), alu(x, y, 0x6)], quirks, &[]);
+
+        let src = if quirks.shift_uses_vy { b } else { a };
+        prop_assert_eq!(cpu.v()[x as usize], src >> 1);
+        prop_assert_eq!(cpu.v()[0xF], src & 1);
+    }
+
+    #[test]
+    fn shift_left_moves_msb_into_vf((x, y) in operands(), a: u8, b: u8, quirks in quirks()) {
+        let cpu = run_with(&[set(x, a), set(y, b), alu(x, y, 0xE)], quirks, &[]);
+
+        let src = if quirks.shift_uses_vy { b } else { a };
+        prop_assert_eq!(cpu.v()[x as usize], src << 1);
+        prop_assert_eq!(cpu.v()[0xF], src >> 7);
+    }
+
+    // With VF as the destination the flag wins over the result
+    #[test]
+    fn arithmetic_into_vf_keeps_flag(y in 0u8..15, (a, b) in values(), n in prop_oneof![
+        Just(0x4u16), Just(0x5), Just(0x7)
+    ]) {
+        let cpu = run(&[set(0xF, a), set(y, b), alu(0xF, y, n)]);
+
+        let flag = match n {
+            0x4 => a as u16 + b as u16 > 0xFF,
+            0x5 => a >= b,
+            _ => b >= a,
+        };
+        prop_assert_eq!(cpu.v()[0xF], flag as u8);
+    }
+
+    #[test]
+    fn logic_ops_reset_vf_only_with_quirk((x, y) in operands(), a: u8, b: u8, f: u8,
+                                          n in 1u16..4, quirks in quirks()) {
+        let cpu = run_with(&[set(x, a), set(y, b), set(0xF, f), alu(x, y, n)], quirks, &[]);
+
+        let expected = match n {
+            1 => a | b,
+            2 => a & b,
+            _ => a ^ b,
+        };
+        prop_assert_eq!(cpu.v()[x as usize], expected);
+        prop_assert_eq!(cpu.v()[0xF], if quirks.logic_resets_vf { 0 } else { f });
+    }
+
+    #[test]
+    fn store_bcd_digits_recombine_to_vx(x in 0u8..16, a: u8) {
+        let cpu = run(&[set(x, a), 0xA000 | SCRATCH, 0xF033 | (x as u16) << 8]);
+
+        let digits = &cpu.mem()[(SCRATCH as usize)..(SCRATCH as usize + 3)];
+        prop_assert!(digits.iter().all(|&d| d < 10));
+        let value = digits[0] as u16 * 100 + digits[1] as u16 * 10 + digits[2] as u16;
+        prop_assert_eq!(value, a as u16);
+    }
+
+    #[test]
+    fn store_and_load_regs_round_trip(regs: [u8; 16], x in 0u8..16, quirks in quirks()) {
+        let mut ops: Vec<u16> = regs.iter().enumerate().map(|(r, &b)| set(r as u8, b)).collect();
+        ops.push(0xA000 | SCRATCH);
+        ops.push(0xF055 | (x as u16) << 8);
+        ops.extend((0..16).map(|r| set(r, 0)));
+        ops.push(0xA000 | SCRATCH);
+        ops.push(0xF065 | (x as u16) << 8);
+        let cpu = run_with(&ops, quirks, &[]);
+
+        let n = x as usize + 1;
+        prop_assert_eq!(&cpu.mem()[(SCRATCH as usize)..(SCRATCH as usize + n)], &regs[..n]);
+        prop_assert_eq!(&cpu.v()[..n], &regs[..n]);
+        prop_assert!(cpu.v()[n..].iter().all(|&r| r == 0));
+
+        let i = if quirks.load_store_increments_i { SCRATCH + n as u16 } else { SCRATCH };
+        prop_assert_eq!(cpu.i(), i);
+    }
+
+    // Drawing a sprite twice restores the screen. Each draw sets VF exactly
+    // when it turns off a pixel, which is when the pixels it toggles overlap
+    // what was lit before it.
+    #[test]
+    fn draw_twice_restores_screen(sprite in prop::collection::vec(any::<u8>(), 1..16),
+                                  background in prop::collection::vec(any::<u8>(), 1..16),
+                                  x: u8, y: u8, bx: u8, by: u8, quirks in quirks()) {
+        let n = sprite.len() as u16;
+        let bn = background.len() as u16;
+        let ops = [
+            set(0, bx), set(1, by), 0xA000 | BACKGROUND, 0xD010 | bn,
+            set(0, x), set(1, y), 0xA000 | SPRITE, 0xD010 | n,
+        ];
+        let preload: [(u16, &[u8]); 2] = [(SPRITE, &sprite), (BACKGROUND, &background)];
+        let mut cpu = run_with(&ops, quirks, &preload);
+        let before = screen(&run_with(&ops[..4], quirks, &preload));
+        let first = screen(&cpu);
+        let first_vf = cpu.v()[0xF];
+
+        cpu.load_rom_bytes(&[0xD0, 0x10 | n as u8]).unwrap();
+        cpu.step_instruction().unwrap();
+        let second = screen(&cpu);
+
+        prop_assert_eq!(&second, &before);
+        let toggled: Vec<usize> = (0..first.len()).filter(|&p| first[p] != before[p]).collect();
+        prop_assert_eq!(first_vf, toggled.iter().any(|&p| before[p]) as u8);
+        prop_assert_eq!(cpu.v()[0xF], toggled.iter().any(|&p| first[p]) as u8);
+    }
+}
@@ -0,0 +1,195 @@
+; Flags test: checks the result and VF of the arithmetic and shift
+; instructions, including VF used as an operand. Draws the number of every
+; passing check in a grid, a missing digit is a failing check.
+;
+; V9   pass flag of the current check
+; VC   cursor x, VD cursor y
+; VE   check number
+        CLS
+        LD VC, 0
+        LD VD, 0
+        LD VE, 0
+
+; 0: 8XY4 without carry
+        LD V9, 1
+        LD V0, 0x20
+        LD V1, 0x30
+        ADD V0, V1
+        SE V0, 0x50
+        LD V9, 0
+        SE VF, 0
+        LD V9, 0
+        CALL result
+
+; 1: 8XY4 with carry
+        LD V9, 1
+        LD V0, 0xF0
+        LD V1, 0x20
+        ADD V0, V1
+        SE V0, 0x10
+        LD V9, 0
+        SE VF, 1
+        LD V9, 0
+        CALL result
+
+; 2: 8XY4 into VF keeps the carry
+        LD V9, 1
+        LD VF, 0xF0
+        LD V1, 0x20
+        ADD VF, V1
+        SE VF, 1
+        LD V9, 0
+        CALL result
+
+; 3: 8XY5 without borrow
+        LD V9, 1
+        LD V0, 0x50
+        LD V1, 0x30
+        SUB V0, V1
+        SE V0, 0x20
+        LD V9, 0
+        SE VF, 1
+        LD V9, 0
+        CALL result
+
+; 4: 8XY5 with borrow
+        LD V9, 1
+        LD V0, 0x30
+        LD V1, 0x50
+        SUB V0, V1
+        SE V0, 0xE0
+        LD V9, 0
+        SE VF, 0
+        LD V9, 0
+        CALL result
+
+; 5: 8XY5 with equal operands doesn't borrow
+        LD V9, 1
+        LD V0, 0x30
+        LD V1, 0x30
+        SUB V0, V1
+        SE V0, 0
+        LD V9, 0
+        SE VF, 1
+        LD V9, 0
+        CALL result
+
+; 6: 8XY7 without borrow
+        LD V9, 1
+        LD V0, 0x30
+        LD V1, 0x50
+        SUBN V0, V1
+        SE V0, 0x20
+        LD V9, 0
+        SE VF, 1
+        LD V9, 0
+        CALL result
+
+; 7: 8XY7 with borrow
+        LD V9, 1
+        LD V0, 0x50
+        LD V1, 0x30
+        SUBN V0, V1
+        SE V0, 0xE0
+        LD V9, 0
+        SE VF, 0
+        LD V9, 0
+        CALL result
+
+; 8: 8XY7 with equal operands doesn't borrow
+        LD V9, 1
+        LD V0, 0x30
+        LD V1, 0x30
+        SUBN V0, V1
+        SE V0, 0
+        LD V9, 0
+        SE VF, 1
+        LD V9, 0
+        CALL result
+
+; 9: 8XY6 shifting out a 1, VX = VY so the shift quirk doesn't matter
+        LD V9, 1
+        LD V0, 0x85
+        LD V1, 0x85
+        SHR V0, V1
+        SE V0, 0x42
+        LD V9, 0
+        SE VF, 1
+        LD V9, 0
+        CALL result
+
+; 10: 8XY6 shifting out a 0
+        LD V9, 1
+        LD V0, 0x84
+        LD V1, 0x84
+        SHR V0, V1
+        SE V0, 0x42
+        LD V9, 0
+        SE VF, 0
+        LD V9, 0
+        CALL result
+
+; 11: 8XYE shifting out a 1
+        LD V9, 1
+        LD V0, 0x81
+        LD V1, 0x81
+        SHL V0, V1
+        SE V0, 0x02
+        LD V9, 0
+        SE VF, 1
+        LD V9, 0
+        CALL result
+
+; 12: 8XYE shifting out a 0
+        LD V9, 1
+        LD V0, 0x41
+        LD V1, 0x41
+        SHL V0, V1
+        SE V0, 0x82
+        LD V9, 0
+        SE VF, 0
+        LD V9, 0
+        CALL result
+
+; 13: 8XY5 into VF keeps the borrow flag
+        LD V9, 1
+        LD VF, 0x30
+        LD V1, 0x50
+        SUB VF, V1
+        SE VF, 0
+        LD V9, 0
+        CALL result
+
+; 14: 8XY6 into VF keeps the shifted out bit
+        LD V9, 1
+        LD VF, 0x85
+        SHR VF, VF
+        SE VF, 1
+        LD V9, 0
+        CALL result
+
+; 15: 7XNN overflowing leaves VF alone
+        LD V9, 1
+        LD VF, 0x05
+        LD V0, 0xFF
+        ADD V0, 1
+        SE V0, 0
+        LD V9, 0
+        SE VF, 0x05
+        LD V9, 0
+        CALL result
+
+done:   JP done
+
+; Draws the check number if V9 is set and moves the cursor
+result: SE V9, 1
+        JP next
+        LD F, VE
+        DRW VC, VD, 5
+next:   ADD VE, 1
+        ADD VC, 5
+        SE VC, 60
+        RET
+        LD VC, 0
+        ADD VD, 6
+        RET
@@ -0,0 +1,24 @@
+; Keypad test: waits for a key with FX0A, then shows the key and the number
+; of presses so far. Once EXA1 sees the key released a bar is drawn under
+; the digits, the bar is missing while the key is still held.
+        CLS
+        LD V3, 0
+loop:   LD V0, K
+        ADD V3, 1
+        CLS
+        LD V1, 0
+        LD V2, 0
+        LD F, V0
+        DRW V1, V2, 5
+        LD V1, 8
+        LD F, V3
+        DRW V1, V2, 5
+held:   SKNP V0
+        JP held
+        LD V1, 0
+        LD V2, 7
+        LD I, bar
+        DRW V1, V2, 1
+        JP loop
+
+bar:    DB 0xFF
@@ -0,0 +1,291 @@
+; Opcode test: runs one check per instruction and draws the number of every
+; passing check (mod 16) in a grid. A missing digit is a failing opcode.
+;
+; V9   pass flag of the current check
+; VC   cursor x, VD cursor y
+; VE   check number
+        CLS
+        LD VC, 0
+        LD VD, 0
+        LD VE, 0
+
+; 0: 3XNN
+        LD V9, 0
+        LD V0, 0x12
+        SE V0, 0x12
+        JP t0a
+        LD V9, 1
+t0a:    SE V0, 0x13
+        JP t0b
+        LD V9, 0
+t0b:    CALL result
+
+; 1: 4XNN
+        LD V9, 0
+        LD V0, 0x12
+        SNE V0, 0x13
+        JP t1a
+        LD V9, 1
+t1a:    SNE V0, 0x12
+        JP t1b
+        LD V9, 0
+t1b:    CALL result
+
+; 2: 5XY0
+        LD V9, 0
+        LD V0, 0x12
+        LD V1, 0x12
+        LD V2, 0x13
+        SE V0, V1
+        JP t2a
+        LD V9, 1
+t2a:    SE V0, V2
+        JP t2b
+        LD V9, 0
+t2b:    CALL result
+
+; 3: 9XY0
+        LD V9, 0
+        LD V0, 0x12
+        LD V1, 0x12
+        LD V2, 0x13
+        SNE V0, V2
+        JP t3a
+        LD V9, 1
+t3a:    SNE V0, V1
+        JP t3b
+        LD V9, 0
+t3b:    CALL result
+
+; 4: 7XNN wraps and leaves VF alone
+        LD V9, 1
+        LD VF, 0x55
+        LD V0, 0xFF
+        ADD V0, 2
+        SE V0, 1
+        LD V9, 0
+        SE VF, 0x55
+        LD V9, 0
+        CALL result
+
+; 5: 8XY0
+        LD V9, 1
+        LD V1, 0x3C
+        LD V0, V1
+        SE V0, 0x3C
+        LD V9, 0
+        CALL result
+
+; 6: 8XY1
+        LD V9, 1
+        LD V0, 0x0F
+        LD V1, 0x30
+        OR V0, V1
+        SE V0, 0x3F
+        LD V9, 0
+        CALL result
+
+; 7: 8XY2
+        LD V9, 1
+        LD V0, 0x3C
+        LD V1, 0x0F
+        AND V0, V1
+        SE V0, 0x0C
+        LD V9, 0
+        CALL result
+
+; 8: 8XY3
+        LD V9, 1
+        LD V0, 0x3C
+        LD V1, 0x0F
+        XOR V0, V1
+        SE V0, 0x33
+        LD V9, 0
+        CALL result
+
+; 9: 8XY4
+        LD V9, 1
+        LD V0, 0x20
+        LD V1, 0x30
+        ADD V0, V1
+        SE V0, 0x50
+        LD V9, 0
+        CALL result
+
+; 10: 8XY5
+        LD V9, 1
+        LD V0, 0x50
+        LD V1, 0x30
+        SUB V0, V1
+        SE V0, 0x20
+        LD V9, 0
+        CALL result
+
+; 11: 8XY6, VX = VY so the shift quirk doesn't matter
+        LD V9, 1
+        LD V0, 0x84
+        LD V1, 0x84
+        SHR V0, V1
+        SE V0, 0x42
+        LD V9, 0
+        CALL result
+
+; 12: 8XY7
+        LD V9, 1
+        LD V0, 0x30
+        LD V1, 0x50
+        SUBN V0, V1
+        SE V0, 0x20
+        LD V9, 0
+        CALL result
+
+; 13: 8XYE
+        LD V9, 1
+        LD V0, 0x21
+        LD V1, 0x21
+        SHL V0, V1
+        SE V0, 0x42
+        LD V9, 0
+        CALL result
+
+; 14: ANNN, FX1E, FX65
+        LD V9, 1
+        LD I, data
+        LD V0, 2
+        ADD I, V0
+        LD V0, [I]
+        SE V0, 0x33
+        LD V9, 0
+        CALL result
+
+; 15: FX55 and FX65 round trip
+        LD V9, 1
+        LD V0, 1
+        LD V1, 2
+        LD V2, 3
+        LD I, buf
+        LD [I], V2
+        LD V0, 0
+        LD V1, 0
+        LD V2, 0
+        LD I, buf
+        LD V2, [I]
+        SE V0, 1
+        LD V9, 0
+        SE V1, 2
+        LD V9, 0
+        SE V2, 3
+        LD V9, 0
+        CALL result
+
+; 16: FX33
+        LD V9, 1
+        LD V0, 137
+        LD I, buf
+        LD B, V0
+        LD I, buf
+        LD V2, [I]
+        SE V0, 1
+        LD V9, 0
+        SE V1, 3
+        LD V9, 0
+        SE V2, 7
+        LD V9, 0
+        CALL result
+
+; 17: 2NNN and 00EE
+        LD V9, 1
+        LD V0, 0
+        CALL sub17
+        SE V0, 0x42
+        LD V9, 0
+        CALL result
+
+; 18: 1NNN
+        LD V9, 0
+        JP t18a
+        JP t18b
+t18a:   LD V9, 1
+t18b:   CALL result
+
+; 19: BNNN, VX = V0 so the jump quirk doesn't matter
+        LD V9, 0
+        LD V0, 4
+        LD V2, 4
+        LD V3, 4
+        JP V0, t19
+t19:    JP t19a
+        JP t19a
+        LD V9, 1
+t19a:   CALL result
+
+; 20: FX15 and FX07
+        LD V9, 1
+        LD V0, 0x20
+        LD DT, V0
+        LD V1, DT
+        SNE V1, 0
+        LD V9, 0
+        CALL result
+
+; 21: CXNN with an empty mask
+        LD V9, 1
+        LD V0, 0xFF
+        RND V0, 0
+        SE V0, 0
+        LD V9, 0
+        CALL result
+
+; 22: FX29 points at the glyph for A
+        LD V9, 1
+        LD V0, 0xA
+        LD F, V0
+        LD V0, [I]
+        SE V0, 0xF0
+        LD V9, 0
+        CALL result
+
+; 23: DXYN collision, drawing twice erases the sprite again
+        LD V9, 1
+        LD I, sprite
+        LD V0, 56
+        LD V1, 26
+        DRW V0, V1, 1
+        SE VF, 0
+        LD V9, 0
+        DRW V0, V1, 1
+        SE VF, 1
+        LD V9, 0
+        CALL result
+
+; 24: EX9E and EXA1 with no key pressed
+        LD V9, 1
+        LD V0, 5
+        SKNP V0
+        LD V9, 0
+        SKP V0
+        JP t24
+        LD V9, 0
+t24:    CALL result
+
+done:   JP done
+
+sub17:  LD V0, 0x42
+        RET
+
+; Draws the check number if V9 is set and moves the cursor
+result: SE V9, 1
+        JP next
+        LD F, VE
+        DRW VC, VD, 5
+next:   ADD VE, 1
+        ADD VC, 5
+        SE VC, 60
+        RET
+        LD VC, 0
+        ADD VD, 6
+        RET
+
+data:   DB 0x11, 0x22, 0x33
+sprite: DB 0xFF
+buf:    DB 0, 0, 0, 0
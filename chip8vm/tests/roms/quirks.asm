@@ -0,0 +1,92 @@
+; Quirks test: detects which interpreter quirks are active. Every row shows
+; the quirk number followed by 1 if the quirk is active and 0 if not.
+;
+;   1  8XY1 resets VF
+;   2  FX55 increments I
+;   3  8XY6 shifts VY
+;   4  DXYN clips sprites at the screen edge
+;   5  BNNN jumps to NNN + VX
+;
+; V9   detected quirk
+; VD   row y
+; VE   quirk number
+        CLS
+        LD VD, 0
+        LD VE, 1
+
+; 1: logic ops reset VF
+        LD VF, 5
+        LD V0, 1
+        LD V1, 2
+        OR V0, V1
+        LD V9, 1
+        SE VF, 0
+        LD V9, 0
+        CALL show
+
+; 2: FX55 increments I, so the second store doesn't overwrite the first
+        LD I, buf
+        LD V0, 0xAA
+        LD [I], V0
+        LD V0, 0xBB
+        LD [I], V0
+        LD I, buf
+        LD V0, [I]
+        LD V9, 1
+        SE V0, 0xAA
+        LD V9, 0
+        CALL show
+
+; 3: 8XY6 shifts VY into VX
+        LD V0, 1
+        LD V1, 4
+        SHR V0, V1
+        LD V9, 1
+        SE V0, 2
+        LD V9, 0
+        CALL show
+
+; 4: a sprite at the right edge doesn't wrap around to column 0
+        LD I, two
+        LD V0, 63
+        LD V1, 20
+        DRW V0, V1, 1
+        LD I, one
+        LD V2, 0
+        DRW V2, V1, 1
+        LD V9, 1
+        SE VF, 0
+        LD V9, 0
+        DRW V2, V1, 1
+        LD I, two
+        DRW V0, V1, 1
+        CALL show
+
+; 5: BNNN uses VX, X being the highest nibble of NNN
+        LD V0, 0
+        LD V2, 2
+        LD V3, 2
+        JP V0, jt
+jt:     JP no
+        JP yes
+no:     LD V9, 0
+        JP t5
+yes:    LD V9, 1
+t5:     CALL show
+
+done:   JP done
+
+; Draws the quirk number and the result on a new row
+show:   LD V8, 0
+        LD F, VE
+        DRW V8, VD, 5
+        LD V8, 8
+        LD F, V9
+        DRW V8, VD, 5
+        ADD VD, 6
+        ADD VE, 1
+        RET
+
+two:    DB 0xC0
+one:    DB 0x80
+buf:    DB 0, 0
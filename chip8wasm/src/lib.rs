@@ -0,0 +1,71 @@
+extern crate wasm_bindgen;
+extern crate chip8core;
+extern crate chip8vm;
+
+use wasm_bindgen::prelude::*;
+use chip8core::{ Vm, Key };
+use chip8vm::{ Cpu, XorShift };
+
+const FRAME_TIME: f64 = 1.0 / 60.0;
+
+#[wasm_bindgen]
+pub struct Emulator {
+    cpu: Cpu<XorShift>,
+    seed: u32,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Emulator {
+        Emulator {
+            cpu: Cpu::with_random(XorShift::default()),
+            seed: 0,
+        }
+    }
+
+    // Seeds CXNN for the next loaded ROM, pass e.g. `Math.random() * 0xFFFFFFFF` from JS
+    pub fn seed(&mut self, seed: u32) {
+        self.seed = seed;
+    }
+
+    #[wasm_bindgen(js_name = loadRom)]
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsValue> {
+        self.cpu = Cpu::with_random(XorShift::new(self.seed));
+        self.cpu.load_rom_bytes(rom).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = runFrame)]
+    pub fn run_frame(&mut self) -> Result<(), JsValue> {
+        self.cpu.step(FRAME_TIME).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    // One byte per pixel, row by row, 1 for lit and 0 for unlit
+    pub fn framebuffer(&self) -> Vec<u8> {
+        self.cpu.pixels()
+            .flat_map(|row| row.iter().map(|&on| on as u8))
+            .collect()
+    }
+
+    #[wasm_bindgen(js_name = setKey)]
+    pub fn set_key(&mut self, key: u8, pressed: bool) -> Result<(), JsValue> {
+        let key = Key::from_index(key).ok_or_else(|| JsValue::from_str("Key must be 0x0-0xF"))?;
+        if pressed {
+            self.cpu.press_key(key);
+        } else {
+            self.cpu.release_key(key);
+        }
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = soundActive)]
+    pub fn sound_active(&self) -> bool {
+        self.cpu.sound_active()
+    }
+}
+
+impl Default for Emulator {
+    fn default() -> Emulator {
+        Emulator::new()
+    }
+}
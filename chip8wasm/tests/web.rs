@@ -0,0 +1,49 @@
+#[cfg(target_arch = "wasm32")]
+extern crate wasm_bindgen_test;
+extern crate chip8wasm;
+
+// Runs under `wasm-pack test --node` on wasm32 and as plain tests elsewhere
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+use chip8wasm::Emulator;
+
+// Draws the font glyph for 0 at (0, 0), starts the sound timer and spins
+const ROM: [u8; 14] = [
+    0xA0, 0x00, // LD I, 0x000
+    0x60, 0x00, // LD V0, 0x00
+    0xD0, 0x05, // DRW V0, V0, 5
+    0x62, 0x0A, // LD V2, 0x0A
+    0xF2, 0x18, // LD ST, V2
+    0x12, 0x0A, // JP 0x20A
+    0x00, 0x00,
+];
+
+#[test]
+fn framebuffer_has_one_byte_per_pixel() {
+    let emulator = Emulator::new();
+    let fb = emulator.framebuffer();
+
+    assert_eq!(fb.len(), 64 * 32);
+    assert!(fb.iter().all(|&p| p == 0));
+}
+
+#[test]
+fn run_frame_executes_loaded_rom() {
+    let mut emulator = Emulator::new();
+    emulator.load_rom(&ROM).unwrap();
+    emulator.run_frame().unwrap();
+
+    let fb = emulator.framebuffer();
+    assert_eq!(&fb[..8], &[1, 1, 1, 1, 0, 0, 0, 0]);
+    assert_eq!(&fb[64..72], &[1, 0, 0, 1, 0, 0, 0, 0]);
+    assert!(emulator.sound_active());
+}
+
+#[test]
+fn set_key_accepts_every_keypad_key() {
+    let mut emulator = Emulator::new();
+    for key in 0..16 {
+        emulator.set_key(key, true).unwrap();
+        emulator.set_key(key, false).unwrap();
+    }
+}
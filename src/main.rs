@@ -1,10 +1,646 @@
+extern crate chip8core;
 extern crate chip8vm;
 extern crate chip8ui;
+extern crate chip8tui;
+extern crate chip8rom;
+extern crate chip8net;
+extern crate chip8rpc;
+extern crate chip8gdb;
+extern crate chip8cheat;
 
-use chip8vm::Cpu;
-use chip8ui::Runner;
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::{ BufRead, Read, Write };
+use std::net::TcpListener;
+use std::path::{ Path, PathBuf };
+use std::process;
+use std::sync::mpsc::{ self, Receiver };
+use chip8vm::{ Cpu, FontSet, LoadOptions, Quirks, Random, SmallFont, TraceSink, TraceFilter,
+               WriterSink, RingBufferSink, XorShift, RING_BUFFER_SIZE };
+#[cfg(feature = "jit")]
+use chip8vm::JitCpu;
+use chip8core::{ DebugVm, Vm };
+use chip8ui::{ Runner, View };
+use chip8rom::{ Platform, Rom, RomError, WatchVm, Watcher };
+use chip8net::{ NetVm, Session };
+use chip8rpc::{ RpcVm, Server };
+use chip8gdb::GdbVm;
+use chip8cheat::CheatList;
+
+// How the main ROM is shown, in the window or the terminal
+struct Frontend<'a> {
+    tui: Option<chip8tui::Settings>,
+    ui: &'a chip8ui::Settings,
+    cheat_file: Option<&'a Path>,
+    // For the menu's reset, save states and recent ROMs
+    rom_path: Option<&'a Path>,
+    rom: &'a [u8],
+    // Reloads the ROM whenever this file changes
+    watch: Option<&'a Path>,
+}
+
+// Which end of a netplay session this instance is
+enum Netplay {
+    Host(String),
+    Join(String),
+}
+
+// One cell of the window when running several VMs side by side. Unset
+// fields fall back to the options given for the main ROM.
+struct ViewSpec {
+    rom_path: Option<String>,
+    quirks: Option<Option<Quirks>>,
+    speed: f64,
+    jit: bool,
+}
 
 fn main() {
+    let mut tui = None;
+    let mut rom_path = None;
+    let mut load_options = LoadOptions::default();
+    let mut preload_blobs = Vec::new();
+    let mut font = FontSet::default();
+    let mut quirks = Some(Quirks::default());
+    let mut trace_path = None;
+    let mut trace_ring = None;
+    let mut trace_filter = TraceFilter::default();
+    let mut jit = false;
+    let mut views = Vec::new();
+    let mut ui_settings = chip8ui::Settings::default();
+    let mut netplay = None;
+    let mut net_settings = chip8net::Settings::default();
+    let mut rpc_addr = None;
+    let mut rpc_paused = false;
+    let mut gdb_addr = None;
+    let mut cheat_dir: Option<PathBuf> = None;
+    let mut watch = false;
+    let mut hex = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tui" => {
+                tui.get_or_insert_with(chip8tui::Settings::default);
+            },
+            "--braille" => {
+                tui.get_or_insert_with(chip8tui::Settings::default).glyphs =
+                    chip8tui::Glyphs::Braille;
+            },
+            "--hold-frames" => {
+                let frames = args.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0)
+                    .expect("--hold-frames takes a number of frames above 0");
+                tui.get_or_insert_with(chip8tui::Settings::default).hold_frames = frames;
+            },
+            "--eti660" => load_options = LoadOptions::eti660(),
+            "--load-address" => {
+                load_options.load_address = args.next().and_then(|a| parse_addr(&a))
+                    .expect("--load-address takes an address, e.g. 0x600");
+            },
+            "--entry" => {
+                load_options.entry_point = Some(args.next().and_then(|a| parse_addr(&a))
+                    .expect("--entry takes an address, e.g. 0x600"));
+            },
+            "--preload" => {
+                let blob = args.next().and_then(|a| parse_preload(&a))
+                    .expect("--preload takes an address and a file, e.g. 0x100:data.bin");
+                preload_blobs.push(blob);
+            },
+            "--font" => {
+                let name = args.next().expect("--font takes a font name or file");
+                // A big font given before stays unless the file has its own
+                let loaded = load_font(&name);
+                font = FontSet {
+                    base_address: font.base_address,
+                    big: loaded.big.or(font.big),
+                    ..loaded
+                };
+            },
+            "--font-base" => {
+                font.base_address = args.next().and_then(|a| parse_addr(&a))
+                    .expect("--font-base takes an address, e.g. 0x50");
+            },
+            "--big-font" => font = font.with_big_font(),
+            "--quirks" => {
+                let name = args.next().expect("--quirks takes a platform or auto");
+                quirks = match name.as_str() {
+                    "auto" => None,
+                    name => Some(Quirks::from_name(name)
+                        .expect("--quirks takes chip8, schip, xochip, none or auto")),
+                };
+            },
+            "--jit" if cfg!(feature = "jit") => jit = true,
+            "--view" => {
+                views.push(args.next().and_then(|s| parse_view(&s))
+                    .expect("--view takes settings, e.g. rom=a.ch8,quirks=schip,speed=2"));
+            },
+            "--broadcast" => ui_settings.broadcast = true,
+            "--fast-forward" => {
+                ui_settings.fast_forward = args.next().and_then(|n| n.parse().ok())
+                    .expect("--fast-forward takes a speed, e.g. 4");
+            },
+            "--slow-motion" => {
+                ui_settings.slow_motion = args.next().and_then(|n| n.parse().ok())
+                    .expect("--slow-motion takes a speed, e.g. 0.25");
+            },
+            "--host" => {
+                netplay = Some(Netplay::Host(args.next()
+                    .expect("--host takes an address to listen on, e.g. 0.0.0.0:7800")));
+            },
+            "--join" => {
+                netplay = Some(Netplay::Join(args.next()
+                    .expect("--join takes the host's address, e.g. 192.168.1.2:7800")));
+            },
+            "--input-delay" => {
+                net_settings.input_delay = args.next().and_then(|n| n.parse().ok())
+                    .expect("--input-delay takes a number of frames");
+            },
+            "--hash-interval" => {
+                net_settings.hash_interval = args.next().and_then(|n| n.parse().ok())
+                    .expect("--hash-interval takes a number of frames, 0 turns it off");
+            },
+            "--rpc" => {
+                rpc_addr = Some(args.next()
+                    .expect("--rpc takes a loopback address or unix:PATH, e.g. 127.0.0.1:7900"));
+            },
+            "--rpc-paused" => rpc_paused = true,
+            "--gdb" => {
+                gdb_addr = Some(args.next()
+                    .expect("--gdb takes a loopback address, e.g. 127.0.0.1:1234"));
+            },
+            "--rom-dir" => {
+                let dir = PathBuf::from(args.next()
+                    .expect("--rom-dir takes a directory for the menu to open ROMs from"));
+                ui_settings.recent_file = Some(dir.join(".chip8-recent"));
+                ui_settings.rom_dir = Some(dir);
+            },
+            "--state-dir" => {
+                ui_settings.state_dir = Some(args.next()
+                    .expect("--state-dir takes a directory to keep save states in").into());
+            },
+            "--fullscreen" => ui_settings.fullscreen = true,
+            "--window" => {
+                ui_settings.window_size = args.next().and_then(|s| parse_size(&s))
+                    .expect("--window takes a size in pixels, e.g. 1280x640");
+            },
+            "--integer-scale" => ui_settings.integer_scale = true,
+            "--effect" => {
+                ui_settings.effect = args.next().and_then(|n| chip8ui::Effect::from_name(&n))
+                    .expect("--effect takes none, grid or scanlines");
+            },
+            "--palette" => {
+                ui_settings.palette = args.next().and_then(|n| chip8ui::Palette::from_name(&n))
+                    .expect("--palette takes white, amber, green or lcd");
+            },
+            "--watch" => watch = true,
+            "--hex" => hex = true,
+            "--cheats" => {
+                cheat_dir = Some(args.next()
+                    .expect("--cheats takes a directory to keep cheats in").into());
+            },
+            "--trace" => trace_path = Some(args.next().expect("--trace takes a file, - for stderr")),
+            "--trace-ring" => {
+                trace_path = Some(args.next().expect("--trace-ring takes a file, - for stderr"));
+                trace_ring.get_or_insert(RING_BUFFER_SIZE);
+            },
+            "--trace-last" => {
+                trace_ring = Some(args.next().and_then(|n| n.parse().ok())
+                    .expect("--trace-last takes a number of instructions"));
+            },
+            "--trace-first" => {
+                trace_filter.first = Some(args.next().and_then(|n| n.parse().ok())
+                    .expect("--trace-first takes a number of instructions"));
+            },
+            "--trace-range" => {
+                trace_filter.address_range = Some(args.next().and_then(|r| parse_range(&r))
+                    .expect("--trace-range takes an address range, e.g. 0x200-0x2FF"));
+            },
+            "--trace-class" => {
+                trace_filter.opcode_classes = args.next().and_then(|c| parse_classes(&c))
+                    .expect("--trace-class takes opcode classes, e.g. 8,D,F");
+            },
+            _ if !arg.starts_with("--") && rom_path.is_none() => rom_path = Some(arg),
+            _ => panic!("Unknown argument: {}", arg),
+        }
+    }
+
+    let preload: Vec<(u16, &[u8])> = preload_blobs.iter()
+        .map(|&(addr, ref data)| (addr, &data[..]))
+        .collect();
+    load_options.preload = &preload;
+
+    // With a ROM directory and no ROM the window starts on the menu
+    if rom_path.is_none() && views.is_empty() && ui_settings.rom_dir.is_some() {
+        if trace_path.is_some() || tui.is_some() || netplay.is_some() || rpc_addr.is_some() ||
+           gdb_addr.is_some() || jit || cheat_dir.is_some() || watch {
+            eprintln!("Starting without a ROM can't be combined with tracing, --tui, netplay, \
+                       --rpc, --gdb, --jit, --cheats or --watch");
+            process::exit(1);
+        }
+        run_waiting(quirks, font, &load_options, &ui_settings);
+        return;
+    }
+
+    // Only read stdin when some VM runs the main ROM
+    let needs_main_rom = views.is_empty() || views.iter().any(|v| v.rom_path.is_none());
+    let main_rom = if rom_path.is_some() || needs_main_rom {
+        Some(match rom_path {
+            Some(ref path) if hex => load_hex_file(path),
+            Some(ref path) => load_rom_file(path),
+            None => load_rom_stdin(hex),
+        }.unwrap_or_else(|e| {
+            eprintln!("Failed to load ROM: {}", e);
+            process::exit(1);
+        }))
+    } else {
+        None
+    };
+
+    if watch && (rom_path.is_none() || !views.is_empty() || netplay.is_some() || hex) {
+        eprintln!("--watch needs a ROM file and can't be combined with --view, netplay or --hex");
+        process::exit(1);
+    }
+
+    if cheat_dir.is_some() && (tui.is_some() || netplay.is_some()) {
+        eprintln!("--cheats can't be combined with --tui or netplay");
+        process::exit(1);
+    }
+    let cheat_dir = cheat_dir.as_deref();
+
+    if !views.is_empty() {
+        if trace_path.is_some() || tui.is_some() || netplay.is_some() || rpc_addr.is_some() ||
+           gdb_addr.is_some() {
+            eprintln!("--view can't be combined with tracing, --tui, netplay, --rpc or --gdb");
+            process::exit(1);
+        }
+        for view in views.iter_mut() {
+            view.jit |= jit;
+        }
+        let main_rom = main_rom.as_ref().map(|rom| (rom, rom_path.as_deref().map(Path::new)));
+        run_views(&views, main_rom, quirks, font, &load_options, &ui_settings, cheat_dir);
+        return;
+    }
+
+    let rom = main_rom.unwrap();
+    let cheat_file = cheat_dir.map(|dir| chip8cheat::cheat_file(dir, &rom.data));
+    let mut frontend = Frontend {
+        tui,
+        ui: &ui_settings,
+        cheat_file: cheat_file.as_deref(),
+        rom_path: rom_path.as_deref().map(Path::new),
+        rom: &rom.data,
+        watch: rom_path.as_deref().map(Path::new).filter(|_| watch),
+    };
+    if let Some(netplay) = netplay {
+        if jit || trace_path.is_some() || rpc_addr.is_some() || gdb_addr.is_some() {
+            eprintln!("Netplay can't be combined with --jit, tracing, --rpc or --gdb");
+            process::exit(1);
+        }
+        let session = match netplay {
+            Netplay::Host(addr) => {
+                let listener = TcpListener::bind(&addr).unwrap_or_else(|e| {
+                    eprintln!("Failed to listen on {}: {}", addr, e);
+                    process::exit(1);
+                });
+                eprintln!("Waiting for the other player on {}", addr);
+                Session::host(&listener, &rom.data, net_settings)
+            },
+            Netplay::Join(addr) => Session::join(&addr, &rom.data),
+        }.unwrap_or_else(|e| {
+            eprintln!("Failed to start netplay: {}", e);
+            process::exit(1);
+        });
+
+        let seed = session.settings().seed;
+        let cpu = build_cpu(Cpu::with_random(XorShift::new(seed)), &rom, quirks, font,
+                            &load_options);
+        let mut vm = NetVm::new(cpu, session);
+        frontend.cheat_file = None;
+        run(&mut vm, &frontend);
+        if let Some(e) = vm.error() {
+            eprintln!("Netplay stopped: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if gdb_addr.is_some() && (jit || rpc_addr.is_some()) {
+        eprintln!("--gdb can't be combined with --jit or --rpc");
+        process::exit(1);
+    }
+
+    let server = rpc_addr.map(|addr| {
+        let server = Server::bind(&addr).unwrap_or_else(|e| {
+            eprintln!("Failed to listen on {}: {}", addr, e);
+            process::exit(1);
+        });
+        match server.local_addr() {
+            Some(local) => eprintln!("Remote control listening on {}", local),
+            None => eprintln!("Remote control listening on {}", addr),
+        }
+        server
+    });
+
+    let mut cpu = build_cpu(Cpu::new(), &rom, quirks, font, &load_options);
+
+    if jit {
+        if trace_path.is_some() {
+            eprintln!("Tracing isn't supported with --jit");
+            process::exit(1);
+        }
+        #[cfg(feature = "jit")]
+        run_remote(JitCpu::from_cpu(cpu), server, rpc_paused, &frontend);
+        return;
+    }
+
+    if let Some(path) = trace_path {
+        let out: Box<dyn Write> = if path == "-" {
+            Box::new(io::stderr())
+        } else {
+            Box::new(File::create(&path).unwrap_or_else(|e| {
+                eprintln!("Failed to create trace file: {}", e);
+                process::exit(1);
+            }))
+        };
+        let sink: Box<dyn TraceSink> = match trace_ring {
+            Some(capacity) => Box::new(RingBufferSink::with_capacity(out, capacity)),
+            None => Box::new(WriterSink::new(out)),
+        };
+        cpu.start_trace(sink, trace_filter);
+    }
+
+    let mut cpu = match gdb_addr {
+        Some(addr) => run_gdb(cpu, &addr, &frontend),
+        None => run_remote(cpu, server, rpc_paused, &frontend),
+    };
+    cpu.stop_trace();
+}
+
+fn build_cpu<R: Random>(mut cpu: Cpu<R>, rom: &Rom, quirks: Option<Quirks>, font: FontSet,
+                        load_options: &LoadOptions) -> Cpu<R> {
+    cpu.set_quirks(quirks.unwrap_or_else(|| platform_quirks(rom.platform)));
+    cpu.load_font(font).unwrap_or_else(|e| {
+        eprintln!("Failed to load font: {}", e);
+        process::exit(1);
+    });
+    cpu.load_rom_bytes_with(&rom.data, load_options).unwrap_or_else(|e| {
+        eprintln!("Failed to load ROM: {}", e);
+        process::exit(1);
+    });
+    cpu
+}
+
+// The main ROM comes with its path, unless it was read from stdin
+fn run_views(specs: &[ViewSpec], main_rom: Option<(&Rom, Option<&Path>)>,
+             quirks: Option<Quirks>, font: FontSet, load_options: &LoadOptions,
+             settings: &chip8ui::Settings, cheat_dir: Option<&Path>) {
+    let mut cheat_files = Vec::new();
+    let mut roms = Vec::new();
+    let mut vms: Vec<Box<dyn Vm>> = specs.iter().map(|spec| {
+        let rom = spec.rom_path.as_ref().map(|path| load_rom_file(path).unwrap_or_else(|e| {
+            eprintln!("Failed to load ROM {}: {}", path, e);
+            process::exit(1);
+        }));
+        let (rom, path) = match (rom.as_ref(), main_rom) {
+            (Some(rom), _) => (rom, spec.rom_path.as_deref().map(Path::new)),
+            (None, main_rom) => main_rom.unwrap(),
+        };
+        cheat_files.push(cheat_dir.map(|dir| chip8cheat::cheat_file(dir, &rom.data)));
+        roms.push((path.map(Path::to_path_buf), rom.data.clone()));
+        let cpu = build_cpu(Cpu::new(), rom, spec.quirks.unwrap_or(quirks), font, load_options);
+
+        #[cfg(feature = "jit")]
+        {
+            if spec.jit {
+                return Box::new(JitCpu::from_cpu(cpu)) as Box<dyn Vm>;
+            }
+        }
+        Box::new(cpu) as Box<dyn Vm>
+    }).collect();
+
+    let mut views: Vec<View> = vms.iter_mut().zip(specs).zip(cheat_files).zip(roms)
+        .map(|(((vm, spec), file), (path, rom))| {
+            let mut view = View::with_speed(&mut **vm, spec.speed);
+            view.set_rom(path, rom);
+            if let Some(file) = file {
+                view.set_cheats(load_cheats(&file), Some(file));
+            }
+            view
+        })
+        .collect();
+    Runner::run_views(&mut views, settings).unwrap();
+}
+
+fn run<V: Vm>(vm: &mut V, frontend: &Frontend) {
+    match frontend.watch {
+        Some(path) => {
+            let (tx, rx) = mpsc::channel();
+            let mut vm = WatchVm::new(vm, Watcher::new(path));
+            vm.on_reload(move |rom| match rom {
+                Ok(rom) => {
+                    eprintln!("Reloaded {}", rom.name);
+                    let _ = tx.send(rom.data.clone());
+                },
+                Err(e) => eprintln!("Failed to reload ROM: {}", e),
+            });
+            show(&mut vm, frontend, Some(rx));
+        },
+        None => show(vm, frontend, None),
+    }
+}
+
+// The reloads tell the window which ROM its menu resets to
+fn show<V: Vm>(vm: &mut V, frontend: &Frontend, reloads: Option<Receiver<Vec<u8>>>) {
+    match frontend.tui {
+        Some(ref settings) => chip8tui::Runner::run_with(vm, settings).unwrap(),
+        None => {
+            let mut view = View::new(vm);
+            view.set_rom(frontend.rom_path.map(Path::to_path_buf), frontend.rom.to_vec());
+            if let Some(reloads) = reloads {
+                view.set_reloads(reloads);
+            }
+            if let Some(file) = frontend.cheat_file {
+                view.set_cheats(load_cheats(file), Some(file.to_path_buf()));
+            }
+            Runner::run_views(&mut [view], frontend.ui).unwrap();
+        },
+    }
+}
+
+// A blank Vm that runs what is opened from the menu, loaded with the
+// options given on the command line
+fn run_waiting(quirks: Option<Quirks>, font: FontSet, load_options: &LoadOptions,
+               settings: &chip8ui::Settings) {
     let mut cpu = Cpu::new();
-    Runner::run(&mut cpu).unwrap();
+    cpu.set_quirks(quirks.unwrap_or_default());
+    cpu.load_font(font).unwrap_or_else(|e| {
+        eprintln!("Failed to load font: {}", e);
+        process::exit(1);
+    });
+    cpu.load_rom_bytes_with(&[], load_options).unwrap_or_else(|e| {
+        eprintln!("Invalid load options: {}", e);
+        process::exit(1);
+    });
+    Runner::run_views(&mut [View::waiting(&mut cpu)], settings).unwrap();
+}
+
+// A ROM without cheats yet gets an empty list
+fn load_cheats(file: &Path) -> CheatList {
+    CheatList::load(file).unwrap_or_else(|e| {
+        eprintln!("Failed to load cheats from {}: {}", file.display(), e);
+        process::exit(1);
+    })
+}
+
+// Hands the Vm back once the frontend quits
+fn run_remote<V: DebugVm>(mut vm: V, server: Option<Server>, paused: bool,
+                          frontend: &Frontend) -> V {
+    match server {
+        Some(server) => {
+            let mut vm = RpcVm::new(vm, server);
+            vm.set_paused(paused);
+            run(&mut vm, frontend);
+            vm.into_inner()
+        },
+        None => {
+            run(&mut vm, frontend);
+            vm
+        },
+    }
+}
+
+// The program waits for gdb to attach before it starts
+fn run_gdb(cpu: Cpu, addr: &str, frontend: &Frontend) -> Cpu {
+    let mut vm = GdbVm::bind(cpu, addr).unwrap_or_else(|e| {
+        eprintln!("Failed to listen on {}: {}", addr, e);
+        process::exit(1);
+    });
+    match vm.local_addr() {
+        Ok(local) => eprintln!("Waiting for gdb on {}", local),
+        Err(_) => eprintln!("Waiting for gdb on {}", addr),
+    }
+    run(&mut vm, frontend);
+    vm.into_inner()
+}
+
+fn parse_addr(s: &str) -> Option<u16> {
+    if s.starts_with("0x") || s.starts_with("0X") {
+        u16::from_str_radix(&s[2..], 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+// A width and height like 1280x640
+fn parse_size(s: &str) -> Option<(u32, u32)> {
+    let mut parts = s.splitn(2, 'x');
+    let width = parts.next()?.parse().ok().filter(|&w| w > 0)?;
+    let height = parts.next()?.parse().ok().filter(|&h| h > 0)?;
+    Some((width, height))
+}
+
+fn parse_view(s: &str) -> Option<ViewSpec> {
+    let mut spec = ViewSpec { rom_path: None, quirks: None, speed: 1.0, jit: false };
+    for setting in s.split(',') {
+        let mut parts = setting.splitn(2, '=');
+        match (parts.next()?, parts.next()) {
+            ("rom", Some(path)) => spec.rom_path = Some(path.to_string()),
+            ("quirks", Some("auto")) => spec.quirks = Some(None),
+            ("quirks", Some(name)) => spec.quirks = Some(Some(Quirks::from_name(name)?)),
+            ("speed", Some(speed)) => spec.speed = speed.parse().ok().filter(|&s: &f64| s > 0.0)?,
+            ("jit", None) if cfg!(feature = "jit") => spec.jit = true,
+            _ => return None,
+        }
+    }
+    Some(spec)
+}
+
+fn parse_range(s: &str) -> Option<(u16, u16)> {
+    let mut parts = s.splitn(2, '-');
+    Some((parse_addr(parts.next()?)?, parse_addr(parts.next()?)?))
+}
+
+fn parse_classes(s: &str) -> Option<u16> {
+    s.split(',').map(|c| u8::from_str_radix(c.trim(), 16).ok().filter(|&c| c < 16))
+        .fold(Some(0), |mask, class| Some(mask? | 1 << class?))
+}
+
+fn load_font(name: &str) -> FontSet {
+    let small = match name {
+        "standard" => SmallFont::Standard,
+        "vip" => SmallFont::Vip,
+        "eti660" => SmallFont::Eti660,
+        "dream6800" => SmallFont::Dream6800,
+        "fishnchips" => SmallFont::FishNChips,
+        path => return File::open(path).and_then(|mut f| FontSet::from_reader(&mut f))
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to load font: {}", e);
+                process::exit(1);
+            }),
+    };
+    FontSet::new(small)
+}
+
+fn platform_quirks(platform: Platform) -> Quirks {
+    match platform {
+        Platform::Chip8 => Quirks::chip8(),
+        Platform::SuperChip => Quirks::superchip(),
+        Platform::XoChip => Quirks::xochip(),
+    }
+}
+
+fn parse_preload(s: &str) -> Option<(u16, Vec<u8>)> {
+    let mut parts = s.splitn(2, ':');
+    let addr = parts.next().and_then(parse_addr)?;
+    let mut data = Vec::new();
+    File::open(parts.next()?).and_then(|mut f| f.read_to_end(&mut data)).ok()?;
+    Some((addr, data))
+}
+
+fn load_rom_file(path: &str) -> Result<Rom, RomError> {
+    match chip8rom::load_path(path) {
+        Err(RomError::AmbiguousArchive(names)) => {
+            let name = prompt_rom(&names)?;
+            chip8rom::load_zip_entry(path, &name)
+        },
+        result => result,
+    }
+}
+
+// With --hex the main ROM is a hex dump, whatever it's called
+fn load_hex_file(path: &str) -> Result<Rom, RomError> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+    chip8rom::from_hex(path, &data)
+}
+
+fn load_rom_stdin(hex: bool) -> Result<Rom, RomError> {
+    let mut data = Vec::new();
+    io::stdin().read_to_end(&mut data)?;
+    if hex {
+        chip8rom::from_hex("stdin", &data)
+    } else {
+        chip8rom::from_bytes("stdin", &data)
+    }
+}
+
+fn prompt_rom(names: &[String]) -> Result<String, RomError> {
+    for (i, name) in names.iter().enumerate() {
+        eprintln!("{:>3}) {}", i + 1, name);
+    }
+
+    let stdin = io::stdin();
+    loop {
+        eprint!("Pick a ROM [1-{}]: ", names.len());
+        io::stderr().flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Err(RomError::AmbiguousArchive(names.to_vec()));
+        }
+        match line.trim().parse::<usize>() {
+            Ok(n) if n >= 1 && n <= names.len() => return Ok(names[n - 1].clone()),
+            _ => continue,
+        }
+    }
 }